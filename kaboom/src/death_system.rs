@@ -2,6 +2,7 @@ use specs;
 use specs::{ReadStorage, WriteStorage, Fetch, FetchMut};
 use slog::Logger;
 
+use pk::TickCounter;
 use pk::cell_dweller::{CellDweller, CellDwellerMessage, SetPosMessage};
 use pk::globe::Globe;
 use pk::net::{SendMessageQueue, NodeResource, Destination, Transport, SendMessage, NetMarker};
@@ -37,11 +38,12 @@ impl<'a> specs::System<'a> for DeathSystem {
         Fetch<'a, NodeResource>,
         FetchMut<'a, SendMessageQueue<Message>>,
         ReadStorage<'a, NetMarker>,
+        Fetch<'a, TickCounter>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
         use specs::Join;
-        use rand::thread_rng;
+        use rand::{XorShiftRng, SeedableRng};
 
         let (
             mut healths,
@@ -52,6 +54,7 @@ impl<'a> specs::System<'a> for DeathSystem {
             node_resource,
             mut send_message_queue,
             net_markers,
+            tick_counter,
         ) = data;
 
         // Don't try to kill anyone off unless we own the world.
@@ -123,11 +126,24 @@ impl<'a> specs::System<'a> for DeathSystem {
                     }
                 };
 
+                // Seed the spawn point RNG with the world seed and the current tick,
+                // so that replaying a recording respawns fighters in the same places.
+                // Mixing in the player ID keeps fighters that die on the same tick apart,
+                // and (being offset by one) keeps the seed from ever being all zeros.
+                let seed = globe.spec().seed;
+                let ticks = tick_counter.ticks;
+                let mut rng = XorShiftRng::from_seed([
+                    seed,
+                    ticks as u32,
+                    (ticks >> 32) as u32,
+                    fighter.player_id.0 as u32 + 1,
+                ]);
+
                 // TODO: this is copy-pasted from `fighter.rs`. Factor it out somewhere
                 // into a function that knows how to spawn replacement fighters.
                 let new_fighter_pos = globe
                     .air_above_random_surface_dry_land(
-                        &mut thread_rng(),
+                        &mut rng,
                         2, // Min air cells above
                         5, // Max distance from starting point
                         50, // Max attempts
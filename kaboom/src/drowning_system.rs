@@ -0,0 +1,61 @@
+use specs;
+use specs::{ReadStorage, WriteStorage, Fetch};
+use slog::Logger;
+
+use pk::types::*;
+use pk::cell_dweller::CellDweller;
+use pk::net::NodeResource;
+
+use ::health::Health;
+
+// Health lost for every second a fighter spends out of breath.
+const DAMAGE_PER_GASP: i32 = 10;
+const SECONDS_BETWEEN_GASPS: TimeDelta = 1.0;
+
+/// Hurts fighters that have been under water for too long.
+pub struct DrowningSystem {
+    log: Logger,
+    seconds_until_next_gasp: TimeDelta,
+}
+
+impl DrowningSystem {
+    pub fn new(parent_log: &Logger) -> DrowningSystem {
+        DrowningSystem {
+            log: parent_log.new(o!()),
+            seconds_until_next_gasp: SECONDS_BETWEEN_GASPS,
+        }
+    }
+}
+
+impl<'a> specs::System<'a> for DrowningSystem {
+    type SystemData = (
+        Fetch<'a, TimeDeltaResource>,
+        ReadStorage<'a, CellDweller>,
+        WriteStorage<'a, Health>,
+        Fetch<'a, NodeResource>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        use specs::Join;
+
+        let (dt, cell_dwellers, mut healths, node_resource) = data;
+
+        // Only the master gets to decide who's been hurt.
+        if !node_resource.is_master {
+            return;
+        }
+
+        self.seconds_until_next_gasp -= dt.0;
+        if self.seconds_until_next_gasp > 0.0 {
+            return;
+        }
+        self.seconds_until_next_gasp = SECONDS_BETWEEN_GASPS;
+
+        for (cd, health) in (&cell_dwellers, &mut healths).join() {
+            if cd.is_out_of_breath() {
+                health.hp -= DAMAGE_PER_GASP;
+                debug!(self.log, "Fighter is drowning"; "hp" => health.hp);
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use specs;
+use specs::{ReadStorage, WriteStorage, Fetch};
+use slog::Logger;
+
+use pk::cell_dweller::{CellDweller, Landings};
+use pk::net::NodeResource;
+
+use ::health::Health;
+
+// Health lost for every cell fallen beyond what a fighter can safely drop.
+const DAMAGE_PER_EXCESS_CELL: i32 = 15;
+
+/// Hurts fighters that fall further than they can safely drop.
+pub struct FallDamageSystem {
+    log: Logger,
+}
+
+impl FallDamageSystem {
+    pub fn new(parent_log: &Logger, world: &mut specs::World) -> FallDamageSystem {
+        use pk::AutoResource;
+
+        Landings::ensure(world);
+
+        FallDamageSystem {
+            log: parent_log.new(o!()),
+        }
+    }
+}
+
+impl<'a> specs::System<'a> for FallDamageSystem {
+    type SystemData = (
+        Fetch<'a, Landings>,
+        ReadStorage<'a, CellDweller>,
+        WriteStorage<'a, Health>,
+        Fetch<'a, NodeResource>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (landings, cell_dwellers, mut healths, node_resource) = data;
+
+        // Only the master gets to decide who's been hurt.
+        if !node_resource.is_master {
+            return;
+        }
+
+        for landing in &landings.landings {
+            // Water breaks your fall.
+            if landing.in_water {
+                continue;
+            }
+            let max_safe_fall = match cell_dwellers.get(landing.entity) {
+                Some(cd) => cd.movement_profile().max_safe_fall,
+                None => continue,
+            };
+            let excess_cells = landing.cells_fallen - max_safe_fall;
+            if excess_cells <= 0 {
+                continue;
+            }
+            if let Some(health) = healths.get_mut(landing.entity) {
+                let damage = excess_cells as i32 * DAMAGE_PER_EXCESS_CELL;
+                health.hp -= damage;
+                debug!(self.log, "Fighter hurt by falling"; "cells_fallen" => landing.cells_fallen, "damage" => damage);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use slog;
+    use specs::RunNow;
+
+    use pk::AutoResource;
+    use pk::globe::Globe;
+    use pk::grid::{GridCoord, GridPoint3, Dir};
+    use pk::cell_dweller::{Landing, MovementProfile};
+
+    use super::*;
+
+    // Land a fighter who can safely fall three cells, and see how much health it has left.
+    fn hp_after_landing(cells_fallen: GridCoord, in_water: bool) -> i32 {
+        let log = slog::Logger::root(slog::Discard, o!());
+        let mut world = specs::World::new();
+        world.register::<CellDweller>();
+        world.register::<Health>();
+        let mut fall_damage_sys = FallDamageSystem::new(&log, &mut world);
+        NodeResource::ensure(&mut world).is_master = true;
+
+        let mut cd = CellDweller::new(GridPoint3::default(), Dir::default(), Globe::new_example().spec(), None);
+        cd.set_movement_profile(MovementProfile {
+            max_safe_fall: 3,
+            ..MovementProfile::default()
+        });
+        let entity = world.create_entity().with(cd).with(Health::new(100)).build();
+        world.write_resource::<Landings>().landings.push(Landing {
+            entity: entity,
+            cells_fallen: cells_fallen,
+            impact_speed: 10.0,
+            in_water: in_water,
+        });
+
+        fall_damage_sys.run_now(&world.res);
+        let healths = world.read::<Health>();
+        healths.get(entity).unwrap().hp
+    }
+
+    #[test]
+    fn short_falls_are_safe() {
+        assert_eq!(hp_after_landing(1, false), 100);
+        assert_eq!(hp_after_landing(3, false), 100);
+    }
+
+    #[test]
+    fn long_falls_hurt_more_the_further_they_are() {
+        assert_eq!(hp_after_landing(4, false), 100 - DAMAGE_PER_EXCESS_CELL);
+        assert_eq!(hp_after_landing(6, false), 100 - 3 * DAMAGE_PER_EXCESS_CELL);
+    }
+
+    #[test]
+    fn water_breaks_falls() {
+        assert_eq!(hp_after_landing(6, true), 100);
+    }
+}
@@ -67,19 +67,29 @@ pub fn create(
     let mut fighter_visual = render::Visual::new_empty();
     fighter_visual.proto_mesh = Some(render::make_axes_mesh());
 
-    let entity = entities.create();
-    updater.insert(entity, cell_dweller::CellDweller::new(
+    let mut cd = cell_dweller::CellDweller::new(
         fighter_pos,
         grid::Dir::default(),
         globe_spec,
         Some(globe_entity),
-    ));
+    );
+    // Let fighters get back out of the ocean, as long as they don't stay under too long.
+    cd.set_movement_profile(cell_dweller::MovementProfile {
+        can_swim: true,
+        max_breath_seconds: Some(10.0),
+        ..Default::default()
+    });
+
+    let entity = entities.create();
+    updater.insert(entity, cd);
     updater.insert(entity, fighter_visual);
     // The CellDweller's transformation will be set based
     // on its coordinates in cell space.
     updater.insert(entity, pk::Spatial::new(globe_entity, Iso3::identity()));
     // Give the fighter some starting health.
     updater.insert(entity, Health::new(100));
+    // Somewhere to keep blocks they dig up.
+    updater.insert(entity, cell_dweller::Inventory::new(10));
     updater.insert(entity, ::fighter::Fighter::new(player_id));
     entity
 }
@@ -6,7 +6,8 @@ use pk;
 use pk::globe::Globe;
 use pk::cell_dweller::{CellDweller, ActiveCellDweller};
 use pk::camera::DefaultCamera;
-use pk::net::{NodeResource, PeerId, NetworkPeers, Destination, Transport, SendMessageQueue, SendMessage, EntityIds, NetMarker};
+use pk::net::{NodeResource, PeerId, PeerJoined, Destination, Transport, SendMessageQueue, SendMessage, EntityIds, NetMarker};
+use pk::{EventChannel, ReaderId};
 
 use ::player::{self, Player, PlayerId, PlayerMessage};
 use ::game_state::GameState;
@@ -18,6 +19,7 @@ use ::message::Message;
 /// System to drive the top-level state machine for level and game state.
 pub struct GameSystem {
     log: Logger,
+    peer_joined_reader: ReaderId,
 }
 
 // TODO: split most of this out into a "new player" system.
@@ -33,9 +35,13 @@ impl GameSystem {
         ClientState::ensure(world);
         player::RecvMessageQueue::ensure(world);
         EntityIds::ensure(world);
+        pk::TickCounter::ensure(world);
+        pk::Replay::ensure(world);
+        let peer_joined_reader = EventChannel::<PeerJoined>::ensure(world).register_reader();
 
         GameSystem {
-            log: parent_log.new(o!("system" => "game"))
+            log: parent_log.new(o!("system" => "game")),
+            peer_joined_reader: peer_joined_reader,
         }
     }
 
@@ -100,11 +106,13 @@ impl<'a> specs::System<'a> for GameSystem {
         WriteStorage<'a, CellDweller>,
         ReadStorage<'a, Fighter>,
         FetchMut<'a, DefaultCamera>,
-        FetchMut<'a, NetworkPeers<Message>>,
         FetchMut<'a, SendMessageQueue<Message>>,
         FetchMut<'a, player::RecvMessageQueue>,
         FetchMut<'a, EntityIds>,
         ReadStorage<'a, NetMarker>,
+        Fetch<'a, pk::TickCounter>,
+        FetchMut<'a, pk::Replay>,
+        Fetch<'a, EventChannel<PeerJoined>>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -119,11 +127,13 @@ impl<'a> specs::System<'a> for GameSystem {
             cell_dwellers,
             fighters,
             mut default_camera,
-            mut network_peers,
             mut send_message_queue,
             mut player_recv_message_queue,
             mut entity_ids,
             net_markers,
+            tick_counter,
+            mut replay,
+            peer_joined_events,
         ) = data;
 
         // TODO: eventually only the server should create this, and then describe it to clients.
@@ -132,8 +142,10 @@ impl<'a> specs::System<'a> for GameSystem {
         if game_state.globe_entity.is_none() {
             // Create the globe first, because we'll need it to figure out where
             // to place the player character.
+            // TODO: random seed every time.
+            let seed = replay.choose_seed(tick_counter.ticks, 14).expect("Couldn't record world seed");
             game_state.globe_entity = Some(
-                planet::create(&entities, &updater)
+                planet::create(&entities, &updater, seed)
             );
 
             // Don't do anything else in the GameSystem for the rest of the frame.
@@ -244,10 +256,13 @@ impl<'a> specs::System<'a> for GameSystem {
             self.create_and_broadcast_player(&mut game_state, &mut send_message_queue, PeerId(0));
         }
 
-        // If there are any new network peers, then pop them off
-        // and maybe do something with them.
-        while let Some(new_peer_id) = network_peers.new_peers.pop_front() {
-            // As a client, we don't care; we just want to clean out the list.
+        // If there are any new network peers, then maybe do something with them.
+        let new_peer_ids: Vec<PeerId> = peer_joined_events
+            .read(&mut self.peer_joined_reader)
+            .map(|peer_joined| peer_joined.peer_id)
+            .collect();
+        for new_peer_id in new_peer_ids {
+            // As a client, we don't care.
             if node_resource.is_master {
                 // Tell the new peer about all existing players.
                 for player in &game_state.players {
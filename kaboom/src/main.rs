@@ -23,6 +23,8 @@ mod recv_demux_system;
 mod weapon;
 mod health;
 mod death_system;
+mod fall_damage_system;
+mod drowning_system;
 
 use std::sync::mpsc;
 
@@ -36,6 +38,28 @@ fn main() {
         .author("Jeff Parsons <jeff@parsons.io>")
         .about("Blow stuff up!")
         .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("bindings")
+                .long("bindings")
+                .value_name("FILE")
+                .help("Load key bindings from a JSON file")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("record")
+                .long("record")
+                .value_name("FILE")
+                .help("Record input and network messages to a file, for reproducing bugs")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("replay")
+                .long("replay")
+                .value_name("FILE")
+                .help("Replay a recording made with --record instead of taking input")
+                .takes_value(true)
+                .conflicts_with("record")
+        )
         .subcommand(
             SubCommand::with_name("connect")
                 .about("connect to a server")
@@ -55,19 +79,40 @@ fn main() {
         // a client to it.
         .get_matches();
 
-    // Set up input adapters.
     let (shoot_input_sender, shoot_input_receiver) = mpsc::channel();
-    let shoot_input_adapter = Box::new(weapon::ShootInputAdapter::new(shoot_input_sender));
 
-    let mut app = pk::AppBuilder::new()
-        .add_common_systems()
+    let mut app_builder = pk::AppBuilder::new();
+    if let Some(path) = matches.value_of("bindings") {
+        app_builder = app_builder.with_input_map_from(path).expect("Couldn't read key bindings");
+    }
+    if let Some(path) = matches.value_of("record") {
+        app_builder = app_builder.record_to(path).expect("Couldn't create recording file");
+    }
+    if let Some(path) = matches.value_of("replay") {
+        app_builder = app_builder.replay_from(path).expect("Couldn't read recording");
+    }
+
+    // Set up input adapters.
+    let input_map = {
+        use pk::AutoResource;
+        pk::InputMapResource::ensure(app_builder.world_mut()).input_map.clone()
+    };
+    let shoot_input_adapter = Box::new(weapon::ShootInputAdapter::new(shoot_input_sender, input_map));
+
+    let mut app = app_builder
+        .with_bundle(pk::cell_dweller::CellDwellerBundle::default())
+        .with_bundle(pk::globe::GlobeBundle {
+            // Let sand and gravel come tumbling down into craters.
+            falling_blocks: true,
+            ..pk::globe::GlobeBundle::default()
+        })
+        .with_bundle(pk::net::NetBundle::<Message>::new())
+        .add_input_adapter(shoot_input_adapter)
         .add_systems(|logger: &slog::Logger, world: &mut specs::World, dispatcher_builder: specs::DispatcherBuilder<'static, 'static>| {
             add_systems(logger, world, dispatcher_builder, shoot_input_receiver)
         })
         .build_gui();
 
-    app.add_input_adapter(shoot_input_adapter);
-
     // Should we start a server or connect to one?
     // NLL SVP.
     {
@@ -111,18 +156,26 @@ fn add_systems(
     world.register::<::health::Health>();
 
     let game_system = game_system::GameSystem::new(logger, world);
-    let new_peer_system = pk::net::NewPeerSystem::<Message>::new(logger, world);
-    let recv_system = pk::net::RecvSystem::<Message>::new(logger, world);
     let recv_demux_system = RecvDemuxSystem::new(logger, world);
     let cd_recv_system = pk::cell_dweller::RecvSystem::new(world, logger);
     let weapon_recv_system = weapon::RecvSystem::new(logger, world);
     let shoot_system = weapon::ShootSystem::new(world, shoot_input_receiver, logger);
-    let explode_system = weapon::ExplodeSystem::new(logger);
+    let explode_system = weapon::ExplodeSystem::new(logger, world);
     let death_system = death_system::DeathSystem::new(logger);
-    let velocity_system = pk::physics::VelocitySystem::new(logger);
-    let gravity_system = pk::physics::GravitySystem::new(logger);
+    let fall_damage_system = fall_damage_system::FallDamageSystem::new(logger, world);
+    let drowning_system = drowning_system::DrowningSystem::new(logger);
+    let mut velocity_system = pk::physics::VelocitySystem::new(logger);
+    let mut gravity_system = pk::physics::GravitySystem::new(logger);
+    let mut terrain_collision_system = pk::physics::TerrainCollisionSystem::new(logger, world);
+    // We need these to run in a specific place relative to our own systems,
+    // so add them ourselves rather than through `AppBuilder::add_auto_system`.
+    {
+        use pk::AutoSystem;
+        velocity_system.init(world);
+        gravity_system.init(world);
+        terrain_collision_system.init(world);
+    }
     let send_mux_system = SendMuxSystem::new(logger, world);
-    let send_system = pk::net::SendSystem::<Message>::new(logger, world);
 
     // TODO: these barriers are probably a bad idea;
     // we should be perfectly happy to render while we're sending
@@ -130,22 +183,23 @@ fn add_systems(
     // used as lifecycle hooks instead.
     dispatcher_builder
         .add(game_system, "kaboom_game", &[])
-        .add(new_peer_system, "new_peer_system", &[])
-        .add(recv_system, "net_recv", &[])
         .add(recv_demux_system, "recv_demux", &["net_recv"])
         .add_barrier()
         .add(cd_recv_system, "cd_recv", &[])
         .add(weapon_recv_system, "weapon_recv", &[])
         .add(shoot_system, "shoot_grenade", &[])
         .add(explode_system, "explode_grenade", &[])
-        .add(death_system, "death", &[])
-        .add(velocity_system, "velocity", &[])
+        .add(fall_damage_system, "fall_damage", &[])
+        .add(drowning_system, "drowning", &[])
+        .add(death_system, "death", &["fall_damage", "drowning"])
         .add(gravity_system, "gravity", &[])
+        .add(terrain_collision_system, "terrain_collision", &["gravity"])
+        .add(velocity_system, "velocity", &["terrain_collision"])
         // TODO: explicitly add all systems here,
         // instead of whatever "simple" wants to throw at you.
         // At the moment they might execute in an order that
         // could add unnecessary latency to receiving/sending messages.
         .add_barrier()
+        // `NetBundle` sends everything queued up here after this.
         .add(send_mux_system, "send_mux", &[])
-        .add(send_system, "net_send", &["send_mux"])
 }
@@ -8,6 +8,7 @@ use pk::globe::{Globe, Spec};
 pub fn create(
     entities: &Entities,
     updater: &Fetch<LazyUpdate>,
+    seed: u32,
 ) -> specs::Entity {
     // Make it small enough that you can find another person easily enough.
     // TODO: eventually make it scale to the number of players present at the start of each round.
@@ -16,8 +17,7 @@ pub fn create(
     let crust_depth = 25.0;
     let floor_radius = ocean_radius - crust_depth;
     let spec = Spec {
-        // TODO: random seed every time.
-        seed: 14,
+        seed: seed,
         floor_radius: floor_radius,
         ocean_radius: ocean_radius,
         block_height: 0.65,
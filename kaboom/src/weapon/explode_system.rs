@@ -1,14 +1,26 @@
 use specs;
-use specs::{Fetch, Entities, ReadStorage, WriteStorage};
+use specs::{Fetch, FetchMut, Entities, ReadStorage, WriteStorage};
 use slog::Logger;
 
 use pk::types::*;
 use pk::Spatial;
-use pk::net::NodeResource;
+use pk::globe::Globe;
+use pk::grid::PosInOwningRoot;
+use pk::cell_dweller::{self, CellDwellerMessage, RemoveBlocksMessage};
+use pk::net::{NodeResource, SendMessage, Destination, Transport};
+use pk::physics::TerrainContacts;
 
 use ::health::Health;
 use super::grenade::Grenade;
 
+// Anything within this distance of an exploding grenade gets damaged,
+// and any terrain whose cell centers are within it gets blown away.
+const BLAST_RADIUS: f64 = 2.5;
+
+// Grenades that hit the ground harder than this go off straight away,
+// rather than bouncing around until their fuse runs out.
+const IMPACT_DETONATION_SPEED: f64 = 8.0;
+
 pub struct ExplodeSystem {
     log: Logger,
 }
@@ -16,11 +28,65 @@ pub struct ExplodeSystem {
 impl ExplodeSystem {
     pub fn new(
         parent_log: &Logger,
+        world: &mut specs::World,
     ) -> ExplodeSystem {
+        use pk::AutoResource;
+
+        // We tell peers about terrain we destroy
+        // through the cell dweller message queue.
+        cell_dweller::SendMessageQueue::ensure(world);
+        TerrainContacts::ensure(world);
+
         ExplodeSystem {
             log: parent_log.new(o!()),
         }
     }
+
+    // Blow away all the terrain around the grenade, and tell everyone else
+    // which cells were destroyed.
+    //
+    // Only the master should do this.
+    fn blow_up_terrain(
+        &self,
+        globe: &mut Globe,
+        grenade_pos: Pt3,
+        send_message_queue: &mut cell_dweller::SendMessageQueue,
+    ) {
+        let cells = globe.cells_in_sphere(grenade_pos, BLAST_RADIUS);
+        // We can't destroy terrain that isn't loaded; that might change
+        // if we ever have a way to defer edits to unloaded chunks.
+        if !cells.unloaded.is_empty() {
+            debug!(self.log, "Explosion reached into unloaded chunks"; "cells" => cells.unloaded.len());
+        }
+        let removed = carve_crater(globe, &cells.loaded);
+        if removed.is_empty() || !send_message_queue.has_consumer {
+            return;
+        }
+        // Send the whole crater at once, rather than a message per cell.
+        send_message_queue.queue.push_back(
+            SendMessage {
+                destination: Destination::EveryoneElse,
+                game_message: CellDwellerMessage::RemoveBlocks(RemoveBlocksMessage {
+                    positions: removed.into_iter().map(|pos| pos.into()).collect(),
+                }),
+                transport: Transport::TCP,
+            }
+        );
+    }
+}
+
+// Remove every solid cell among the given cells,
+// and return the positions of those that were removed.
+fn carve_crater(globe: &mut Globe, cells: &[PosInOwningRoot]) -> Vec<PosInOwningRoot> {
+    let mut removed = Vec::new();
+    for &pos in cells {
+        if !globe.authoritative_cell(pos).material.is_solid() {
+            continue;
+        }
+        cell_dweller::remove_block(globe, pos);
+        removed.push(pos);
+    }
+    removed
 }
 
 impl<'a> specs::System<'a> for ExplodeSystem {
@@ -30,7 +96,10 @@ impl<'a> specs::System<'a> for ExplodeSystem {
         WriteStorage<'a, Grenade>,
         WriteStorage<'a, Health>,
         ReadStorage<'a, Spatial>,
+        WriteStorage<'a, Globe>,
         Fetch<'a, NodeResource>,
+        FetchMut<'a, cell_dweller::SendMessageQueue>,
+        Fetch<'a, TerrainContacts>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -43,9 +112,23 @@ impl<'a> specs::System<'a> for ExplodeSystem {
             mut grenades,
             mut healths,
             spatials,
+            mut globes,
             node_resource,
+            mut send_message_queue,
+            terrain_contacts,
         ) = data;
 
+        // Set off any grenades that slammed into the ground.
+        for contact in &terrain_contacts.contacts {
+            if contact.impact_speed < IMPACT_DETONATION_SPEED {
+                continue;
+            }
+            if let Some(grenade) = grenades.get_mut(contact.entity) {
+                trace!(self.log, "Grenade hit the ground hard"; "impact_speed" => contact.impact_speed);
+                grenade.time_to_live_seconds = 0.0;
+            }
+        }
+
         for (grenade_entity, grenade) in (&*entities, &mut grenades).join() {
             // Count down each grenade's timer, and remove it if
             // it's been alive too long.
@@ -63,7 +146,7 @@ impl<'a> specs::System<'a> for ExplodeSystem {
                 for (living_thing_entity, health, _spatial) in (&*entities, &mut healths, &spatials).join() {
                     let relative_transform = spatials.a_relative_to_b(living_thing_entity, grenade_entity);
                     let distance_squared = relative_transform.translation.vector.norm_squared();
-                    let blast_radius_squared = 2.5 * 2.5;
+                    let blast_radius_squared = BLAST_RADIUS * BLAST_RADIUS;
 
                     if distance_squared <= blast_radius_squared {
                         health.hp -= 100;
@@ -71,7 +154,92 @@ impl<'a> specs::System<'a> for ExplodeSystem {
                         debug!(self.log, "Damaged something!");
                     }
                 }
+
+                // Blow a hole in the globe the grenade is moving relative to.
+                let globe_entity = match spatials.get(grenade_entity).and_then(|spatial| spatial.parent_entity()) {
+                    Some(globe_entity) => globe_entity,
+                    None => {
+                        warn!(self.log, "Grenade isn't attached to anything; there's no terrain to blow up!");
+                        continue;
+                    }
+                };
+                let grenade_transform = spatials.a_relative_to_b(grenade_entity, globe_entity);
+                let grenade_pos = Pt3::from_coordinates(grenade_transform.translation.vector);
+                let globe = match globes.get_mut(globe_entity) {
+                    Some(globe) => globe,
+                    None => {
+                        warn!(self.log, "Grenade is attached to something that isn't a globe; there's no terrain to blow up!");
+                        continue;
+                    }
+                };
+                self.blow_up_terrain(globe, grenade_pos, &mut send_message_queue);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pk::globe::ChunkOrigin;
+    use pk::globe::chunk::Material;
+    use pk::grid::{GridPoint2, GridPoint3};
+
+    use super::*;
+
+    #[test]
+    fn crater_is_carved_on_both_sides_of_chunk_border() {
+        let mut globe = Globe::new_example();
+        let spec = globe.spec();
+        let chunk_resolution = spec.chunk_resolution;
+
+        // Find some land right on the border between two chunks, so the crater
+        // has to carve out cells that both chunks keep a copy of.
+        // Keep well away from the other edges of the chunks, too.
+        let border_x = chunk_resolution[0];
+        let land_pos = (0..spec.root_resolution[1])
+            .filter(|y| {
+                let y_in_chunk = y % chunk_resolution[1];
+                y_in_chunk >= 4 && y_in_chunk <= chunk_resolution[1] - 4
+            })
+            .filter_map(|y| globe.find_surface_dry_land(GridPoint2::new(0.into(), border_x, y), 2, 5))
+            .next()
+            .expect("Should have found some land on the chunk border");
+
+        let grenade_pos = spec.cell_center_center(land_pos);
+        for pos in globe.cells_in_sphere(grenade_pos, BLAST_RADIUS).unloaded {
+            let chunk_origin = globe.origin_of_chunk_owning(pos);
+            globe.ensure_chunk_present(chunk_origin);
+        }
+        let cells = globe.cells_in_sphere(grenade_pos, BLAST_RADIUS);
+        assert!(cells.unloaded.is_empty());
+
+        let removed = carve_crater(&mut globe, &cells.loaded);
+        assert!(removed.contains(&PosInOwningRoot::new(land_pos, spec.root_resolution)));
+
+        let mut border_cells_checked = 0;
+        for pos in removed {
+            let pos: GridPoint3 = pos.into();
+            assert_eq!(globe.authoritative_cell(PosInOwningRoot::new(pos, spec.root_resolution)).material, Material::Air);
+            if pos.x != border_x {
+                continue;
+            }
+            // The chunks on either side of the border should agree that it's gone.
+            for &chunk_x in &[border_x - chunk_resolution[0], border_x] {
+                let chunk_origin = ChunkOrigin::new(
+                    GridPoint3::new(
+                        pos.root,
+                        chunk_x,
+                        pos.y / chunk_resolution[1] * chunk_resolution[1],
+                        pos.z / chunk_resolution[2] * chunk_resolution[2],
+                    ),
+                    spec.root_resolution,
+                    chunk_resolution,
+                );
+                let chunk = globe.chunk_at(chunk_origin).expect("Chunk should have been loaded");
+                assert_eq!(chunk.cell(pos).material, Material::Air);
             }
+            border_cells_checked += 1;
         }
+        assert!(border_cells_checked > 0);
     }
 }
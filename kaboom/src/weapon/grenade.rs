@@ -13,6 +13,7 @@ use pk::render;
 use pk::cell_dweller::CellDweller;
 use pk::physics::Velocity;
 use pk::physics::Mass;
+use pk::physics::Collider;
 use pk::Spatial;
 
 use ::player::PlayerId;
@@ -96,5 +97,11 @@ pub fn shoot_grenade(
     updater.insert(entity, bullet_spatial);
     updater.insert(entity, bullet_velocity);
     updater.insert(entity, Mass{});
+    // Bounce off hills, losing a bit of speed each time.
+    updater.insert(entity, Collider::new(
+        0.1, // Radius
+        0.5, // Restitution
+        0.3, // Friction
+    ));
     updater.insert(entity, Grenade::new(fired_by_player_id));
 }
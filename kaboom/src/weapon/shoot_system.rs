@@ -1,4 +1,4 @@
-use std::sync::mpsc;
+use std::sync::{Arc, Mutex, mpsc};
 use specs;
 use specs::{Fetch, FetchMut, ReadStorage, WriteStorage};
 use slog::Logger;
@@ -7,6 +7,7 @@ use piston::input::Input;
 use pk::cell_dweller::ActiveCellDweller;
 use pk::types::*;
 use pk::input_adapter;
+use pk::InputMap;
 use pk::net::{SendMessageQueue, Destination, Transport, SendMessage, NetMarker};
 
 use super::{ShootGrenadeMessage, WeaponMessage};
@@ -14,32 +15,35 @@ use ::fighter::Fighter;
 use ::client_state::ClientState;
 use ::message::Message;
 
+pub const SHOOT_ACTION: &str = "shoot";
+
 pub struct ShootInputAdapter {
     sender: mpsc::Sender<ShootEvent>,
+    input_map: Arc<Mutex<InputMap>>,
 }
 
 impl ShootInputAdapter {
-    pub fn new(sender: mpsc::Sender<ShootEvent>) -> ShootInputAdapter {
-        ShootInputAdapter { sender: sender }
+    pub fn new(sender: mpsc::Sender<ShootEvent>, input_map: Arc<Mutex<InputMap>>) -> ShootInputAdapter {
+        use piston::input::Button::Keyboard;
+        use piston::input::keyboard::Key;
+
+        input_map
+            .lock()
+            .expect("Couldn't get lock on input map")
+            .set_default(SHOOT_ACTION, &[Keyboard(Key::Space)]);
+
+        ShootInputAdapter {
+            sender: sender,
+            input_map: input_map,
+        }
     }
 }
 
 impl input_adapter::InputAdapter for ShootInputAdapter {
     fn handle(&self, input_event: &Input) {
-        use piston::input::{Button, ButtonState};
-        use piston::input::keyboard::Key;
-
-        if let &Input::Button(button_args) = input_event {
-            if let Button::Keyboard(key) = button_args.button {
-                let is_down = match button_args.state {
-                    ButtonState::Press => true,
-                    ButtonState::Release => false,
-                };
-                match key {
-                    Key::Space => self.sender.send(ShootEvent(is_down)).unwrap(),
-                    _ => (),
-                }
-            }
+        let input_map = self.input_map.lock().expect("Couldn't get lock on input map");
+        if let Some((SHOOT_ACTION, is_down)) = input_map.action_for_input(input_event) {
+            self.sender.send(ShootEvent(is_down)).unwrap();
         }
     }
 }
@@ -0,0 +1,99 @@
+//! Generates a small globe and writes the geometry for its whole surface
+//! out to a Wavefront OBJ or PLY file, for inspecting in other tools.
+//!
+//! Usage:
+//!
+//! ```text
+//! cargo run --example export_globe -- <output.obj|output.ply> [--seed <seed>] [--root <0-4>]
+//! ```
+
+extern crate planetkit as pk;
+#[macro_use]
+extern crate slog;
+
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+use std::process;
+use std::str::FromStr;
+
+use pk::globe::Globe;
+use pk::grid::{Root, ROOTS};
+use pk::render::MeshFormat;
+
+const USAGE: &str = "Usage: export_globe <output.obj|output.ply> [--seed <seed>] [--root <0-4>]";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut output_path: Option<String> = None;
+    let mut seed: Option<u32> = None;
+    let mut roots: Vec<Root> = ROOTS.to_vec();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => {
+                seed = Some(parse_next(&mut args, "--seed"));
+            }
+            "--root" => {
+                let root_index: u8 = parse_next(&mut args, "--root");
+                if root_index as usize >= ROOTS.len() {
+                    exit_with_usage("Root must be between 0 and 4");
+                }
+                roots = vec![Root::new(root_index)];
+            }
+            _ if output_path.is_none() => output_path = Some(arg),
+            _ => exit_with_usage(&format!("Unexpected argument: {}", arg)),
+        }
+    }
+    let output_path = match output_path {
+        Some(output_path) => output_path,
+        None => exit_with_usage("No output file given"),
+    };
+    let format = match MeshFormat::from_path(&output_path) {
+        Some(format) => format,
+        None => exit_with_usage("Output file must end in .obj or .ply"),
+    };
+
+    let log = slog::Logger::root(slog::Discard, o!());
+
+    let mut spec = Globe::new_example().spec();
+    if let Some(seed) = seed {
+        spec.seed = seed;
+    }
+    let mut globe = Globe::new(spec);
+    pk::globe::ensure_surface_chunks_present(&mut globe, &roots);
+    let mesh = match pk::globe::make_loaded_chunks_mesh(&globe, &log) {
+        Some(mesh) => mesh,
+        None => {
+            eprintln!("Nothing to export");
+            process::exit(1);
+        }
+    };
+
+    let file = File::create(&output_path).unwrap_or_else(|err| {
+        eprintln!("Couldn't create {}: {}", output_path, err);
+        process::exit(1);
+    });
+    let mut writer = BufWriter::new(file);
+    if let Err(err) = pk::render::write_mesh(&mesh, format, &mut writer) {
+        eprintln!("Couldn't write {}: {}", output_path, err);
+        process::exit(1);
+    }
+    println!(
+        "Wrote {} vertices and {} triangles to {}",
+        mesh.vertexes.len(),
+        mesh.indexes.len() / 3,
+        output_path
+    );
+}
+
+fn parse_next<T: FromStr, I: Iterator<Item = String>>(args: &mut I, flag: &str) -> T {
+    match args.next().and_then(|value| value.parse().ok()) {
+        Some(value) => value,
+        None => exit_with_usage(&format!("{} needs a number", flag)),
+    }
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2);
+}
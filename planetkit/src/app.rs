@@ -1,6 +1,6 @@
 use std::sync::{Arc, Mutex, mpsc};
 use piston_window::PistonWindow;
-use piston::input::{UpdateArgs, RenderArgs};
+use piston::input::{Input, UpdateArgs, RenderArgs};
 use slog::Logger;
 use gfx;
 use gfx_device_gl;
@@ -11,6 +11,9 @@ use render;
 use render::{Visual, Mesh, MeshRepository};
 use types::*;
 use input_adapter::InputAdapter;
+use fixed_timestep::{FixedTimestep, TickCounter, InterpolationAlpha};
+use replay::Replay;
+use auto_system::ensure_component_registered;
 
 fn get_projection(w: &PistonWindow) -> [[f32; 4]; 4] {
     use piston::window::Window;
@@ -29,7 +32,11 @@ pub struct App {
     t: TimeDelta,
     log: Logger,
     world: specs::World,
-    dispatcher: specs::Dispatcher<'static, 'static>,
+    // Systems that run once per fixed-length tick of the simulation.
+    tick_dispatcher: specs::Dispatcher<'static, 'static>,
+    // Systems that run once per frame, after any ticks for that frame.
+    frame_dispatcher: specs::Dispatcher<'static, 'static>,
+    fixed_timestep: FixedTimestep,
     encoder_channel: render::EncoderChannel<gfx_device_gl::Resources, gfx_device_gl::CommandBuffer>,
     input_adapters: Vec<Box<InputAdapter>>,
     // TEMP: Share with rendering system until the rendering system
@@ -50,14 +57,26 @@ pub struct App {
 }
 
 impl App {
-    // Add all your systems before passing the dispatcher in.
+    // Add all your systems before passing the dispatchers in.
     pub fn new(
         parent_log: &Logger,
         mut window: PistonWindow,
         mut world: specs::World,
-        dispatcher_builder: specs::DispatcherBuilder<'static, 'static>,
+        tick_dispatcher_builder: specs::DispatcherBuilder<'static, 'static>,
+        frame_dispatcher_builder: specs::DispatcherBuilder<'static, 'static>,
+        fixed_timestep: FixedTimestep,
     ) -> App {
         use camera_controllers::{FirstPersonSettings, FirstPerson};
+        use auto_resource::AutoResource;
+
+        TickCounter::ensure(&mut world);
+        InterpolationAlpha::ensure(&mut world);
+        Replay::ensure(&mut world);
+
+        // The render system isn't added like other systems,
+        // so it has to register what it uses itself.
+        ensure_component_registered::<Visual>(&mut world);
+        ensure_component_registered::<::Spatial>(&mut world);
 
         // Rendering system, with bi-directional channel to pass
         // encoder back and forth between this thread (which owns
@@ -118,7 +137,7 @@ impl App {
             mesh_repo_ptr.clone(),
         );
 
-        let dispatcher_builder = dispatcher_builder
+        let frame_dispatcher_builder = frame_dispatcher_builder
             // Wait for unknown systems to finish before rendering.
             .add_barrier()
             .add(render_sys, "render", &[]);
@@ -127,7 +146,9 @@ impl App {
             t: 0.0,
             log: log,
             world: world,
-            dispatcher: dispatcher_builder.build(),
+            tick_dispatcher: tick_dispatcher_builder.build(),
+            frame_dispatcher: frame_dispatcher_builder.build(),
+            fixed_timestep: fixed_timestep,
             encoder_channel: device_encoder_channel,
             input_adapters: Vec::new(),
             projection: projection,
@@ -164,12 +185,14 @@ impl App {
 
             // Dispatch input events to any systems that care.
             if let Event::Input(input) = e {
-                for adapter in &self.input_adapters {
-                    adapter.handle(&input);
-                }
+                self.handle_live_input(&input);
             }
         }
 
+        if let Err(err) = self.world.write_resource::<Replay>().flush() {
+            warn!(self.log, "Failed to write recording"; "error" => format!("{}", err));
+        }
+
         info!(self.log, "Quitting");
     }
 
@@ -201,13 +224,65 @@ impl App {
     fn update(&mut self, args: &UpdateArgs) {
         self.t += args.dt;
 
+        // Catch the simulation up to real time in fixed-length ticks,
+        // so that it behaves the same regardless of frame rate.
+        let ticks = self.fixed_timestep.advance(args.dt);
+        for _ in 0..ticks {
+            self.replay_input();
+            self.world.write_resource::<TimeDeltaResource>().0 = self.fixed_timestep.seconds_per_tick();
+            self.tick_dispatcher.dispatch(&mut self.world.res);
+            self.world.maintain();
+            self.world.write_resource::<TickCounter>().ticks += 1;
+        }
+
+        // Then do everything that only needs doing once per frame.
+        self.world.write_resource::<InterpolationAlpha>().alpha = self.fixed_timestep.alpha();
         self.world.write_resource::<TimeDeltaResource>().0 = args.dt;
-        self.dispatcher.dispatch(&mut self.world.res);
+        self.frame_dispatcher.dispatch(&mut self.world.res);
         self.world.maintain();
 
+        {
+            let mut replay = self.world.write_resource::<Replay>();
+            if let Err(err) = replay.flush() {
+                warn!(self.log, "Failed to write recording"; "error" => format!("{}", err));
+            }
+            if replay.finish_if_done() {
+                info!(self.log, "Finished replaying recording; back to live input");
+            }
+        }
+
         self.realize_proto_meshes();
     }
 
+    fn handle_live_input(&mut self, input: &Input) {
+        let tick = self.world.read_resource::<TickCounter>().ticks;
+        let mut replay = self.world.write_resource::<Replay>();
+        // Only the recording gets to drive the simulation while replaying.
+        if replay.is_replaying() {
+            return;
+        }
+        // Systems pick up input from their adapters when they next run,
+        // so stamp it with the next tick to be simulated.
+        if let Err(err) = replay.record_input(tick, input) {
+            warn!(self.log, "Failed to record input"; "error" => format!("{}", err));
+        }
+        for adapter in &self.input_adapters {
+            adapter.handle(input);
+        }
+    }
+
+    // Feed recorded input to adapters as if it had just happened,
+    // right before the tick it was originally handled in.
+    fn replay_input(&mut self) {
+        let tick = self.world.read_resource::<TickCounter>().ticks;
+        let mut replay = self.world.write_resource::<Replay>();
+        while let Some(input) = replay.next_input(tick) {
+            for adapter in &self.input_adapters {
+                adapter.handle(&input);
+            }
+        }
+    }
+
     // This whole thing is a horrible hack around
     // not being able to create GL resource factories
     // on other threads. It's acting as a proof that
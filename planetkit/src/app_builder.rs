@@ -1,4 +1,9 @@
-use std::sync::mpsc;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter};
+use std::mem;
+use std::path::Path;
 
 use slog;
 #[cfg(not(target_os="emscripten"))] use slog_term;
@@ -8,7 +13,14 @@ use specs;
 
 use ::types::*;
 use ::app::App;
-use ::cell_dweller;
+use ::auto_system::{AutoSystem, PendingSystem, add_pending_systems};
+use ::fixed_timestep::{FixedTimestep, DEFAULT_TICKS_PER_SECOND};
+use ::replay::{Replay, Recorder, Recording, RecordingHeader};
+use ::input_adapter::InputAdapter;
+use ::input_map::{InputMap, InputMapResource};
+use ::bundle::Bundle;
+use ::cell_dweller::CellDwellerBundle;
+use ::globe::GlobeBundle;
 use ::window;
 
 /// Builder for [`App`].
@@ -24,9 +36,20 @@ pub struct AppBuilder {
     root_log: slog::Logger,
     world: specs::World,
     dispatcher_builder: shred::DispatcherBuilder<'static, 'static>,
-    // We may or may not create these, depending on the game.
-    movement_input_adapter: Option<Box<cell_dweller::MovementInputAdapter>>,
-    mining_input_adapter: Option<Box<cell_dweller::MiningInputAdapter>>,
+    frame_dispatcher_builder: shred::DispatcherBuilder<'static, 'static>,
+    ticks_per_second: f64,
+    // Set if we're replaying a recording, which has to run
+    // at the tick rate and with the bindings it was recorded with.
+    recorded_header: Option<RecordingHeader>,
+    recording_file: Option<File>,
+    // Auto systems waiting to be added to the dispatchers,
+    // and the names of those that already have been.
+    pending_systems: Vec<PendingSystem>,
+    pending_frame_systems: Vec<PendingSystem>,
+    pending_final_systems: Vec<PendingSystem>,
+    auto_system_names: HashSet<&'static str>,
+    auto_frame_system_names: HashSet<&'static str>,
+    input_adapters: Vec<Box<InputAdapter>>,
 }
 
 impl AppBuilder {
@@ -48,18 +71,9 @@ impl AppBuilder {
         let drain = slog::Discard;
         let root_log = slog::Logger::root(drain, o!("pk_version" => env!("CARGO_PKG_VERSION")));
 
-        // Create world and register all component types.
-        // TODO: move component type registration elsewhere;
-        // AutoSystems that use them should ensure that they are registered.
+        // Create world. Component types are registered by
+        // the `AutoSystem`s that use them as they are added.
         let mut world = specs::World::new();
-        world.register::<::cell_dweller::CellDweller>();
-        world.register::<::render::Visual>();
-        world.register::<::Spatial>();
-        world.register::<::physics::Velocity>();
-        world.register::<::physics::Mass>();
-        world.register::<::globe::Globe>();
-        world.register::<::globe::ChunkView>();
-        world.register::<::net::NetMarker>();
 
         // Initialize common resources.
         // These should be impossible to create from
@@ -74,76 +88,212 @@ impl AppBuilder {
             root_log: root_log,
             world: world,
             dispatcher_builder: specs::DispatcherBuilder::new(),
-            movement_input_adapter: None,
-            mining_input_adapter: None,
+            frame_dispatcher_builder: specs::DispatcherBuilder::new(),
+            ticks_per_second: DEFAULT_TICKS_PER_SECOND,
+            recorded_header: None,
+            recording_file: None,
+            pending_systems: Vec::new(),
+            pending_frame_systems: Vec::new(),
+            pending_final_systems: Vec::new(),
+            auto_system_names: HashSet::new(),
+            auto_frame_system_names: HashSet::new(),
+            input_adapters: Vec::new(),
         }
     }
 
-    pub fn build_gui(self) -> App {
+    pub fn build_gui(mut self) -> App {
+        use ::AutoResource;
+
+        self.flush_auto_systems();
+        if !self.pending_final_systems.is_empty() {
+            let pending_final_systems = mem::replace(&mut self.pending_final_systems, Vec::new());
+            let dispatcher_builder = mem::replace(&mut self.dispatcher_builder, specs::DispatcherBuilder::new())
+                .add_barrier();
+            self.dispatcher_builder = add_pending_systems(
+                pending_final_systems,
+                &mut self.auto_system_names,
+                dispatcher_builder,
+            );
+        }
+
+        let ticks_per_second = self.recorded_header
+            .as_ref()
+            .map_or(self.ticks_per_second, |header| header.ticks_per_second);
+
+        // Input adapters have set up all their default bindings by now,
+        // so this is the last word on what every button does.
+        let input_map = InputMapResource::ensure(&mut self.world).input_map.clone();
+        if let Some(header) = self.recorded_header.take() {
+            *input_map.lock().expect("Couldn't get lock on input map") = header.input_map;
+        }
+
+        if let Some(recording_file) = self.recording_file.take() {
+            let header = RecordingHeader {
+                ticks_per_second: ticks_per_second,
+                input_map: input_map.lock().expect("Couldn't get lock on input map").clone(),
+            };
+            let recorder = Recorder::new(&header, BufWriter::new(recording_file))
+                .expect("Couldn't write recording header");
+            Replay::ensure(&mut self.world).start_recording(recorder);
+        }
+
         // TODO: move that function into this file; it doesn't need its own module.
         let window = window::make_window(&self.root_log);
 
         // TODO: hand the root log over to App, rather than making it borrow it.
-        let mut app = App::new(&self.root_log, window, self.world, self.dispatcher_builder);
-        if let Some(movement_input_adapter) = self.movement_input_adapter {
-            app.add_input_adapter(movement_input_adapter);
-        }
-        if let Some(mining_input_adapter) = self.mining_input_adapter {
-            app.add_input_adapter(mining_input_adapter);
+        let mut app = App::new(
+            &self.root_log,
+            window,
+            self.world,
+            self.dispatcher_builder,
+            self.frame_dispatcher_builder,
+            FixedTimestep::new(ticks_per_second),
+        );
+        for input_adapter in self.input_adapters {
+            app.add_input_adapter(input_adapter);
         }
         app
     }
 
+    /// Add systems that advance the simulation. These run once per fixed-length tick,
+    /// which might be any number of times per frame; see `with_tick_rate`.
     pub fn add_systems<F: AddSystemsFn<'static, 'static>>(mut self, add_systems_fn: F) -> Self {
+        // Let these depend on any auto systems added before them.
+        self.flush_auto_systems();
         self.dispatcher_builder = add_systems_fn(&self.root_log, &mut self.world, self.dispatcher_builder);
         self
     }
 
-    /// Add a few systems that you're likely to want, especially if you're just getting
-    /// started with PlanetKit and want to get up and running quickly.
-    pub fn add_common_systems(mut self) -> Self {
-        use ::globe;
+    /// Add systems that only need to run once per frame, after any ticks for that frame,
+    /// e.g. to build geometry for things that are about to be drawn.
+    pub fn add_frame_systems<F: AddSystemsFn<'static, 'static>>(mut self, add_systems_fn: F) -> Self {
+        self.flush_auto_systems();
+        self.frame_dispatcher_builder = add_systems_fn(&self.root_log, &mut self.world, self.frame_dispatcher_builder);
+        self
+    }
 
-        // Set up input adapters.
-        let (movement_input_sender, movement_input_receiver) = mpsc::channel();
-        self.movement_input_adapter = Some(Box::new(cell_dweller::MovementInputAdapter::new(movement_input_sender)));
+    /// Initialize a system, and add it to run once per tick (like `add_systems`)
+    /// after any other auto systems it depends on.
+    ///
+    /// Systems added through `add_systems` after this can depend on it by name.
+    pub fn add_auto_system<S: AutoSystem>(mut self, system: S) -> Self {
+        let pending_system = PendingSystem::new(system, &mut self.world);
+        self.pending_systems.push(pending_system);
+        self
+    }
 
-        let (mining_input_sender, mining_input_receiver) = mpsc::channel();
-        self.mining_input_adapter = Some(Box::new(cell_dweller::MiningInputAdapter::new(mining_input_sender)));
+    /// Like `add_auto_system`, but for systems that only need to run
+    /// once per frame (like `add_frame_systems`).
+    pub fn add_auto_frame_system<S: AutoSystem>(mut self, system: S) -> Self {
+        let pending_system = PendingSystem::new(system, &mut self.world);
+        self.pending_frame_systems.push(pending_system);
+        self
+    }
 
-        let mut movement_sys = cell_dweller::MovementSystem::new(&mut self.world, movement_input_receiver, &self.root_log);
-        // TODO: move into AutoSystem
-        movement_sys.init(&mut self.world);
+    /// Like `add_auto_system`, but runs after every other system added
+    /// for each tick, regardless of when it is added; e.g. to send out
+    /// network messages queued up by everything else.
+    pub fn add_final_auto_system<S: AutoSystem>(mut self, system: S) -> Self {
+        let pending_system = PendingSystem::new(system, &mut self.world);
+        self.pending_final_systems.push(pending_system);
+        self
+    }
 
-        let mut mining_sys = cell_dweller::MiningSystem::new(mining_input_receiver, &self.root_log);
-        // TODO: move into AutoSystem
-        mining_sys.init(&mut self.world);
+    /// Add all the systems, input adapters, etc. in a bundle.
+    pub fn with_bundle<B: Bundle>(self, bundle: B) -> Self {
+        bundle.add_to(self)
+    }
 
-        let physics_sys = cell_dweller::PhysicsSystem::new(
-            &self.root_log,
-            0.1, // Seconds between falls
-        );
+    pub fn add_input_adapter(mut self, input_adapter: Box<InputAdapter>) -> Self {
+        self.input_adapters.push(input_adapter);
+        self
+    }
 
-        let chunk_sys = globe::ChunkSystem::new(&self.root_log);
+    /// Logger that systems added to the app should derive their own from.
+    pub fn root_log(&self) -> &slog::Logger {
+        &self.root_log
+    }
 
-        let chunk_view_sys = globe::ChunkViewSystem::new(
-            &self.root_log,
-            0.05, // Seconds between geometry creation
+    /// Access the world before the app is built, e.g. to set up
+    /// resources that systems in a bundle will need.
+    pub fn world_mut(&mut self) -> &mut specs::World {
+        &mut self.world
+    }
+
+    // Add any pending auto systems to their dispatchers. Until this happens,
+    // they can still be reordered to come after each other's dependencies.
+    fn flush_auto_systems(&mut self) {
+        let pending_systems = mem::replace(&mut self.pending_systems, Vec::new());
+        let dispatcher_builder = mem::replace(&mut self.dispatcher_builder, specs::DispatcherBuilder::new());
+        self.dispatcher_builder = add_pending_systems(pending_systems, &mut self.auto_system_names, dispatcher_builder);
+
+        let pending_frame_systems = mem::replace(&mut self.pending_frame_systems, Vec::new());
+        let frame_dispatcher_builder = mem::replace(&mut self.frame_dispatcher_builder, specs::DispatcherBuilder::new());
+        self.frame_dispatcher_builder = add_pending_systems(
+            pending_frame_systems,
+            &mut self.auto_frame_system_names,
+            frame_dispatcher_builder,
         );
+    }
+
+    /// Set how many times per second to run the systems added with `add_systems`.
+    ///
+    /// Defaults to `DEFAULT_TICKS_PER_SECOND`.
+    pub fn with_tick_rate(mut self, ticks_per_second: f64) -> Self {
+        self.ticks_per_second = ticks_per_second;
+        self
+    }
+
+    /// Load the player's own key bindings from the given file;
+    /// see `InputMap::read` for the format.
+    ///
+    /// Bindings in the file take precedence over the defaults
+    /// set up by input adapters, regardless of what order they're added in.
+    pub fn with_input_map_from<P: AsRef<Path>>(mut self, path: P) -> io::Result<Self> {
+        use ::AutoResource;
+
+        let loaded_input_map = InputMap::read(BufReader::new(File::open(path)?))?;
+        InputMapResource::ensure(&mut self.world)
+            .input_map
+            .lock()
+            .expect("Couldn't get lock on input map")
+            .override_with(loaded_input_map);
+        Ok(self)
+    }
+
+    /// Record all input, received network messages, and world seeds
+    /// to the given file, so that the session can be replayed later
+    /// with `replay_from`.
+    pub fn record_to<P: AsRef<Path>>(mut self, path: P) -> io::Result<Self> {
+        self.recording_file = Some(File::create(path)?);
+        Ok(self)
+    }
 
-        self.add_systems(|_logger: &slog::Logger, _world: &mut specs::World, dispatcher_builder: specs::DispatcherBuilder<'static, 'static>| {
-            dispatcher_builder
-                // Try to get stuff most directly linked to input done first
-                // to avoid another frame of lag.
-                .add(movement_sys, "cd_movement", &[])
-                .add(mining_sys, "cd_mining", &["cd_movement"])
-                .add_barrier()
-                .add(physics_sys, "physics", &[])
-                .add(chunk_sys, "chunk", &[])
-                // Don't depend on chunk system; chunk view can lag happily, so we'd prefer
-                // to be able to run it in parallel.
-                .add(chunk_view_sys, "chunk_view", &[])
-        })
+    /// Replay a session recorded with `record_to` instead of taking live input
+    /// or network messages, then hand control back once the recording runs out.
+    ///
+    /// For the replay to match the original session, the app must be set up
+    /// with the same systems, and any globes created with seeds chosen through `Replay`.
+    /// The tick rate and key bindings are always taken from the recording, and the
+    /// bindings stay in effect after the recording runs out.
+    pub fn replay_from<P: AsRef<Path>>(mut self, path: P) -> io::Result<Self> {
+        use ::AutoResource;
+
+        let recording = Recording::read(BufReader::new(File::open(path)?))?;
+        info!(self.root_log, "Replaying recording"; "events" => recording.events.len());
+        self.recorded_header = Some(recording.header.clone());
+        Replay::ensure(&mut self.world).start_replaying(recording);
+        Ok(self)
+    }
+
+    /// Add a few systems that you're likely to want, especially if you're just getting
+    /// started with PlanetKit and want to get up and running quickly.
+    ///
+    /// This is just the default `CellDwellerBundle` and `GlobeBundle`;
+    /// add those yourself instead if you want to configure them.
+    pub fn add_common_systems(self) -> Self {
+        self.with_bundle(CellDwellerBundle::default())
+            .with_bundle(GlobeBundle::default())
     }
 }
 
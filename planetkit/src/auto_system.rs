@@ -0,0 +1,219 @@
+use std::any::TypeId;
+use std::collections::HashSet;
+
+use specs;
+
+use auto_resource::AutoResource;
+
+/// `System`s that know how to set up everything they need in a `World`,
+/// and which other systems they need to run after.
+///
+/// This is to `System`s what `AutoResource` is to resources: add them with
+/// `AppBuilder::add_auto_system`, and it will take care of initializing them
+/// and adding them to the dispatcher in an order that respects their dependencies.
+pub trait AutoSystem: for<'a> specs::System<'a> + Send + 'static {
+    /// Name to add the system to the dispatcher under.
+    /// Other systems can use this to depend on it.
+    fn name(&self) -> &'static str;
+
+    /// Names of systems that need to finish before this one runs, if present.
+    ///
+    /// Only systems added through `AppBuilder::add_auto_system` (or `add_auto_frame_system`)
+    /// are known about; dependencies on anything else are ignored.
+    fn dependencies(&self) -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// Register any component types and ensure any resources this system uses.
+    ///
+    /// Use `ensure_component_registered` rather than `World::register`, because
+    /// other systems will probably need some of the same components.
+    fn init(&mut self, world: &mut specs::World);
+}
+
+/// Register component type `T` with the world, unless it has already been
+/// registered through this function.
+///
+/// Specs won't let the same component type be registered twice,
+/// so every system that needs `T` should use this to register it.
+pub fn ensure_component_registered<T>(world: &mut specs::World)
+where
+    T: specs::Component,
+    T::Storage: Default,
+{
+    let is_new = RegisteredComponents::ensure(world)
+        .component_types
+        .insert(TypeId::of::<T>());
+    if is_new {
+        world.register::<T>();
+    }
+}
+
+// `World`-global resource tracking which component types
+// have been registered by `ensure_component_registered`.
+struct RegisteredComponents {
+    component_types: HashSet<TypeId>,
+}
+
+impl AutoResource for RegisteredComponents {
+    fn new(_world: &mut specs::World) -> RegisteredComponents {
+        RegisteredComponents { component_types: HashSet::new() }
+    }
+}
+
+// Lets us hold on to systems of different types until we know
+// what order to add them to the dispatcher in.
+trait AddToDispatcher {
+    fn add_to(
+        self: Box<Self>,
+        name: &str,
+        dependencies: &[&str],
+        dispatcher_builder: specs::DispatcherBuilder<'static, 'static>,
+    ) -> specs::DispatcherBuilder<'static, 'static>;
+}
+
+impl<S: AutoSystem> AddToDispatcher for S {
+    fn add_to(
+        self: Box<Self>,
+        name: &str,
+        dependencies: &[&str],
+        dispatcher_builder: specs::DispatcherBuilder<'static, 'static>,
+    ) -> specs::DispatcherBuilder<'static, 'static> {
+        dispatcher_builder.add(*self, name, dependencies)
+    }
+}
+
+/// An `AutoSystem` that has been initialized, but not yet added to a dispatcher.
+pub struct PendingSystem {
+    name: &'static str,
+    dependencies: Vec<&'static str>,
+    system: Box<AddToDispatcher>,
+}
+
+impl PendingSystem {
+    pub fn new<S: AutoSystem>(mut system: S, world: &mut specs::World) -> PendingSystem {
+        system.init(world);
+        PendingSystem {
+            name: system.name(),
+            dependencies: system.dependencies(),
+            system: Box::new(system),
+        }
+    }
+}
+
+/// Add pending systems to the dispatcher, each after everything it depends on.
+///
+/// `added_names` holds the names of systems that have already been added this way;
+/// dependencies on systems that are neither pending nor in there are dropped.
+/// Otherwise systems are added in the order they were given.
+///
+/// # Panics
+///
+/// Panics if there is a cycle of dependencies.
+pub fn add_pending_systems(
+    mut pending: Vec<PendingSystem>,
+    added_names: &mut HashSet<&'static str>,
+    mut dispatcher_builder: specs::DispatcherBuilder<'static, 'static>,
+) -> specs::DispatcherBuilder<'static, 'static> {
+    for pending_system in order_pending_systems(&mut pending) {
+        let known_dependencies: Vec<&str> = pending_system
+            .dependencies
+            .iter()
+            .cloned()
+            .filter(|dependency| added_names.contains(dependency))
+            .collect();
+        dispatcher_builder = pending_system.system.add_to(
+            pending_system.name,
+            &known_dependencies,
+            dispatcher_builder,
+        );
+        added_names.insert(pending_system.name);
+    }
+    dispatcher_builder
+}
+
+// Pull systems out of `pending` in an order where each comes after
+// anything it depends on that is also pending.
+fn order_pending_systems(pending: &mut Vec<PendingSystem>) -> Vec<PendingSystem> {
+    let mut ordered: Vec<PendingSystem> = Vec::new();
+    while !pending.is_empty() {
+        let next_index = pending.iter().position(|candidate| {
+            candidate.dependencies.iter().all(|dependency| {
+                !pending.iter().any(|other| other.name == *dependency)
+            })
+        });
+        match next_index {
+            Some(next_index) => ordered.push(pending.remove(next_index)),
+            None => {
+                let names: Vec<&str> = pending.iter().map(|pending_system| pending_system.name).collect();
+                panic!("Systems have cyclic dependencies: {:?}", names);
+            }
+        }
+    }
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct DummySystem {
+        name: &'static str,
+        dependencies: Vec<&'static str>,
+    }
+
+    impl<'a> specs::System<'a> for DummySystem {
+        type SystemData = ();
+
+        fn run(&mut self, _data: Self::SystemData) {}
+    }
+
+    impl AutoSystem for DummySystem {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn dependencies(&self) -> Vec<&'static str> {
+            self.dependencies.clone()
+        }
+
+        fn init(&mut self, world: &mut specs::World) {
+            ensure_component_registered::<::Spatial>(world);
+        }
+    }
+
+    fn pending(world: &mut specs::World, name: &'static str, dependencies: Vec<&'static str>) -> PendingSystem {
+        PendingSystem::new(DummySystem { name: name, dependencies: dependencies }, world)
+    }
+
+    #[test]
+    fn systems_come_after_their_dependencies() {
+        let mut world = specs::World::new();
+        let mut pending_systems = vec![
+            pending(&mut world, "c", vec!["b", "not_present"]),
+            pending(&mut world, "a", vec![]),
+            pending(&mut world, "b", vec!["a"]),
+            pending(&mut world, "d", vec![]),
+        ];
+        let ordered = order_pending_systems(&mut pending_systems);
+        let names: Vec<&str> = ordered.iter().map(|pending_system| pending_system.name).collect();
+        assert_eq!(names, vec!["a", "b", "c", "d"]);
+
+        // Should be able to build a dispatcher out of them, too,
+        // even though one depends on a system that isn't there.
+        let mut added_names = HashSet::new();
+        let _dispatcher = add_pending_systems(ordered, &mut added_names, specs::DispatcherBuilder::new()).build();
+        assert_eq!(added_names.len(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "cyclic")]
+    fn cyclic_dependencies_are_rejected() {
+        let mut world = specs::World::new();
+        let mut pending_systems = vec![
+            pending(&mut world, "a", vec!["b"]),
+            pending(&mut world, "b", vec!["a"]),
+        ];
+        order_pending_systems(&mut pending_systems);
+    }
+}
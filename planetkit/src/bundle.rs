@@ -0,0 +1,19 @@
+use app_builder::AppBuilder;
+
+/// A package of related systems, input adapters, components, and resources
+/// that can be added to an `AppBuilder` in one go, e.g. everything needed
+/// for the player to walk around as a `CellDweller`.
+///
+/// Bundles are plain structs, so games can configure them through their fields
+/// before adding them, or leave a bundle out entirely and add its pieces
+/// (or their own replacements) by hand.
+///
+/// See `cell_dweller::CellDwellerBundle`, `globe::GlobeBundle`, and `net::NetBundle`.
+pub trait Bundle {
+    /// Add everything in the bundle to the app.
+    ///
+    /// Systems should be added with `AppBuilder::add_auto_system`
+    /// (or one of its variants) so that they don't depend on which
+    /// other bundles happen to have been added before them.
+    fn add_to(self, app_builder: AppBuilder) -> AppBuilder;
+}
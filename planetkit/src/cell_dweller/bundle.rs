@@ -0,0 +1,63 @@
+use std::sync::mpsc;
+
+use app_builder::AppBuilder;
+use bundle::Bundle;
+use input_map::InputMapResource;
+use super::{
+    MovementSystem,
+    MovementInputAdapter,
+    MiningSystem,
+    MiningInputAdapter,
+    PhysicsSystem,
+};
+
+/// Lets the player walk, jump, swim, dig, and build as the `ActiveCellDweller`.
+///
+/// Includes input adapters for movement and mining, with default key bindings
+/// that can be overridden through the `InputMap`.
+pub struct CellDwellerBundle {
+    /// How quickly cell dwellers speed up when they fall, in cells per second squared.
+    pub gravity: f64,
+    /// The fastest cell dwellers can fall, in cells per second.
+    pub terminal_velocity: f64,
+}
+
+impl Default for CellDwellerBundle {
+    fn default() -> CellDwellerBundle {
+        CellDwellerBundle {
+            gravity: 20.0,
+            terminal_velocity: 30.0,
+        }
+    }
+}
+
+impl Bundle for CellDwellerBundle {
+    fn add_to(self, mut app_builder: AppBuilder) -> AppBuilder {
+        use ::AutoResource;
+
+        let log = app_builder.root_log().clone();
+        let input_map = InputMapResource::ensure(app_builder.world_mut()).input_map.clone();
+
+        let (movement_input_sender, movement_input_receiver) = mpsc::channel();
+        let movement_input_adapter = MovementInputAdapter::new(movement_input_sender, input_map.clone());
+        let movement_sys = MovementSystem::new(app_builder.world_mut(), movement_input_receiver, &log);
+
+        let (mining_input_sender, mining_input_receiver) = mpsc::channel();
+        let mining_input_adapter = MiningInputAdapter::new(mining_input_sender, input_map);
+        let mining_sys = MiningSystem::new(mining_input_receiver, &log);
+
+        let physics_sys = PhysicsSystem::new(
+            app_builder.world_mut(),
+            &log,
+            self.gravity,
+            self.terminal_velocity,
+        );
+
+        app_builder
+            .add_input_adapter(Box::new(movement_input_adapter))
+            .add_input_adapter(Box::new(mining_input_adapter))
+            .add_auto_system(movement_sys)
+            .add_auto_system(mining_sys)
+            .add_auto_system(physics_sys)
+    }
+}
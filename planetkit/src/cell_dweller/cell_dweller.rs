@@ -1,9 +1,10 @@
 use specs;
 
 use types::*;
-use grid::{GridPoint3, Dir};
+use grid::{GridCoord, GridPoint3, Dir};
 use globe::Spec;
 use movement::*;
+use super::MovementProfile;
 
 pub struct CellDweller {
     // TODO: make these private and use guts trait pattern to expose them internally.
@@ -16,11 +17,27 @@ pub struct CellDweller {
     // transform was modified so we know when the former is dirty.
     is_real_space_transform_dirty: bool,
     pub globe_spec: Spec,
-    pub seconds_between_moves: TimeDelta,
+    movement_profile: MovementProfile,
     pub seconds_until_next_move: TimeDelta,
-    pub seconds_between_turns: TimeDelta,
     pub seconds_until_next_turn: TimeDelta,
-    pub seconds_until_next_fall: TimeDelta,
+    /// How fast the cell dweller is moving up, in cells per second.
+    /// Negative while it's falling.
+    pub vertical_velocity: f64,
+    /// How far the cell dweller has fallen toward the cell below it;
+    /// it moves down a cell each time this reaches one.
+    pub fall_progress: f64,
+    /// Where the cell dweller started falling from, if it's falling.
+    pub fall_start_z: Option<GridCoord>,
+    /// Whether the cell dweller is deliberately staying under water,
+    /// rather than floating up to the surface. Set by whatever
+    /// is controlling it, e.g. the `MovementSystem`.
+    pub is_diving: bool,
+    /// How far the cell dweller has floated toward the cell above it;
+    /// it moves up a cell each time this reaches one.
+    pub float_progress: f64,
+    /// How long the cell dweller has been holding its breath, give or take
+    /// however long it's had to catch its breath again at the surface since.
+    pub seconds_under_water: TimeDelta,
     pub globe_entity: Option<specs::Entity>,
 }
 
@@ -37,17 +54,43 @@ impl CellDweller {
             last_turn_bias: TurnDir::Right,
             is_real_space_transform_dirty: true,
             globe_spec: globe_spec,
-            // TODO: accept as parameter
-            seconds_between_moves: 0.1,
+            movement_profile: MovementProfile::default(),
             seconds_until_next_move: 0.0,
-            // TODO: accept as parameter
-            seconds_between_turns: 0.2,
             seconds_until_next_turn: 0.0,
-            seconds_until_next_fall: 0.0,
+            vertical_velocity: 0.0,
+            fall_progress: 0.0,
+            fall_start_z: None,
+            is_diving: false,
+            float_progress: 0.0,
+            seconds_under_water: 0.0,
             globe_entity: globe_entity,
         }
     }
 
+    pub fn movement_profile(&self) -> &MovementProfile {
+        &self.movement_profile
+    }
+
+    pub fn set_movement_profile(&mut self, new_movement_profile: MovementProfile) {
+        self.movement_profile = new_movement_profile;
+    }
+
+    /// How much breath the cell dweller has left, from 1 (plenty)
+    /// down to 0 (drowning), or `None` if it doesn't need to breathe.
+    pub fn breath_remaining(&self) -> Option<f64> {
+        self.movement_profile.max_breath_seconds.map(|max_breath_seconds| {
+            if max_breath_seconds <= 0.0 {
+                return 0.0;
+            }
+            (1.0 - self.seconds_under_water / max_breath_seconds).max(0.0)
+        })
+    }
+
+    /// Whether the cell dweller needs to breathe, and can't.
+    pub fn is_out_of_breath(&self) -> bool {
+        self.breath_remaining() == Some(0.0)
+    }
+
     pub fn pos(&self) -> GridPoint3 {
         self.pos
     }
@@ -83,6 +126,15 @@ impl CellDweller {
         self.is_real_space_transform_dirty = true;
     }
 
+    /// The position of the cell directly in front of the cell dweller.
+    pub fn pos_in_front(&self) -> GridPoint3 {
+        let mut new_pos = self.pos;
+        let mut new_dir = self.dir;
+        move_forward(&mut new_pos, &mut new_dir, self.globe_spec.root_resolution)
+            .expect("CellDweller should have been in good state.");
+        new_pos
+    }
+
     /// Calculate position in real-space.
     fn real_pos(&self) -> Pt3 {
         self.globe_spec.cell_bottom_center(self.pos)
@@ -121,3 +173,25 @@ impl CellDweller {
 impl specs::Component for CellDweller {
     type Storage = specs::HashMapStorage<CellDweller>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breath_runs_out() {
+        let mut cd = CellDweller::new(GridPoint3::default(), Dir::default(), Spec::new_earth_scale_example(), None);
+        assert_eq!(cd.breath_remaining(), None);
+        assert!(!cd.is_out_of_breath());
+
+        cd.set_movement_profile(MovementProfile {
+            max_breath_seconds: Some(10.0),
+            ..Default::default()
+        });
+        cd.seconds_under_water = 2.5;
+        assert_eq!(cd.breath_remaining(), Some(0.75));
+        cd.seconds_under_water = 12.0;
+        assert_eq!(cd.breath_remaining(), Some(0.0));
+        assert!(cd.is_out_of_breath());
+    }
+}
@@ -0,0 +1,225 @@
+use specs;
+
+use globe::chunk::Material;
+
+/// The most of any one kind of item that can share a single inventory slot.
+pub const MAX_STACK_SIZE: u32 = 64;
+
+/// Anything that can be carried around in an `Inventory`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ItemKind {
+    /// A block picked up from a globe, that can be put back down again.
+    Block(Material),
+}
+
+/// Some number of the same kind of item, all in the one slot.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Stack {
+    pub kind: ItemKind,
+    pub count: u32,
+}
+
+/// Things being carried around by an entity; usually a `CellDweller`.
+///
+/// The master is the authority on what's in each inventory,
+/// and tells the owning peer whenever its contents change.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Inventory {
+    slots: Vec<Option<Stack>>,
+    selected_slot: usize,
+}
+
+impl Inventory {
+    pub fn new(slot_count: usize) -> Inventory {
+        Inventory {
+            slots: vec![None; slot_count],
+            selected_slot: 0,
+        }
+    }
+
+    pub fn slots(&self) -> &[Option<Stack>] {
+        &self.slots
+    }
+
+    /// Add as many of the given item as will fit, filling partial stacks
+    /// of the same kind first, and then empty slots.
+    ///
+    /// Returns how many wouldn't fit.
+    pub fn add(&mut self, kind: ItemKind, mut count: u32) -> u32 {
+        for slot in self.slots.iter_mut() {
+            if count == 0 {
+                break;
+            }
+            if let Some(ref mut stack) = *slot {
+                if stack.kind == kind {
+                    let moved = count.min(MAX_STACK_SIZE - stack.count);
+                    stack.count += moved;
+                    count -= moved;
+                }
+            }
+        }
+        for slot in self.slots.iter_mut() {
+            if count == 0 {
+                break;
+            }
+            if slot.is_none() {
+                let moved = count.min(MAX_STACK_SIZE);
+                *slot = Some(Stack {
+                    kind: kind,
+                    count: moved,
+                });
+                count -= moved;
+            }
+        }
+        count
+    }
+
+    /// How many more of the given item would fit, in partial stacks
+    /// of the same kind and in empty slots.
+    pub fn room_for(&self, kind: ItemKind) -> u32 {
+        self.slots
+            .iter()
+            .map(|slot| match *slot {
+                Some(stack) if stack.kind == kind => MAX_STACK_SIZE - stack.count,
+                Some(_) => 0,
+                None => MAX_STACK_SIZE,
+            })
+            .sum()
+    }
+
+    /// Remove the given number of an item, emptying slots as they run out.
+    ///
+    /// Returns `false` and leaves the inventory untouched
+    /// if there aren't enough of them to remove.
+    pub fn remove(&mut self, kind: ItemKind, mut count: u32) -> bool {
+        if self.count(kind) < count {
+            return false;
+        }
+        // Take from the back so that earlier slots stay put where possible.
+        for slot in self.slots.iter_mut().rev() {
+            if count == 0 {
+                break;
+            }
+            let is_now_empty = match *slot {
+                Some(ref mut stack) => {
+                    if stack.kind != kind {
+                        continue;
+                    }
+                    let moved = count.min(stack.count);
+                    stack.count -= moved;
+                    count -= moved;
+                    stack.count == 0
+                }
+                None => false,
+            };
+            if is_now_empty {
+                *slot = None;
+            }
+        }
+        true
+    }
+
+    /// How many of the given item are in the inventory, across all slots.
+    pub fn count(&self, kind: ItemKind) -> u32 {
+        self.slots
+            .iter()
+            .filter_map(|slot| *slot)
+            .filter(|stack| stack.kind == kind)
+            .map(|stack| stack.count)
+            .sum()
+    }
+
+    pub fn selected_slot(&self) -> usize {
+        self.selected_slot
+    }
+
+    /// Panics if there's no such slot.
+    pub fn select_slot(&mut self, slot_index: usize) {
+        assert!(slot_index < self.slots.len());
+        self.selected_slot = slot_index;
+    }
+
+    /// Select the next slot, wrapping around to the first.
+    pub fn select_next_slot(&mut self) {
+        if !self.slots.is_empty() {
+            self.selected_slot = (self.selected_slot + 1) % self.slots.len();
+        }
+    }
+
+    /// Select the previous slot, wrapping around to the last.
+    pub fn select_previous_slot(&mut self) {
+        if !self.slots.is_empty() {
+            self.selected_slot = (self.selected_slot + self.slots.len() - 1) % self.slots.len();
+        }
+    }
+
+    /// The kind of item in the currently selected slot, if any.
+    pub fn selected_kind(&self) -> Option<ItemKind> {
+        self.slots
+            .get(self.selected_slot)
+            .and_then(|slot| *slot)
+            .map(|stack| stack.kind)
+    }
+}
+
+impl specs::Component for Inventory {
+    type Storage = specs::HashMapStorage<Inventory>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIRT: ItemKind = ItemKind::Block(Material::Dirt);
+    const WATER: ItemKind = ItemKind::Block(Material::Water);
+
+    #[test]
+    fn add_stacks_same_kinds_together() {
+        let mut inventory = Inventory::new(3);
+        assert_eq!(inventory.add(DIRT, 10), 0);
+        assert_eq!(inventory.add(WATER, 1), 0);
+        assert_eq!(inventory.add(DIRT, MAX_STACK_SIZE), 0);
+        assert_eq!(inventory.count(DIRT), 10 + MAX_STACK_SIZE);
+        assert_eq!(
+            inventory.slots(),
+            &[
+                Some(Stack { kind: DIRT, count: MAX_STACK_SIZE }),
+                Some(Stack { kind: WATER, count: 1 }),
+                Some(Stack { kind: DIRT, count: 10 }),
+            ]
+        );
+        // No room left for any more dirt.
+        assert_eq!(inventory.room_for(DIRT), MAX_STACK_SIZE - 10);
+        assert_eq!(inventory.room_for(WATER), MAX_STACK_SIZE - 1);
+        assert_eq!(inventory.add(DIRT, 100), 100 - (MAX_STACK_SIZE - 10));
+        assert_eq!(inventory.room_for(DIRT), 0);
+    }
+
+    #[test]
+    fn slot_selection_wraps_around() {
+        let mut inventory = Inventory::new(3);
+        inventory.add(DIRT, 1);
+        inventory.add(WATER, MAX_STACK_SIZE + 1);
+        assert_eq!(inventory.selected_kind(), Some(DIRT));
+        inventory.select_previous_slot();
+        assert_eq!(inventory.selected_slot(), 2);
+        assert_eq!(inventory.selected_kind(), Some(WATER));
+        inventory.select_next_slot();
+        assert_eq!(inventory.selected_slot(), 0);
+        inventory.select_next_slot();
+        assert_eq!(inventory.selected_slot(), 1);
+        assert_eq!(inventory.selected_kind(), Some(WATER));
+    }
+
+    #[test]
+    fn remove_is_all_or_nothing() {
+        let mut inventory = Inventory::new(2);
+        inventory.add(DIRT, 3);
+        assert!(!inventory.remove(DIRT, 4));
+        assert_eq!(inventory.count(DIRT), 3);
+        assert!(inventory.remove(DIRT, 3));
+        assert_eq!(inventory.count(DIRT), 0);
+        assert_eq!(inventory.slots(), &[None, None]);
+        assert_eq!(inventory.selected_kind(), None);
+    }
+}
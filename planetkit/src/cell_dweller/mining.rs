@@ -1,8 +1,14 @@
-use super::CellDweller;
+use super::{CellDweller, Tool};
 use movement::*;
 use grid::PosInOwningRoot;
 use globe::chunk::{Cell, Material};
 use globe::Globe;
+use types::TimeDelta;
+
+/// How much longer the master will let a cell dweller think it has been digging
+/// than the master has seen, to allow for messages taking different amounts
+/// of time to arrive.
+pub const DIG_SECONDS_TOLERANCE: TimeDelta = 0.1;
 
 /// Assumes that the given CellDweller is indeed attached to the given globe.
 /// May panick if this is not true.
@@ -25,7 +31,7 @@ pub fn can_pick_up(cd: &mut CellDweller, globe: &mut Globe) -> bool {
             // Chunk not loaded; wait until it is before attempting to pick up.
             Err(_) => return false,
         };
-        if under_cell.material != Material::Dirt {
+        if !under_cell.material.is_solid() {
             return false;
         }
     }
@@ -38,7 +44,7 @@ pub fn can_pick_up(cd: &mut CellDweller, globe: &mut Globe) -> bool {
     let anything_to_pick_up = {
         // Chunk might not be loaded; in that case assume nothing to pick up.
         globe.maybe_non_authoritative_cell(new_pos).map(|cell| {
-            cell.material == Material::Dirt
+            cell.material.hardness().is_some()
         }).unwrap_or(false)
     };
     // Also require that there's air above the block;
@@ -54,6 +60,41 @@ pub fn can_pick_up(cd: &mut CellDweller, globe: &mut Globe) -> bool {
     anything_to_pick_up && air_above_target
 }
 
+/// How long it would take the given CellDweller to dig out the block in front of it,
+/// using the given tool or bare hands.
+///
+/// Returns `None` if there's nothing there that can be dug, or the chunk isn't loaded.
+pub fn dig_seconds(cd: &CellDweller, globe: &Globe, tool: Option<Tool>) -> Option<TimeDelta> {
+    let material = match material_in_front(cd, globe) {
+        Some(material) => material,
+        None => return None,
+    };
+    material.hardness().map(|hardness| {
+        let dig_speed = tool.map(|tool| tool.dig_speed(material)).unwrap_or(1.0);
+        hardness / dig_speed
+    })
+}
+
+/// Whether the given CellDweller has been digging for long enough
+/// to dig out the block in front of it, using the given tool or bare hands.
+///
+/// Returns `false` if there's nothing there that can be dug, or the chunk isn't loaded.
+pub fn has_dug_long_enough(cd: &CellDweller, globe: &Globe, tool: Option<Tool>, seconds_elapsed: TimeDelta) -> bool {
+    dig_seconds(cd, globe, tool)
+        .map(|seconds_required| seconds_elapsed + DIG_SECONDS_TOLERANCE >= seconds_required)
+        .unwrap_or(false)
+}
+
+/// What the cell in front of the given CellDweller is made of,
+/// or `None` if its chunk isn't loaded.
+pub fn material_in_front(cd: &CellDweller, globe: &Globe) -> Option<Material> {
+    let mut new_pos = cd.pos;
+    let mut new_dir = cd.dir;
+    move_forward(&mut new_pos, &mut new_dir, globe.spec().root_resolution)
+        .expect("CellDweller should have been in good state.");
+    globe.maybe_non_authoritative_cell(new_pos).ok().map(|cell| cell.material)
+}
+
 // If anything was picked up, then return the position we picked up,
 // and what was in it.
 pub fn pick_up_if_possible(cd: &mut CellDweller, globe: &mut Globe) -> Option<(PosInOwningRoot, Cell)> {
@@ -80,36 +121,79 @@ pub fn pick_up_if_possible(cd: &mut CellDweller, globe: &mut Globe) -> Option<(P
     Some((new_pos_in_owning_root, removed_cell))
 }
 
-pub fn remove_block(globe: &mut Globe, pos_in_owning_root: PosInOwningRoot) -> Cell {
-    use globe::is_point_on_chunk_edge;
-
-    // Keep for later, so we can return what was in it.
-    let cloned_cell = {
-        let cell = globe.authoritative_cell_mut(pos_in_owning_root);
-        let cs = cell.clone();
-        cell.material = Material::Air;
-        cs
+/// Find the cell in front of the given CellDweller where it could place a block, if any.
+///
+/// This only considers the terrain; it's up to the caller to check that there
+/// isn't something else like another CellDweller already in that cell.
+///
+/// Assumes that the given CellDweller is indeed attached to the given globe.
+/// May panick if this is not true.
+pub fn placement_target(cd: &CellDweller, globe: &Globe) -> Option<PosInOwningRoot> {
+    let mut new_pos = cd.pos;
+    let mut new_dir = cd.dir;
+    move_forward(&mut new_pos, &mut new_dir, globe.spec().root_resolution)
+        .expect("CellDweller should have been in good state.");
+    let is_empty = {
+        // Chunk might not be loaded; in that case assume there's no room.
+        globe.maybe_non_authoritative_cell(new_pos).map(|cell| {
+            cell.material == Material::Air
+        }).unwrap_or(false)
     };
-    // Some extra stuff is only relevant if the cell is on the edge of its chunk.
-    if is_point_on_chunk_edge(
-        *pos_in_owning_root.pos(),
-        globe.spec().chunk_resolution,
-    )
-    {
-        // Bump version of owned shared cells.
-        globe.increment_chunk_owned_edge_version_for_cell(pos_in_owning_root);
-        // Propagate change to neighbouring chunks.
-        let chunk_origin = globe.origin_of_chunk_owning(pos_in_owning_root);
-        globe.push_shared_cells_for_chunk(chunk_origin);
+    if !is_empty {
+        return None;
+    }
+    Some(PosInOwningRoot::new(new_pos, globe.spec().root_resolution))
+}
+
+/// Check whether a block of the given material can be placed in the given cell:
+/// the chunk owning it has to be loaded, the cell has to be empty,
+/// and none of the given cell dwellers can be standing in it.
+///
+/// Placing air would just be mining by another name, so that's not allowed either.
+pub fn can_place_block<'a, I>(globe: &Globe, pos: PosInOwningRoot, material: Material, cell_dwellers: I) -> bool
+where
+    I: IntoIterator<Item = &'a CellDweller>,
+{
+    if material == Material::Air {
+        return false;
+    }
+    if !is_cell_loaded(globe, pos) {
+        return false;
+    }
+    if globe.authoritative_cell(pos).material != Material::Air {
+        return false;
     }
-    // Mark the view for the containing chunk and those containing each cell surrounding
-    // it as being dirty. (This cell might affect the visibility of cells in those chunks.)
-    // TODO: different API where you commit to changing a cell
-    // in a closure you get back that has a reference to it?
-    // Or contains a _wrapper_ around it so it knows if you mutated it? Ooooh.
-    globe.mark_chunk_views_affected_by_cell_as_dirty(pos_in_owning_root.into());
+    // Don't let anyone bury a cell dweller.
+    // TODO: check other kinds of entities that occupy cells, too.
+    let root_resolution = globe.spec().root_resolution;
+    !cell_dwellers.into_iter().any(|cd| {
+        PosInOwningRoot::new(cd.pos, root_resolution) == pos
+    })
+}
+
+/// Whether the chunk owning the given cell is loaded, so that
+/// its authoritative cell can be read or changed.
+pub fn is_cell_loaded(globe: &Globe, pos: PosInOwningRoot) -> bool {
+    globe.chunk_at(globe.origin_of_chunk_owning(pos)).is_some()
+}
+
+pub fn remove_block(globe: &mut Globe, pos_in_owning_root: PosInOwningRoot) -> Cell {
     // TODO: remember on the cell-dweller that it's carrying something?
     // Or should that be a different kind of component?
+    set_block_material(globe, pos_in_owning_root, Material::Air)
+}
+
+/// Fill the cell at the given position with the given material,
+/// and return what used to be in it.
+pub fn place_block(globe: &mut Globe, pos_in_owning_root: PosInOwningRoot, material: Material) -> Cell {
+    set_block_material(globe, pos_in_owning_root, material)
+}
+
+fn set_block_material(globe: &mut Globe, pos_in_owning_root: PosInOwningRoot, material: Material) -> Cell {
+    use globe::chunk::MAX_FLUID_LEVEL;
 
-    cloned_cell
+    let mut new_cell = *globe.authoritative_cell(pos_in_owning_root);
+    new_cell.material = material;
+    new_cell.fluid_level = if material == Material::Water { MAX_FLUID_LEVEL } else { 0 };
+    globe.set_authoritative_cell(pos_in_owning_root, new_cell)
 }
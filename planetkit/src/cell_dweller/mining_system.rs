@@ -1,4 +1,4 @@
-use std::sync::mpsc;
+use std::sync::{Arc, Mutex, mpsc};
 use specs;
 use specs::{ReadStorage, WriteStorage, Fetch, FetchMut};
 use slog::Logger;
@@ -9,10 +9,20 @@ use super::{
     ActiveCellDweller,
     SendMessageQueue,
     CellDwellerMessage,
+    StartDiggingMessage,
     TryPickUpBlockMessage,
+    TryPlaceBlockMessage,
+    Inventory,
+    ItemKind,
+    Tool,
 };
+use types::*;
+use grid::{GridPoint3, Dir};
 use globe::Globe;
+use globe::chunk::Material;
 use input_adapter;
+use input_map::InputMap;
+use auto_system::{AutoSystem, ensure_component_registered};
 use ::net::{
     SendMessage,
     Transport,
@@ -20,39 +30,83 @@ use ::net::{
     NetMarker,
 };
 
+pub const PICK_UP_ACTION: &str = "pick_up";
+pub const PLACE_ACTION: &str = "place";
+pub const NEXT_SLOT_ACTION: &str = "next_slot";
+pub const PREVIOUS_SLOT_ACTION: &str = "previous_slot";
+
 // TODO: own file?
 pub struct MiningInputAdapter {
     sender: mpsc::Sender<MiningEvent>,
+    input_map: Arc<Mutex<InputMap>>,
 }
 
 impl MiningInputAdapter {
-    pub fn new(sender: mpsc::Sender<MiningEvent>) -> MiningInputAdapter {
-        MiningInputAdapter { sender: sender }
+    pub fn new(sender: mpsc::Sender<MiningEvent>, input_map: Arc<Mutex<InputMap>>) -> MiningInputAdapter {
+        use piston::input::Button::Keyboard;
+        use piston::input::keyboard::Key;
+
+        {
+            let mut input_map = input_map.lock().expect("Couldn't get lock on input map");
+            input_map.set_default(PICK_UP_ACTION, &[Keyboard(Key::U)]);
+            input_map.set_default(PLACE_ACTION, &[Keyboard(Key::O)]);
+            input_map.set_default(NEXT_SLOT_ACTION, &[Keyboard(Key::P)]);
+            input_map.set_default(PREVIOUS_SLOT_ACTION, &[Keyboard(Key::Y)]);
+        }
+
+        MiningInputAdapter {
+            sender: sender,
+            input_map: input_map,
+        }
     }
 }
 
 impl input_adapter::InputAdapter for MiningInputAdapter {
     fn handle(&self, input_event: &Input) {
-        use piston::input::{Button, ButtonState};
-        use piston::input::keyboard::Key;
-
-        if let &Input::Button(button_args) = input_event {
-            if let Button::Keyboard(key) = button_args.button {
-                let is_down = match button_args.state {
-                    ButtonState::Press => true,
-                    ButtonState::Release => false,
-                };
-                match key {
-                    Key::U => self.sender.send(MiningEvent::PickUp(is_down)).unwrap(),
-                    _ => (),
-                }
-            }
+        let input_map = self.input_map.lock().expect("Couldn't get lock on input map");
+        if let Some((action, is_down)) = input_map.action_for_input(input_event) {
+            let event = match action {
+                PICK_UP_ACTION => MiningEvent::PickUp(is_down),
+                // Only place one block, or move one slot, per key press.
+                PLACE_ACTION if is_down => MiningEvent::Place,
+                NEXT_SLOT_ACTION if is_down => MiningEvent::SelectNextSlot,
+                PREVIOUS_SLOT_ACTION if is_down => MiningEvent::SelectPreviousSlot,
+                _ => return,
+            };
+            self.sender.send(event).unwrap();
         }
     }
 }
 
 pub enum MiningEvent {
     PickUp(bool),
+    Place,
+    SelectNextSlot,
+    SelectPreviousSlot,
+}
+
+/// `World`-global resource describing how far the active cell dweller
+/// is through digging out the block in front of it.
+///
+/// Useful for showing the player how much longer they have to wait.
+pub struct MiningProgress {
+    /// Between 0 and 1, or `None` if the active cell dweller isn't digging.
+    pub fraction_complete: Option<f64>,
+}
+
+impl ::AutoResource for MiningProgress {
+    fn new(_world: &mut specs::World) -> MiningProgress {
+        MiningProgress { fraction_complete: None }
+    }
+}
+
+// Where the cell dweller was standing and facing when it started digging,
+// so we can tell if it has wandered off, and how long it's been at it.
+struct Dig {
+    pos: GridPoint3,
+    dir: Dir,
+    seconds_elapsed: TimeDelta,
+    seconds_required: TimeDelta,
 }
 
 pub struct MiningSystem {
@@ -63,6 +117,16 @@ pub struct MiningSystem {
     // But Piston seems to have some kind of silly key-repeat thing built in.
     // TODO: clarify.
     pick_up: bool,
+    dig: Option<Dig>,
+    // Whether a block should be placed this tick; set
+    // by each key press, and cleared as soon as we run.
+    place: bool,
+    // How many slots to move the inventory selection by,
+    // from key presses since we last ran.
+    slot_steps: i32,
+    // What to build with if the cell dweller doesn't have an inventory.
+    // Otherwise we use whatever is in its selected slot.
+    material_to_place: Material,
 }
 
 impl MiningSystem {
@@ -71,20 +135,20 @@ impl MiningSystem {
             input_receiver: input_receiver,
             log: parent_log.new(o!()),
             pick_up: false,
+            dig: None,
+            place: false,
+            slot_steps: 0,
+            material_to_place: Material::Dirt,
         }
     }
 
-    // TODO: move into special trait, e.g., `PlanetKitSystem`, and require all systems to be
-    // added through my interface. We can then specialise that to automatically call this initialisation
-    // code if the system happens to provide it.
-    pub fn init(&mut self, world: &mut specs::World) {
-        ActiveCellDweller::ensure_registered(world);
-    }
-
     fn consume_input(&mut self) {
         loop {
             match self.input_receiver.try_recv() {
                 Ok(MiningEvent::PickUp(b)) => self.pick_up = b,
+                Ok(MiningEvent::Place) => self.place = true,
+                Ok(MiningEvent::SelectNextSlot) => self.slot_steps += 1,
+                Ok(MiningEvent::SelectPreviousSlot) => self.slot_steps -= 1,
                 Err(_) => return,
             }
         }
@@ -93,27 +157,55 @@ impl MiningSystem {
 
 impl<'a> specs::System<'a> for MiningSystem {
     type SystemData = (
+        Fetch<'a, TimeDeltaResource>,
         WriteStorage<'a, CellDweller>,
         WriteStorage<'a, Globe>,
         Fetch<'a, ActiveCellDweller>,
         FetchMut<'a, SendMessageQueue>,
         ReadStorage<'a, NetMarker>,
+        WriteStorage<'a, Inventory>,
+        ReadStorage<'a, Tool>,
+        FetchMut<'a, MiningProgress>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
+        use std::mem;
+
         self.consume_input();
+        let place = mem::replace(&mut self.place, false);
 
         let (
+            dt,
             mut cell_dwellers,
             mut globes,
             active_cell_dweller_resource,
             mut send_message_queue,
             net_markers,
+            mut inventories,
+            tools,
+            mut mining_progress,
         ) = data;
         let active_cell_dweller_entity = match active_cell_dweller_resource.maybe_entity {
             Some(entity) => entity,
-            None => return,
+            None => {
+                self.dig = None;
+                mining_progress.fraction_complete = None;
+                return;
+            }
         };
+        // The selected slot is ours to choose; the master doesn't need to know.
+        if let Some(inventory) = inventories.get_mut(active_cell_dweller_entity) {
+            while self.slot_steps > 0 {
+                inventory.select_next_slot();
+                self.slot_steps -= 1;
+            }
+            while self.slot_steps < 0 {
+                inventory.select_previous_slot();
+                self.slot_steps += 1;
+            }
+        }
+        self.slot_steps = 0;
+
         let cd = cell_dwellers.get_mut(active_cell_dweller_entity).expect(
             "Someone deleted the controlled entity's CellDweller",
         );
@@ -141,8 +233,71 @@ impl<'a> specs::System<'a> for MiningSystem {
         };
 
         // If we're trying to pick up, and from our perspective (we might not be the server)
-        // we _can_ pick up, then request to the server to pick up the block.
-        if self.pick_up && super::mining::can_pick_up(cd, globe) {
+        // we _can_ pick up, then keep digging until we're done, and then
+        // request to the server to pick up the block.
+        //
+        // Stop digging as soon as the cell dweller moves or turns away.
+        // Don't bother if there's nowhere to put it; the master would refuse anyway.
+        let has_room = match inventories.get(active_cell_dweller_entity) {
+            Some(inventory) => super::mining::material_in_front(cd, globe)
+                .map(|material| inventory.room_for(ItemKind::Block(material)) > 0)
+                .unwrap_or(false),
+            None => true,
+        };
+        let is_digging = self.pick_up && has_room && super::mining::can_pick_up(cd, globe);
+        let is_still_in_place = self.dig
+            .as_ref()
+            .map(|dig| dig.pos == cd.pos && dig.dir == cd.dir)
+            .unwrap_or(false);
+        if !is_digging {
+            self.dig = None;
+        } else if !is_still_in_place {
+            let tool = tools.get(active_cell_dweller_entity).cloned();
+            self.dig = super::mining::dig_seconds(cd, globe, tool).map(|seconds_required| {
+                trace!(self.log, "Started digging"; "seconds_required" => seconds_required);
+                Dig {
+                    pos: cd.pos,
+                    dir: cd.dir,
+                    seconds_elapsed: 0.0,
+                    seconds_required: seconds_required,
+                }
+            });
+
+            // Let the master know when we started, so that it can check
+            // we've been at it for long enough when we ask for the block.
+            if self.dig.is_some() && send_message_queue.has_consumer {
+                let cd_entity_id = net_markers
+                    .get(active_cell_dweller_entity)
+                    .expect("Shouldn't be trying to tell peers about entities that don't have global IDs!")
+                    .id;
+                send_message_queue.queue.push_back(
+                    SendMessage {
+                        destination: Destination::Master,
+                        game_message: CellDwellerMessage::StartDigging(StartDiggingMessage {
+                            cd_entity_id: cd_entity_id,
+                        }),
+                        transport: Transport::TCP,
+                    }
+                )
+            }
+        }
+        let is_dig_complete = match self.dig {
+            Some(ref mut dig) => {
+                dig.seconds_elapsed += dt.0;
+                dig.seconds_elapsed >= dig.seconds_required
+            }
+            None => false,
+        };
+        mining_progress.fraction_complete = self.dig.as_ref().map(|dig| {
+            if dig.seconds_required > 0.0 {
+                (dig.seconds_elapsed / dig.seconds_required).min(1.0)
+            } else {
+                1.0
+            }
+        });
+        if is_dig_complete {
+            self.dig = None;
+
             // Post a message to the server (even if that's us)
             // requesting to remove the block.
             debug!(self.log, "Requesting to pick up a block");
@@ -166,5 +321,62 @@ impl<'a> specs::System<'a> for MiningSystem {
                 )
             }
         }
+
+        // Same deal for placing blocks; the server will also make sure
+        // there isn't something else in the way.
+        let maybe_material_to_place = match inventories.get(active_cell_dweller_entity) {
+            Some(inventory) => match inventory.selected_kind() {
+                Some(ItemKind::Block(material)) => Some(material),
+                None => None,
+            },
+            None => Some(self.material_to_place),
+        };
+        let material_to_place = match maybe_material_to_place {
+            Some(material) => material,
+            // Nothing to build with.
+            None => return,
+        };
+        if place && super::mining::placement_target(cd, globe).is_some() {
+            debug!(self.log, "Requesting to place a block"; "material" => format!("{:?}", material_to_place));
+
+            if send_message_queue.has_consumer {
+                let cd_entity_id = net_markers
+                    .get(active_cell_dweller_entity)
+                    .expect("Shouldn't be trying to tell peers about entities that don't have global IDs!")
+                    .id;
+                send_message_queue.queue.push_back(
+                    SendMessage {
+                        destination: Destination::Master,
+                        game_message: CellDwellerMessage::TryPlaceBlock(TryPlaceBlockMessage {
+                            cd_entity_id: cd_entity_id,
+                            material: material_to_place,
+                        }),
+                        transport: Transport::TCP,
+                    }
+                )
+            }
+        }
+    }
+}
+
+impl AutoSystem for MiningSystem {
+    fn name(&self) -> &'static str {
+        "cd_mining"
+    }
+
+    fn dependencies(&self) -> Vec<&'static str> {
+        // Mine from wherever movement left the cell dweller this tick.
+        vec!["cd_movement"]
+    }
+
+    fn init(&mut self, world: &mut specs::World) {
+        use ::AutoResource;
+        ActiveCellDweller::ensure_registered(world);
+        MiningProgress::ensure(world);
+        ensure_component_registered::<CellDweller>(world);
+        ensure_component_registered::<Globe>(world);
+        ensure_component_registered::<NetMarker>(world);
+        ensure_component_registered::<Inventory>(world);
+        ensure_component_registered::<Tool>(world);
     }
 }
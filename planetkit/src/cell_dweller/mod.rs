@@ -4,18 +4,49 @@ mod mining;
 mod mining_system;
 mod physics_system;
 mod recv_system;
+mod inventory;
+mod tool;
+mod movement_profile;
+mod surroundings;
+mod bundle;
+
+#[cfg(test)]
+mod tests;
 
 use std::collections::vec_deque::VecDeque;
 use grid::{GridPoint3, Dir};
+use globe::chunk::Material;
 use ::movement::TurnDir;
 use ::net::{SendMessage, RecvMessage};
 
 pub use ::AutoResource;
 pub use self::cell_dweller::CellDweller;
-pub use self::movement_system::{MovementSystem, MovementEvent, MovementInputAdapter};
-pub use self::mining_system::{MiningSystem, MiningEvent, MiningInputAdapter};
-pub use self::physics_system::PhysicsSystem;
+pub use self::movement_system::{
+    MovementSystem,
+    MovementEvent,
+    MovementInputAdapter,
+    STEP_FORWARD_ACTION,
+    STEP_BACKWARD_ACTION,
+    TURN_LEFT_ACTION,
+    TURN_RIGHT_ACTION,
+    JUMP_ACTION,
+    DIVE_ACTION,
+};
+pub use self::mining_system::{
+    MiningSystem,
+    MiningEvent,
+    MiningInputAdapter,
+    MiningProgress,
+    PICK_UP_ACTION,
+    PLACE_ACTION,
+};
+pub use self::mining::{remove_block, place_block, can_place_block, is_cell_loaded};
+pub use self::physics_system::{PhysicsSystem, Landings, Landing};
 pub use self::recv_system::RecvSystem;
+pub use self::inventory::{Inventory, ItemKind, Stack, MAX_STACK_SIZE};
+pub use self::tool::Tool;
+pub use self::movement_profile::MovementProfile;
+pub use self::bundle::CellDwellerBundle;
 
 use shred;
 use specs;
@@ -41,8 +72,15 @@ impl ActiveCellDweller {
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub enum CellDwellerMessage {
     SetPos(SetPosMessage),
+    StartDigging(StartDiggingMessage),
     TryPickUpBlock(TryPickUpBlockMessage),
     RemoveBlock(RemoveBlockMessage),
+    RemoveBlocks(RemoveBlocksMessage),
+    TryPlaceBlock(TryPlaceBlockMessage),
+    PlaceBlock(PlaceBlockMessage),
+    SetInventory(SetInventoryMessage),
+    SetFluidLevels(SetFluidLevelsMessage),
+    SetFallenBlocks(SetFallenBlocksMessage),
 }
 
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
@@ -53,6 +91,14 @@ pub struct SetPosMessage {
     pub new_last_turn_bias: TurnDir,
 }
 
+/// Sent to the master when a cell dweller starts digging out the block in front of it,
+/// so that the master can check it has been at it for long enough
+/// before letting it pick the block up.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub struct StartDiggingMessage {
+    pub cd_entity_id: u64,
+}
+
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
 pub struct TryPickUpBlockMessage {
     // TODO:
@@ -89,6 +135,61 @@ pub struct RemoveBlockMessage {
     pub pos: GridPoint3,
 }
 
+/// Like `RemoveBlockMessage`, but for a whole bunch of blocks
+/// removed at once, e.g. by an explosion.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub struct RemoveBlocksMessage {
+    // TODO: identify the globe; see `RemoveBlockMessage`.
+    pub positions: Vec<GridPoint3>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub struct TryPlaceBlockMessage {
+    // TODO:
+    // pub globe_entity_id: u64,
+    pub cd_entity_id: u64,
+    // Like `TryPickUpBlockMessage`, we just use whatever
+    // the server thinks is in front of you.
+    pub material: Material,
+}
+
+// TODO: see notes on `RemoveBlockMessage`; this belongs
+// wherever that ends up.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub struct PlaceBlockMessage {
+    // TODO: identify the globe.
+    pub pos: GridPoint3,
+    pub material: Material,
+}
+
+/// Sent by the master to the peer controlling a cell dweller
+/// whenever the contents of its inventory change.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub struct SetInventoryMessage {
+    pub cd_entity_id: u64,
+    pub inventory: Inventory,
+}
+
+/// Sent by the master to tell everyone else how water has flowed;
+/// see `globe::FluidSystem`.
+// TODO: like `RemoveBlockMessage`, this doesn't really belong here.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub struct SetFluidLevelsMessage {
+    // TODO: identify the globe; see `RemoveBlockMessage`.
+    /// New fluid level of each cell that changed; zero means it's now air.
+    pub cells: Vec<(GridPoint3, u8)>,
+}
+
+/// Sent by the master to tell everyone else which blocks have fallen;
+/// see `globe::FallingBlockSystem`.
+// TODO: like `RemoveBlockMessage`, this doesn't really belong here.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub struct SetFallenBlocksMessage {
+    // TODO: identify the globe; see `RemoveBlockMessage`.
+    /// New material and fluid level of each cell that changed.
+    pub cells: Vec<(GridPoint3, Material, u8)>,
+}
+
 /// `World`-global resource for outbound cell-dweller network messages.
 pub struct SendMessageQueue {
     // We don't want to queue up any messages unless there's
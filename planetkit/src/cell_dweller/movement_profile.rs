@@ -0,0 +1,46 @@
+use types::*;
+use grid::GridCoord;
+
+/// Describes how a particular `CellDweller` gets around.
+///
+/// Different creatures can move very differently; give each
+/// `CellDweller` its own profile with `CellDweller::set_movement_profile`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MovementProfile {
+    pub seconds_between_moves: TimeDelta,
+    pub seconds_between_turns: TimeDelta,
+    /// How many cells higher than where it's standing the cell dweller
+    /// can step up onto without jumping.
+    pub max_step_height: GridCoord,
+    /// How many cells the cell dweller can fall without hurting itself.
+    pub max_safe_fall: GridCoord,
+    /// How many cells the cell dweller rises when it jumps,
+    /// and how much higher it can step up while jumping.
+    pub jump_height: GridCoord,
+    /// Whether the cell dweller can get around in water, rather than
+    /// just sinking to the bottom.
+    pub can_swim: bool,
+    /// Whether the cell dweller can climb walls that are too high to step up.
+    pub can_climb: bool,
+    /// How many times longer each move takes while swimming.
+    pub swim_move_factor: f64,
+    /// How long the cell dweller can stay under water before it runs out of breath,
+    /// or `None` if it doesn't need to breathe.
+    pub max_breath_seconds: Option<TimeDelta>,
+}
+
+impl Default for MovementProfile {
+    fn default() -> MovementProfile {
+        MovementProfile {
+            seconds_between_moves: 0.1,
+            seconds_between_turns: 0.2,
+            max_step_height: 1,
+            max_safe_fall: 3,
+            jump_height: 1,
+            can_swim: false,
+            can_climb: false,
+            swim_move_factor: 2.0,
+            max_breath_seconds: None,
+        }
+    }
+}
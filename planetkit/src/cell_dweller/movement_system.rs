@@ -1,4 +1,4 @@
-use std::sync::mpsc;
+use std::sync::{Arc, Mutex, mpsc};
 use specs;
 use specs::{ReadStorage, WriteStorage, Fetch, FetchMut};
 use slog::Logger;
@@ -16,7 +16,11 @@ use Spatial;
 use movement::*;
 use globe::Globe;
 use globe::chunk::Material;
+use grid::GridCoord;
+use super::surroundings::{is_standing_on_solid_ground, is_swimming, is_facing_wall};
 use input_adapter;
+use input_map::InputMap;
+use auto_system::{AutoSystem, ensure_component_registered};
 use ::net::{
     SendMessage,
     Transport,
@@ -24,47 +28,56 @@ use ::net::{
     NetMarker,
 };
 
+pub const STEP_FORWARD_ACTION: &str = "step_forward";
+pub const STEP_BACKWARD_ACTION: &str = "step_backward";
+pub const TURN_LEFT_ACTION: &str = "turn_left";
+pub const TURN_RIGHT_ACTION: &str = "turn_right";
+pub const JUMP_ACTION: &str = "jump";
+pub const DIVE_ACTION: &str = "dive";
+
 // TODO: own file?
 pub struct MovementInputAdapter {
     sender: mpsc::Sender<MovementEvent>,
+    input_map: Arc<Mutex<InputMap>>,
 }
 
 impl MovementInputAdapter {
-    pub fn new(sender: mpsc::Sender<MovementEvent>) -> MovementInputAdapter {
-        MovementInputAdapter { sender: sender }
+    pub fn new(sender: mpsc::Sender<MovementEvent>, input_map: Arc<Mutex<InputMap>>) -> MovementInputAdapter {
+        use piston::input::Button::Keyboard;
+        use piston::input::keyboard::Key;
+
+        {
+            // Arrow keys, vi keys, and WASD keys, unless the player prefers something else.
+            let mut input_map = input_map.lock().expect("Couldn't get lock on input map");
+            input_map.set_default(STEP_FORWARD_ACTION, &[Keyboard(Key::Up), Keyboard(Key::I), Keyboard(Key::W)]);
+            input_map.set_default(STEP_BACKWARD_ACTION, &[Keyboard(Key::Down), Keyboard(Key::K), Keyboard(Key::S)]);
+            input_map.set_default(TURN_LEFT_ACTION, &[Keyboard(Key::Left), Keyboard(Key::J), Keyboard(Key::A)]);
+            input_map.set_default(TURN_RIGHT_ACTION, &[Keyboard(Key::Right), Keyboard(Key::L), Keyboard(Key::D)]);
+            input_map.set_default(JUMP_ACTION, &[Keyboard(Key::Z)]);
+            input_map.set_default(DIVE_ACTION, &[Keyboard(Key::X)]);
+        }
+
+        MovementInputAdapter {
+            sender: sender,
+            input_map: input_map,
+        }
     }
 }
 
 impl input_adapter::InputAdapter for MovementInputAdapter {
     fn handle(&self, input_event: &Input) {
-        use piston::input::{Button, ButtonState};
-        use piston::input::keyboard::Key;
-
-        if let &Input::Button(button_args) = input_event {
-            if let Button::Keyboard(key) = button_args.button {
-                let is_down = match button_args.state {
-                    ButtonState::Press => true,
-                    ButtonState::Release => false,
-                };
-                match key {
-                    // Arrow keys.
-                    Key::Up => self.sender.send(MovementEvent::StepForward(is_down)).unwrap(),
-                    Key::Down => self.sender.send(MovementEvent::StepBackward(is_down)).unwrap(),
-                    Key::Left => self.sender.send(MovementEvent::TurnLeft(is_down)).unwrap(),
-                    Key::Right => self.sender.send(MovementEvent::TurnRight(is_down)).unwrap(),
-                    // Vi keys.
-                    Key::I => self.sender.send(MovementEvent::StepForward(is_down)).unwrap(),
-                    Key::K => self.sender.send(MovementEvent::StepBackward(is_down)).unwrap(),
-                    Key::J => self.sender.send(MovementEvent::TurnLeft(is_down)).unwrap(),
-                    Key::L => self.sender.send(MovementEvent::TurnRight(is_down)).unwrap(),
-                    // WASD keys.
-                    Key::W => self.sender.send(MovementEvent::StepForward(is_down)).unwrap(),
-                    Key::S => self.sender.send(MovementEvent::StepBackward(is_down)).unwrap(),
-                    Key::A => self.sender.send(MovementEvent::TurnLeft(is_down)).unwrap(),
-                    Key::D => self.sender.send(MovementEvent::TurnRight(is_down)).unwrap(),
-                    _ => (),
-                }
-            }
+        let input_map = self.input_map.lock().expect("Couldn't get lock on input map");
+        if let Some((action, is_down)) = input_map.action_for_input(input_event) {
+            let event = match action {
+                STEP_FORWARD_ACTION => MovementEvent::StepForward(is_down),
+                STEP_BACKWARD_ACTION => MovementEvent::StepBackward(is_down),
+                TURN_LEFT_ACTION => MovementEvent::TurnLeft(is_down),
+                TURN_RIGHT_ACTION => MovementEvent::TurnRight(is_down),
+                JUMP_ACTION => MovementEvent::Jump(is_down),
+                DIVE_ACTION => MovementEvent::Dive(is_down),
+                _ => return,
+            };
+            self.sender.send(event).unwrap();
         }
     }
 }
@@ -74,6 +87,10 @@ pub enum MovementEvent {
     StepBackward(bool),
     TurnLeft(bool),
     TurnRight(bool),
+    /// Swim up when in water; otherwise jump.
+    Jump(bool),
+    /// Swim down when in water.
+    Dive(bool),
 }
 
 pub struct MovementSystem {
@@ -83,9 +100,11 @@ pub struct MovementSystem {
     step_backward: bool,
     turn_left: bool,
     turn_right: bool,
-    max_step_height: u8,
+    jump: bool,
+    dive: bool,
 }
 
+#[derive(Clone, Copy)]
 enum ForwardOrBackward {
     Forward,
     Backward,
@@ -107,23 +126,11 @@ impl MovementSystem {
             step_backward: false,
             turn_left: false,
             turn_right: false,
-            max_step_height: 1,
+            jump: false,
+            dive: false,
         }
     }
 
-    // TODO: move into special trait, e.g., `PlanetKitSystem`, and require all systems to be
-    // added through my interface. We can then specialise that to automatically call this initialisation
-    // code if the system happens to provide it.
-    pub fn init(&mut self, world: &mut specs::World) {
-        // TODO: move this into `new`.
-        ActiveCellDweller::ensure_registered(world);
-    }
-
-    // Pretty much only for tests.
-    pub fn set_step_height(&mut self, new_max_step_height: u8) {
-        self.max_step_height = new_max_step_height;
-    }
-
     fn consume_input(&mut self) {
         loop {
             match self.input_receiver.try_recv() {
@@ -131,6 +138,8 @@ impl MovementSystem {
                 Ok(MovementEvent::StepBackward(b)) => self.step_backward = b,
                 Ok(MovementEvent::TurnLeft(b)) => self.turn_left = b,
                 Ok(MovementEvent::TurnRight(b)) => self.turn_right = b,
+                Ok(MovementEvent::Jump(b)) => self.jump = b,
+                Ok(MovementEvent::Dive(b)) => self.dive = b,
                 Err(_) => return,
             }
         }
@@ -142,22 +151,23 @@ impl MovementSystem {
         globe: &Globe,
         forward_or_backward: ForwardOrBackward,
     ) {
-        // Only allow movement if you're sitting above solid ground.
-        // (Or, rather, the stuff we consider to be solid for now,
-        // which is anything other than air.)
+        // Only allow movement if you're sitting above solid ground,
+        // or otherwise have something to push off from.
         //
         // TODO: Fix to be <= 0 and log error.
         if cd.pos.z < 0 {
             // There's nothing below; someone built a silly globe.
             return;
         }
-        let under_pos = cd.pos.with_z(cd.pos.z - 1);
-        let under_cell = match globe.maybe_non_authoritative_cell(under_pos) {
-            Ok(cell) => cell,
+        let profile = *cd.movement_profile();
+        let is_standing = match is_standing_on_solid_ground(cd, globe) {
+            Some(is_standing) => is_standing,
             // Chunk not loaded; wait until it is before attempting to move.
-            Err(_) => return,
+            None => return,
         };
-        if under_cell.material != Material::Dirt {
+        let is_swimming = is_swimming(cd, globe);
+        let is_clinging = profile.can_climb && is_facing_wall(cd, globe);
+        if !is_standing && !is_swimming && !is_clinging {
             return;
         }
 
@@ -186,15 +196,20 @@ impl MovementSystem {
         }.expect("CellDweller should have been in good state.");
 
         // Ask the globe if we can go there, attempting to climb up if there is a hil/cliff.
-        // Usually we'll allow climbing a maximum of one block, but especially in certain tests
-        // we want to let you climb higher!
-        for _ in 0..(self.max_step_height + 1) {
+        // How high we can go depends on the cell dweller, and whether it's jumping.
+        // Swimmers can kick off to climb out of the water, too.
+        let max_step_height = if self.jump && (is_standing || is_swimming) {
+            profile.max_step_height + profile.jump_height
+        } else {
+            profile.max_step_height
+        };
+        for _ in 0..(max_step_height + 1) {
             let cell = match globe.maybe_non_authoritative_cell(new_pos) {
                 Ok(cell) => cell,
                 // Chunk not loaded; wait until it is before attempting to move.
                 Err(_) => return,
             };
-            let can_move_to_cell = cell.material != Material::Dirt;
+            let can_move_to_cell = !cell.material.is_solid();
 
             if !can_move_to_cell {
                 // Try again one higher.
@@ -202,14 +217,105 @@ impl MovementSystem {
                 continue;
             }
 
+            let move_delay = move_delay(cd, globe);
             cd.set_cell_transform(new_pos, new_dir, new_last_turn_bias);
             // REVISIT: += ?
-            cd.seconds_until_next_move = cd.seconds_between_moves;
+            cd.seconds_until_next_move = move_delay;
             trace!(self.log, "Stepped"; "new_pos" => format!("{:?}", cd.pos()), "new_dir" => format!("{:?}", cd.dir()));
 
-            break;
+            return;
+        }
+
+        // It's too high to step up. If we can climb, then
+        // start climbing up the wall instead.
+        if let ForwardOrBackward::Forward = forward_or_backward {
+            if profile.can_climb && is_facing_wall(cd, globe) {
+                self.rise_if_possible(cd, globe, 1);
+            }
+        }
+    }
+
+    // Jump straight up, if there's something to jump off.
+    fn jump_if_possible(&self, cd: &mut CellDweller, globe: &Globe) {
+        if cd.pos.z < 0 {
+            // There's nothing below; someone built a silly globe.
+            return;
+        }
+        if is_standing_on_solid_ground(cd, globe) != Some(true) {
+            return;
+        }
+        let jump_height = cd.movement_profile().jump_height;
+        self.rise_if_possible(cd, globe, jump_height);
+    }
+
+    // Swim up a cell, but not out of the top of the water;
+    // once we're at the surface we just float there.
+    fn swim_up_if_possible(&self, cd: &mut CellDweller, globe: &Globe) {
+        let above_pos = cd.pos.with_z(cd.pos.z + 1);
+        let is_water_above = globe
+            .maybe_non_authoritative_cell(above_pos)
+            .map(|cell| cell.material == Material::Water)
+            .unwrap_or(false);
+        if is_water_above {
+            self.rise_if_possible(cd, globe, 1);
         }
     }
+
+    // Swim down a cell, if there's more water below.
+    fn swim_down_if_possible(&self, cd: &mut CellDweller, globe: &Globe) {
+        if cd.pos.z <= 0 {
+            return;
+        }
+        let under_pos = cd.pos.with_z(cd.pos.z - 1);
+        let is_water_below = globe
+            .maybe_non_authoritative_cell(under_pos)
+            .map(|cell| cell.material == Material::Water)
+            .unwrap_or(false);
+        if !is_water_below {
+            return;
+        }
+        let move_delay = move_delay(cd, globe);
+        let (dir, last_turn_bias) = (cd.dir, cd.last_turn_bias);
+        cd.set_cell_transform(under_pos, dir, last_turn_bias);
+        cd.seconds_until_next_move = move_delay;
+        trace!(self.log, "Dove"; "new_pos" => format!("{:?}", cd.pos()));
+    }
+
+    // Move straight up by as many as `max_cells`, stopping short of anything solid.
+    fn rise_if_possible(&self, cd: &mut CellDweller, globe: &Globe, max_cells: GridCoord) {
+        let mut new_pos = cd.pos;
+        for _ in 0..max_cells {
+            let above_pos = new_pos.with_z(new_pos.z + 1);
+            let is_clear = globe
+                .maybe_non_authoritative_cell(above_pos)
+                .map(|cell| !cell.material.is_solid())
+                // Chunk not loaded; don't go there yet.
+                .unwrap_or(false);
+            if !is_clear {
+                break;
+            }
+            new_pos = above_pos;
+        }
+        if new_pos == cd.pos {
+            return;
+        }
+        let move_delay = move_delay(cd, globe);
+        let (dir, last_turn_bias) = (cd.dir, cd.last_turn_bias);
+        cd.set_cell_transform(new_pos, dir, last_turn_bias);
+        cd.seconds_until_next_move = move_delay;
+        trace!(self.log, "Rose"; "new_pos" => format!("{:?}", cd.pos()));
+    }
+}
+
+// How long the cell dweller has to wait after moving from where it is now;
+// it's slower in water.
+fn move_delay(cd: &CellDweller, globe: &Globe) -> TimeDelta {
+    let profile = cd.movement_profile();
+    if is_swimming(cd, globe) {
+        profile.seconds_between_moves * profile.swim_move_factor
+    } else {
+        profile.seconds_between_moves
+    }
 }
 
 impl<'a> specs::System<'a> for MovementSystem {
@@ -267,6 +373,8 @@ impl<'a> specs::System<'a> for MovementSystem {
             }
         };
 
+        // Let physics know whether to float us back up to the surface.
+        cd.is_diving = self.dive && is_swimming(cd, globe);
 
         // Count down until we're allowed to move next.
         if cd.seconds_until_next_move > 0.0 {
@@ -284,6 +392,14 @@ impl<'a> specs::System<'a> for MovementSystem {
                 ForwardOrBackward::Backward
             };
             self.step_if_possible(cd, globe, forward_or_backward);
+        } else if !still_waiting_to_move && self.jump {
+            if is_swimming(cd, globe) {
+                self.swim_up_if_possible(cd, globe);
+            } else {
+                self.jump_if_possible(cd, globe);
+            }
+        } else if !still_waiting_to_move && self.dive && is_swimming(cd, globe) {
+            self.swim_down_if_possible(cd, globe);
         }
 
         // Count down until we're allowed to turn next.
@@ -294,11 +410,11 @@ impl<'a> specs::System<'a> for MovementSystem {
         if !still_waiting_to_turn {
             if self.turn_left && !self.turn_right {
                 cd.turn(TurnDir::Left);
-                cd.seconds_until_next_turn = cd.seconds_between_turns;
+                cd.seconds_until_next_turn = cd.movement_profile().seconds_between_turns;
                 trace!(self.log, "Turned left"; "new_pos" => format!("{:?}", cd.pos()), "new_dir" => format!("{:?}", cd.dir()));
             } else if self.turn_right && !self.turn_left {
                 cd.turn(TurnDir::Right);
-                cd.seconds_until_next_turn = cd.seconds_between_turns;
+                cd.seconds_until_next_turn = cd.movement_profile().seconds_between_turns;
                 trace!(self.log, "Turned right"; "new_pos" => format!("{:?}", cd.pos()), "new_dir" => format!("{:?}", cd.dir()));
             }
         }
@@ -348,3 +464,17 @@ impl<'a> specs::System<'a> for MovementSystem {
         }
     }
 }
+
+impl AutoSystem for MovementSystem {
+    fn name(&self) -> &'static str {
+        "cd_movement"
+    }
+
+    fn init(&mut self, world: &mut specs::World) {
+        ActiveCellDweller::ensure_registered(world);
+        ensure_component_registered::<CellDweller>(world);
+        ensure_component_registered::<Spatial>(world);
+        ensure_component_registered::<Globe>(world);
+        ensure_component_registered::<NetMarker>(world);
+    }
+}
@@ -1,77 +1,216 @@
 use specs;
-use specs::{ReadStorage, WriteStorage, Fetch};
+use specs::{Entities, ReadStorage, WriteStorage, Fetch, FetchMut};
 use slog::Logger;
 
 use types::*;
+use grid::GridCoord;
 use super::CellDweller;
+use super::surroundings::{is_in_water, is_swimming, is_submerged, is_facing_wall};
 use Spatial;
 use globe::Globe;
-use globe::chunk::Material;
+use auto_system::{AutoSystem, ensure_component_registered};
+
+// How many times faster cell dwellers catch their breath
+// at the surface than they lose it under water.
+const BREATH_RECOVERY_RATE: f64 = 4.0;
+
+// Water slows down anything sinking through it.
+const WATER_TERMINAL_VELOCITY_FACTOR: f64 = 0.2;
+
+// How fast swimmers float up to the surface when they aren't diving,
+// in cells per second.
+const FLOAT_SPEED: f64 = 2.0;
+
+/// A `CellDweller` coming to rest after falling.
+#[derive(Clone, Copy, Debug)]
+pub struct Landing {
+    pub entity: specs::Entity,
+    /// How many cells lower the cell dweller is than where it started falling.
+    pub cells_fallen: GridCoord,
+    /// How fast the cell dweller was falling when it landed, in cells per second.
+    pub impact_speed: f64,
+    /// Whether it landed in water rather than on something solid.
+    pub in_water: bool,
+}
+
+/// `World`-global resource listing every `CellDweller` that landed
+/// during the last run of the `PhysicsSystem`.
+///
+/// Useful for applying fall damage or playing landing effects.
+/// Each run of the `PhysicsSystem` starts a fresh list, so read it from
+/// a tick system to catch every landing. A frame system only sees whoever
+/// landed in the last tick before it ran, and would miss the rest.
+pub struct Landings {
+    pub landings: Vec<Landing>,
+}
+
+impl ::AutoResource for Landings {
+    fn new(_world: &mut specs::World) -> Landings {
+        Landings { landings: Vec::new() }
+    }
+}
 
 pub struct PhysicsSystem {
     log: Logger,
-    pub seconds_between_falls: TimeDelta,
+    /// How quickly cell dwellers speed up when they fall, in cells per second squared.
+    pub gravity: f64,
+    /// The fastest cell dwellers can fall, in cells per second.
+    pub terminal_velocity: f64,
 }
 
 impl PhysicsSystem {
-    pub fn new(parent_log: &Logger, seconds_between_falls: TimeDelta) -> PhysicsSystem {
+    pub fn new(
+        world: &mut specs::World,
+        parent_log: &Logger,
+        gravity: f64,
+        terminal_velocity: f64,
+    ) -> PhysicsSystem {
+        use ::AutoResource;
+
+        Landings::ensure(world);
+
         PhysicsSystem {
             log: parent_log.new(o!()),
-            seconds_between_falls: seconds_between_falls,
+            gravity: gravity,
+            terminal_velocity: terminal_velocity,
         }
     }
 
-    // Fall under the force of gravity if there's anywhere to fall to.
-    // Note that "gravity" moves you down at a constant speed;
-    // i.e. it doesn't accelerate you like in the real world.
-    fn maybe_fall(&self, cd: &mut CellDweller, globe: &Globe, dt: TimeDelta) {
-        // Only make you fall if there's air below you.
-        if cd.pos.z <= 0 {
-            // There's nothing below; someone built a silly globe.
-            return;
-        }
-        let under_pos = cd.pos.with_z(cd.pos.z - 1);
-        let under_cell = match globe.maybe_non_authoritative_cell(under_pos) {
-            Ok(cell) => cell,
+    // Fall under the force of gravity if there's anywhere to fall to,
+    // speeding up the longer we fall.
+    //
+    // Returns a `Landing` if we just came to rest after falling.
+    fn maybe_fall(&self, entity: specs::Entity, cd: &mut CellDweller, globe: &Globe, dt: TimeDelta) -> Option<Landing> {
+        match self.support(cd, globe) {
             // Chunk not loaded; wait until it is before attempting to fall.
-            Err(_) => return,
+            None => return None,
+            Some(Support::Supported { in_water }) => return self.land(entity, cd, in_water),
+            Some(Support::Unsupported) => (),
+        }
+
+        if cd.fall_start_z.is_none() {
+            cd.fall_start_z = Some(cd.pos.z);
+        }
+        let terminal_velocity = if is_in_water(cd, globe) {
+            self.terminal_velocity * WATER_TERMINAL_VELOCITY_FACTOR
+        } else {
+            self.terminal_velocity
         };
+        cd.vertical_velocity = (cd.vertical_velocity - self.gravity * dt).max(-terminal_velocity);
+        cd.fall_progress += -cd.vertical_velocity * dt;
 
-        if under_cell.material == Material::Dirt {
-            // Reset time until we can fall to the time
-            // between falls; we don't want to instantly
-            // fall down every step of size 1.
-            cd.seconds_until_next_fall = self.seconds_between_falls;
-            return;
+        // Move down a cell at a time, in case we're falling
+        // fast enough to hit something part way.
+        while cd.fall_progress >= 1.0 {
+            match self.support(cd, globe) {
+                None => return None,
+                Some(Support::Supported { in_water }) => return self.land(entity, cd, in_water),
+                Some(Support::Unsupported) => (),
+            }
+            let under_pos = cd.pos.with_z(cd.pos.z - 1);
+            cd.set_grid_point(under_pos);
+            cd.fall_progress -= 1.0;
+            trace!(self.log, "Fell under force of gravity"; "new_pos" => format!("{:?}", cd.pos()), "speed" => -cd.vertical_velocity);
         }
+        None
+    }
+
+    // Stop falling, if we were.
+    fn land(&self, entity: specs::Entity, cd: &mut CellDweller, in_water: bool) -> Option<Landing> {
+        let impact_speed = -cd.vertical_velocity;
+        cd.vertical_velocity = 0.0;
+        cd.fall_progress = 0.0;
+        cd.fall_start_z.take().map(|fall_start_z| {
+            let landing = Landing {
+                entity: entity,
+                cells_fallen: fall_start_z - cd.pos.z,
+                impact_speed: impact_speed,
+                in_water: in_water,
+            };
+            debug!(self.log, "Landed"; "cells_fallen" => landing.cells_fallen, "impact_speed" => impact_speed);
+            landing
+        })
+    }
 
-        // Count down until we're allowed to fall next.
-        if cd.seconds_until_next_fall > 0.0 {
-            cd.seconds_until_next_fall = (cd.seconds_until_next_fall - dt).max(0.0);
+    // Float up toward the surface if we're swimming under water,
+    // unless we're trying to stay down there.
+    fn maybe_float(&self, cd: &mut CellDweller, globe: &Globe, dt: TimeDelta) {
+        if !is_swimming(cd, globe) || cd.is_diving || !is_submerged(cd, globe) {
+            cd.float_progress = 0.0;
+            return;
+        }
+        cd.float_progress += FLOAT_SPEED * dt;
+        // Stop as soon as there's no more water above us,
+        // so we float with our head out of the water.
+        while cd.float_progress >= 1.0 && is_submerged(cd, globe) {
+            let above_pos = cd.pos.with_z(cd.pos.z + 1);
+            cd.set_grid_point(above_pos);
+            cd.float_progress -= 1.0;
+            trace!(self.log, "Floated up"; "new_pos" => format!("{:?}", cd.pos()));
         }
-        let still_waiting_to_fall = cd.seconds_until_next_fall > 0.0;
-        if still_waiting_to_fall {
+    }
+
+    // Hold our breath while under water, and catch it again at the surface.
+    fn breathe(&self, cd: &mut CellDweller, globe: &Globe, dt: TimeDelta) {
+        if cd.movement_profile().max_breath_seconds.is_none() {
             return;
         }
+        let was_out_of_breath = cd.is_out_of_breath();
+        if is_submerged(cd, globe) {
+            cd.seconds_under_water += dt;
+        } else {
+            cd.seconds_under_water = (cd.seconds_under_water - dt * BREATH_RECOVERY_RATE).max(0.0);
+        }
+        if cd.is_out_of_breath() && !was_out_of_breath {
+            debug!(self.log, "Ran out of breath"; "pos" => format!("{:?}", cd.pos()));
+        }
+    }
 
-        // Move down by one cell.
-        cd.set_grid_point(under_pos);
-        // REVISIT: += ?
-        cd.seconds_until_next_fall = self.seconds_between_falls;
-        trace!(self.log, "Fell under force of gravity"; "new_pos" => format!("{:?}", cd.pos()));
+    // `None` if we don't know because the chunk isn't loaded.
+    fn support(&self, cd: &CellDweller, globe: &Globe) -> Option<Support> {
+        if cd.pos.z <= 0 {
+            // There's nothing below; someone built a silly globe.
+            return Some(Support::Supported { in_water: false });
+        }
+        let under_pos = cd.pos.with_z(cd.pos.z - 1);
+        let is_under_cell_solid = match globe.maybe_non_authoritative_cell(under_pos) {
+            Ok(cell) => cell.material.is_solid(),
+            Err(_) => return None,
+        };
+        if is_under_cell_solid {
+            return Some(Support::Supported { in_water: false });
+        }
+        // Cell dwellers that can swim or climb don't sink in water
+        // or slide down walls they're hanging onto.
+        // (Swimmers float back up instead, unless they're diving; see `maybe_float`.)
+        if is_swimming(cd, globe) {
+            return Some(Support::Supported { in_water: true });
+        }
+        if cd.movement_profile().can_climb && is_facing_wall(cd, globe) {
+            return Some(Support::Supported { in_water: false });
+        }
+        Some(Support::Unsupported)
     }
 }
 
+enum Support {
+    Supported { in_water: bool },
+    Unsupported,
+}
+
 impl<'a> specs::System<'a> for PhysicsSystem {
     type SystemData = (Fetch<'a, TimeDeltaResource>,
+     Entities<'a>,
      WriteStorage<'a, CellDweller>,
      WriteStorage<'a, Spatial>,
-     ReadStorage<'a, Globe>);
+     ReadStorage<'a, Globe>,
+     FetchMut<'a, Landings>);
 
     fn run(&mut self, data: Self::SystemData) {
         use specs::Join;
-        let (dt, mut cell_dwellers, mut spatials, globes) = data;
-        for (cd, spatial) in (&mut cell_dwellers, &mut spatials).join() {
+        let (dt, entities, mut cell_dwellers, mut spatials, globes, mut landings) = data;
+        landings.landings.clear();
+        for (entity, cd, spatial) in (&*entities, &mut cell_dwellers, &mut spatials).join() {
             // Get the associated globe, complaining loudly if we fail.
             let globe_entity = match cd.globe_entity {
                 Some(globe_entity) => globe_entity,
@@ -94,7 +233,11 @@ impl<'a> specs::System<'a> for PhysicsSystem {
                 }
             };
 
-            self.maybe_fall(cd, globe, dt.0);
+            if let Some(landing) = self.maybe_fall(entity, cd, globe, dt.0) {
+                landings.landings.push(landing);
+            }
+            self.maybe_float(cd, globe, dt.0);
+            self.breathe(cd, globe, dt.0);
 
             // Update real-space coordinates if necessary.
             // TODO: do this in a separate system; it needs to be done before
@@ -107,3 +250,21 @@ impl<'a> specs::System<'a> for PhysicsSystem {
         }
     }
 }
+
+impl AutoSystem for PhysicsSystem {
+    fn name(&self) -> &'static str {
+        "physics"
+    }
+
+    fn dependencies(&self) -> Vec<&'static str> {
+        // Apply gravity after any voluntary movement this tick,
+        // and after any digging that might have left nothing underfoot.
+        vec!["cd_movement", "cd_mining"]
+    }
+
+    fn init(&mut self, world: &mut specs::World) {
+        ensure_component_registered::<CellDweller>(world);
+        ensure_component_registered::<Spatial>(world);
+        ensure_component_registered::<Globe>(world);
+    }
+}
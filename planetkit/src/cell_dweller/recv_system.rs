@@ -1,5 +1,6 @@
+use std::collections::HashMap;
 use specs;
-use specs::{WriteStorage, Fetch, FetchMut};
+use specs::{ReadStorage, WriteStorage, Fetch, FetchMut};
 use slog::Logger;
 
 use super::{
@@ -9,10 +10,18 @@ use super::{
     CellDwellerMessage,
     SendMessage,
     RemoveBlockMessage,
+    PlaceBlockMessage,
+    SetInventoryMessage,
+    Inventory,
+    ItemKind,
+    Tool,
 };
 use Spatial;
-use grid::PosInOwningRoot;
-use globe::Globe;
+use types::*;
+use grid::{GridPoint3, Dir, PosInOwningRoot};
+use globe::{Globe, set_fluid_level, set_fallen_cell};
+use globe::chunk::Material;
+use auto_system::ensure_component_registered;
 use net::{
     EntityIds,
     NodeResource,
@@ -20,8 +29,21 @@ use net::{
     Transport,
 };
 
+// Where a cell dweller was standing and facing when it told us
+// it had started digging, and when that was.
+struct StartedDig {
+    pos: GridPoint3,
+    dir: Dir,
+    started_at: TimeDelta,
+}
+
 pub struct RecvSystem {
     log: Logger,
+    // How long this system has been running for,
+    // to time how long cell dwellers have been digging.
+    seconds_elapsed: TimeDelta,
+    // Digs we've been told about, by cell dweller global ID.
+    started_digs: HashMap<u64, StartedDig>,
 }
 
 impl RecvSystem {
@@ -31,18 +53,24 @@ impl RecvSystem {
     ) -> RecvSystem {
         use ::AutoResource;
         RecvMessageQueue::ensure(world);
+        ensure_component_registered::<Tool>(world);
 
         RecvSystem {
             log: parent_log.new(o!()),
+            seconds_elapsed: 0.0,
+            started_digs: HashMap::new(),
         }
     }
 }
 
 impl<'a> specs::System<'a> for RecvSystem {
     type SystemData = (
+        Fetch<'a, TimeDeltaResource>,
         WriteStorage<'a, Globe>,
         WriteStorage<'a, CellDweller>,
         WriteStorage<'a, Spatial>,
+        WriteStorage<'a, Inventory>,
+        ReadStorage<'a, Tool>,
         FetchMut<'a, RecvMessageQueue>,
         FetchMut<'a, SendMessageQueue>,
         Fetch<'a, EntityIds>,
@@ -51,14 +79,18 @@ impl<'a> specs::System<'a> for RecvSystem {
 
     fn run(&mut self, data: Self::SystemData) {
         let (
+            dt,
             mut globes,
             mut cell_dwellers,
             mut spatials,
+            mut inventories,
+            tools,
             mut recv_message_queue,
             mut send_message_queue,
             entity_ids,
             node_resource,
         ) = data;
+        self.seconds_elapsed += dt.0;
 
         // Slurp all inbound messages.
         while let Some(message) = recv_message_queue.queue.pop_front() {
@@ -120,6 +152,31 @@ impl<'a> specs::System<'a> for RecvSystem {
                         )
                     }
                 },
+                CellDwellerMessage::StartDigging(start_digging_message) => {
+                    // TODO: validate that we are the server.
+
+                    // Look up the entity from its global ID.
+                    let cell_dweller_entity = match entity_ids.mapping.get(&start_digging_message.cd_entity_id) {
+                        Some(ent) => ent,
+                        // We probably just don't know about it yet.
+                        None => {
+                            // TODO: demote to trace
+                            info!(self.log, "Heard about cell dweller we don't know about yet"; "entity_id" => start_digging_message.cd_entity_id);
+                            continue;
+                        },
+                    };
+                    let cd = cell_dwellers.get(*cell_dweller_entity).expect(
+                        "Missing CellDweller",
+                    );
+
+                    // Remember where they started from, so we can tell
+                    // if they give up and start digging somewhere else.
+                    self.started_digs.insert(start_digging_message.cd_entity_id, StartedDig {
+                        pos: cd.pos,
+                        dir: cd.dir,
+                        started_at: self.seconds_elapsed,
+                    });
+                },
                 CellDwellerMessage::TryPickUpBlock(try_pick_up_block_message) => {
                     // TODO: validate that we are the server.
 
@@ -160,6 +217,35 @@ impl<'a> specs::System<'a> for RecvSystem {
                         }
                     };
 
+                    // Make sure they've been digging the same block for long enough,
+                    // with whatever tool we think they're holding. This also takes
+                    // care of refusing to dig anything that's too hard to dig at all.
+                    let seconds_digging = match self.started_digs.remove(&try_pick_up_block_message.cd_entity_id) {
+                        Some(ref dig) if dig.pos == cd.pos && dig.dir == cd.dir => {
+                            self.seconds_elapsed - dig.started_at
+                        },
+                        _ => {
+                            debug!(self.log, "Refusing to pick up block; cell dweller wasn't digging it");
+                            continue;
+                        },
+                    };
+                    let tool = tools.get(*cell_dweller_entity).cloned();
+                    if !super::mining::has_dug_long_enough(cd, globe, tool, seconds_digging) {
+                        debug!(self.log, "Refusing to pick up block; cell dweller hasn't been digging for long enough"; "seconds_digging" => seconds_digging);
+                        continue;
+                    }
+
+                    // Don't dig anything out if there's nowhere to put it.
+                    if let Some(inventory) = inventories.get(*cell_dweller_entity) {
+                        let has_room = super::mining::material_in_front(cd, globe)
+                            .map(|material| inventory.room_for(ItemKind::Block(material)) > 0)
+                            .unwrap_or(false);
+                        if !has_room {
+                            debug!(self.log, "Inventory is full; refusing to pick up block");
+                            continue;
+                        }
+                    }
+
                     // TODO: validate that peer is allowed to remove the block.
                     // TODO: handle their source position and target pickup spot.
                     // Initially just trust the client is honest.
@@ -190,6 +276,22 @@ impl<'a> specs::System<'a> for RecvSystem {
                                 transport: Transport::TCP,
                             }
                         );
+
+                        // Put it in their pocket, if they have one.
+                        if let Some(inventory) = inventories.get_mut(*cell_dweller_entity) {
+                            let leftover = inventory.add(ItemKind::Block(cell.material), 1);
+                            debug_assert_eq!(leftover, 0, "Should have checked for room before picking up");
+                            send_message_queue.queue.push_back(
+                                SendMessage {
+                                    destination: Destination::One(message.source),
+                                    game_message: CellDwellerMessage::SetInventory(SetInventoryMessage {
+                                        cd_entity_id: try_pick_up_block_message.cd_entity_id,
+                                        inventory: inventory.clone(),
+                                    }),
+                                    transport: Transport::TCP,
+                                }
+                            );
+                        }
                     }
                 },
                 CellDwellerMessage::RemoveBlock(remove_block_message) => {
@@ -203,10 +305,210 @@ impl<'a> specs::System<'a> for RecvSystem {
 
                     let pos_in_owning_root =
                         PosInOwningRoot::new(remove_block_message.pos, globe.spec().root_resolution);
+                    if !super::mining::is_cell_loaded(globe, pos_in_owning_root) {
+                        debug!(self.log, "Ignoring block removed from chunk that isn't loaded"; "pos" => format!("{:?}", remove_block_message.pos));
+                        continue;
+                    }
                     let removed_cell = super::mining::remove_block(globe, pos_in_owning_root);
 
                     debug!(self.log, "Removed a block master told me to"; "pos" => format!("{:?}", remove_block_message.pos), "cell" => format!("{:?}", removed_cell));
                 },
+                CellDwellerMessage::RemoveBlocks(remove_blocks_message) => {
+                    // For now just find the first globe, and assume that's
+                    // the one we're supposed to be working with.
+                    use specs::Join;
+                    let globe = (&mut globes).join().next().expect("Should've been at least one globe.");
+
+                    // TODO: validate that positions make sense; see `RemoveBlock` above.
+
+                    for pos in &remove_blocks_message.positions {
+                        let pos_in_owning_root = PosInOwningRoot::new(*pos, globe.spec().root_resolution);
+                        // TODO: remember edits to chunks we don't have loaded.
+                        if super::mining::is_cell_loaded(globe, pos_in_owning_root) {
+                            super::mining::remove_block(globe, pos_in_owning_root);
+                        }
+                    }
+
+                    debug!(self.log, "Removed blocks master told me to"; "count" => remove_blocks_message.positions.len());
+                },
+                CellDwellerMessage::TryPlaceBlock(try_place_block_message) => {
+                    // TODO: validate that we are the server.
+
+                    // Look up the entity from its global ID.
+                    let cell_dweller_entity = match entity_ids.mapping.get(&try_place_block_message.cd_entity_id) {
+                        Some(ent) => ent,
+                        // We probably just don't know about it yet.
+                        None => {
+                            // TODO: demote to trace
+                            info!(self.log, "Heard about cell dweller we don't know about yet"; "entity_id" => try_place_block_message.cd_entity_id);
+                            continue;
+                        },
+                    };
+                    let cd = cell_dwellers.get(*cell_dweller_entity).expect(
+                        "Missing CellDweller",
+                    );
+
+                    // Get the associated globe, complaining loudly if we fail.
+                    // TODO: again, need a pattern for this that isn't awful.
+                    let globe_entity = match cd.globe_entity {
+                        Some(globe_entity) => globe_entity,
+                        None => {
+                            warn!(
+                                self.log,
+                                "There was no associated globe entity or it wasn't actually a Globe! Can't proceed!"
+                            );
+                            continue;
+                        }
+                    };
+                    let globe = match globes.get_mut(globe_entity) {
+                        Some(globe) => globe,
+                        None => {
+                            warn!(
+                                self.log,
+                                "The globe associated with this CellDweller is not alive! Can't proceed!"
+                            );
+                            continue;
+                        }
+                    };
+
+                    // TODO: validate that peer is allowed to place the block.
+                    let pos_in_owning_root = match super::mining::placement_target(cd, globe) {
+                        Some(pos) => pos,
+                        None => continue,
+                    };
+
+                    // Don't let anyone bury another cell dweller (or themself),
+                    // or place a block of air.
+                    let material = try_place_block_message.material;
+                    let can_place = {
+                        use specs::Join;
+                        super::mining::can_place_block(globe, pos_in_owning_root, material, (&cell_dwellers).join())
+                    };
+                    if !can_place {
+                        debug!(self.log, "Refusing to place block"; "pos" => format!("{:?}", pos_in_owning_root), "material" => format!("{:?}", material));
+                        continue;
+                    }
+
+                    // If they're carrying an inventory, then they need
+                    // to have the block in it. Otherwise they can build
+                    // as much as they like.
+                    if let Some(inventory) = inventories.get_mut(*cell_dweller_entity) {
+                        if !inventory.remove(ItemKind::Block(material), 1) {
+                            debug!(self.log, "Refusing to place a block the cell dweller isn't carrying"; "material" => format!("{:?}", material));
+                            continue;
+                        }
+                        send_message_queue.queue.push_back(
+                            SendMessage {
+                                destination: Destination::One(message.source),
+                                game_message: CellDwellerMessage::SetInventory(SetInventoryMessage {
+                                    cd_entity_id: try_place_block_message.cd_entity_id,
+                                    inventory: inventory.clone(),
+                                }),
+                                transport: Transport::TCP,
+                            }
+                        );
+                    }
+
+                    let replaced_cell = super::mining::place_block(globe, pos_in_owning_root, material);
+                    debug!(self.log, "Placed a block because a peer asked"; "pos" => format!("{:?}", pos_in_owning_root), "replaced_cell" => format!("{:?}", replaced_cell));
+
+                    // Tell everyone else what happened.
+                    // TODO: see notes on `RemoveBlock` above.
+                    let place_block_message = PlaceBlockMessage {
+                        pos: pos_in_owning_root.into(),
+                        material: material,
+                    };
+                    send_message_queue.queue.push_back(
+                        SendMessage {
+                            destination: Destination::EveryoneElse,
+                            game_message: CellDwellerMessage::PlaceBlock(place_block_message),
+                            transport: Transport::TCP,
+                        }
+                    );
+                },
+                CellDwellerMessage::PlaceBlock(place_block_message) => {
+                    // For now just find the first globe, and assume that's
+                    // the one we're supposed to be working with.
+                    use specs::Join;
+                    let globe = (&mut globes).join().next().expect("Should've been at least one globe.");
+
+                    // TODO: validate that this came from the master.
+
+                    // We can't place blocks in chunks we don't have loaded,
+                    // and the master should never ask us to place air.
+                    let pos_in_owning_root =
+                        PosInOwningRoot::new(place_block_message.pos, globe.spec().root_resolution);
+                    if !super::mining::is_cell_loaded(globe, pos_in_owning_root) {
+                        debug!(self.log, "Ignoring block placed in chunk that isn't loaded"; "pos" => format!("{:?}", place_block_message.pos));
+                        continue;
+                    }
+                    if place_block_message.material == Material::Air {
+                        warn!(self.log, "Master asked to place a block of air; ignoring");
+                        continue;
+                    }
+                    let replaced_cell = super::mining::place_block(globe, pos_in_owning_root, place_block_message.material);
+
+                    debug!(self.log, "Placed a block master told me to"; "pos" => format!("{:?}", place_block_message.pos), "replaced_cell" => format!("{:?}", replaced_cell));
+                },
+                CellDwellerMessage::SetFluidLevels(set_fluid_levels_message) => {
+                    // For now just find the first globe, and assume that's
+                    // the one we're supposed to be working with.
+                    use specs::Join;
+                    let globe = (&mut globes).join().next().expect("Should've been at least one globe.");
+
+                    // TODO: validate that this came from the master.
+
+                    for &(pos, fluid_level) in &set_fluid_levels_message.cells {
+                        let pos_in_owning_root = PosInOwningRoot::new(pos, globe.spec().root_resolution);
+                        // TODO: remember edits to chunks we don't have loaded.
+                        if super::mining::is_cell_loaded(globe, pos_in_owning_root) {
+                            set_fluid_level(globe, pos_in_owning_root, fluid_level);
+                        }
+                    }
+
+                    trace!(self.log, "Water flowed because master told me to"; "cells" => set_fluid_levels_message.cells.len());
+                },
+                CellDwellerMessage::SetFallenBlocks(set_fallen_blocks_message) => {
+                    // For now just find the first globe, and assume that's
+                    // the one we're supposed to be working with.
+                    use specs::Join;
+                    let globe = (&mut globes).join().next().expect("Should've been at least one globe.");
+
+                    // TODO: validate that this came from the master.
+
+                    for &(pos, material, fluid_level) in &set_fallen_blocks_message.cells {
+                        let pos_in_owning_root = PosInOwningRoot::new(pos, globe.spec().root_resolution);
+                        // TODO: remember edits to chunks we don't have loaded.
+                        if super::mining::is_cell_loaded(globe, pos_in_owning_root) {
+                            set_fallen_cell(globe, pos_in_owning_root, material, fluid_level);
+                        }
+                    }
+
+                    trace!(self.log, "Blocks fell because master told me to"; "cells" => set_fallen_blocks_message.cells.len());
+                },
+                CellDwellerMessage::SetInventory(set_inventory_message) => {
+                    // Look up the entity from its global ID.
+                    let cell_dweller_entity = match entity_ids.mapping.get(&set_inventory_message.cd_entity_id) {
+                        Some(ent) => ent,
+                        // We probably just don't know about it yet.
+                        None => {
+                            // TODO: demote to trace
+                            info!(self.log, "Heard about cell dweller we don't know about yet"; "entity_id" => set_inventory_message.cd_entity_id);
+                            continue;
+                        },
+                    };
+
+                    // TODO: validate that this came from the master.
+                    debug!(self.log, "Updating inventory because master told me to"; "inventory" => format!("{:?}", set_inventory_message.inventory));
+                    let mut inventory = set_inventory_message.inventory;
+                    // Which slot is selected is up to the owning peer, not the master.
+                    if let Some(old_inventory) = inventories.get(*cell_dweller_entity) {
+                        if old_inventory.selected_slot() < inventory.slots().len() {
+                            inventory.select_slot(old_inventory.selected_slot());
+                        }
+                    }
+                    inventories.insert(*cell_dweller_entity, inventory);
+                },
             }
         }
     }
@@ -0,0 +1,48 @@
+// Questions about what's around a `CellDweller`, shared by the systems that move it.
+//
+// These all assume that the given CellDweller is indeed attached to the given globe.
+
+use super::CellDweller;
+use globe::Globe;
+use globe::chunk::Material;
+
+/// `None` if we don't know because the chunk isn't loaded.
+pub fn is_standing_on_solid_ground(cd: &CellDweller, globe: &Globe) -> Option<bool> {
+    if cd.pos.z <= 0 {
+        return Some(false);
+    }
+    let under_pos = cd.pos.with_z(cd.pos.z - 1);
+    globe
+        .maybe_non_authoritative_cell(under_pos)
+        .map(|cell| cell.material.is_solid())
+        .ok()
+}
+
+pub fn is_in_water(cd: &CellDweller, globe: &Globe) -> bool {
+    globe
+        .maybe_non_authoritative_cell(cd.pos)
+        .map(|cell| cell.material == Material::Water)
+        .unwrap_or(false)
+}
+
+/// Whether the cell dweller is in water with more water above it,
+/// so it can't come up for air.
+pub fn is_submerged(cd: &CellDweller, globe: &Globe) -> bool {
+    is_in_water(cd, globe) &&
+        globe
+            .maybe_non_authoritative_cell(cd.pos.with_z(cd.pos.z + 1))
+            .map(|cell| cell.material == Material::Water)
+            .unwrap_or(false)
+}
+
+/// Whether the cell dweller can swim, and is in the water.
+pub fn is_swimming(cd: &CellDweller, globe: &Globe) -> bool {
+    cd.movement_profile().can_swim && is_in_water(cd, globe)
+}
+
+pub fn is_facing_wall(cd: &CellDweller, globe: &Globe) -> bool {
+    globe
+        .maybe_non_authoritative_cell(cd.pos_in_front())
+        .map(|cell| cell.material.is_solid())
+        .unwrap_or(false)
+}
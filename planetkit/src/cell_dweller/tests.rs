@@ -0,0 +1,510 @@
+use std::sync::mpsc;
+use rand::{XorShiftRng, SeedableRng};
+use slog;
+use specs;
+
+use types::*;
+use grid::{GridCoord, GridPoint3, PosInOwningRoot, Dir};
+use globe::Globe;
+use globe::chunk::Material;
+use movement::move_forward;
+use auto_system::{AutoSystem, ensure_component_registered};
+use Spatial;
+use super::*;
+use super::mining::{placement_target, dig_seconds, has_dug_long_enough, DIG_SECONDS_TOLERANCE};
+
+// A cell dweller standing on dry land, with every chunk
+// within a few cells of it loaded.
+fn cell_dweller_on_dry_land() -> (Globe, CellDweller) {
+    let mut globe = Globe::new_example();
+    let spec = globe.spec();
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let pos = globe
+        .air_above_random_surface_dry_land(
+            &mut rng,
+            3, // Min air cells above
+            5, // Max distance from starting point
+            5, // Max attempts
+        )
+        .expect("Should have been able to find some land");
+    load_chunks_around(&mut globe, pos, 4.0);
+    let cd = CellDweller::new(pos, Dir::default(), spec, None);
+    (globe, cd)
+}
+
+fn load_chunks_around(globe: &mut Globe, pos: GridPoint3, radius: f64) {
+    let center = globe.spec().cell_center_center(pos);
+    for cell_pos in globe.cells_in_sphere(center, radius).unloaded {
+        let chunk_origin = globe.origin_of_chunk_owning(cell_pos);
+        globe.ensure_chunk_present(chunk_origin);
+    }
+}
+
+// A world with nothing in it but the given globe and cell dweller,
+// with the cell dweller under the player's control.
+struct TestWorld {
+    world: specs::World,
+    cd_entity: specs::Entity,
+}
+
+impl TestWorld {
+    fn new(globe: Globe, mut cd: CellDweller) -> TestWorld {
+        let mut world = specs::World::new();
+        world.add_resource(TimeDeltaResource(0.0));
+        ActiveCellDweller::ensure_registered(&mut world);
+        ensure_component_registered::<CellDweller>(&mut world);
+        ensure_component_registered::<Spatial>(&mut world);
+        ensure_component_registered::<Globe>(&mut world);
+
+        let globe_entity = world.create_entity().with(globe).build();
+        cd.globe_entity = Some(globe_entity);
+        let cd_entity = world
+            .create_entity()
+            .with(cd)
+            .with(Spatial::new_root())
+            .build();
+        world.write_resource::<ActiveCellDweller>().maybe_entity = Some(cd_entity);
+
+        TestWorld {
+            world: world,
+            cd_entity: cd_entity,
+        }
+    }
+
+    fn log() -> slog::Logger {
+        slog::Logger::root(slog::Discard, o!("pk_version" => env!("CARGO_PKG_VERSION")))
+    }
+
+    // Run the given system for one tick of `dt` seconds.
+    fn tick<S>(&mut self, system: &mut S, dt: TimeDelta)
+    where
+        S: for<'a> specs::System<'a>,
+    {
+        use specs::RunNow;
+        self.world.write_resource::<TimeDeltaResource>().0 = dt;
+        system.run_now(&self.world.res);
+        self.world.maintain();
+    }
+
+    fn cd_pos(&self) -> GridPoint3 {
+        self.world.read::<CellDweller>().get(self.cd_entity).unwrap().pos
+    }
+
+    fn cd_vertical_velocity(&self) -> f64 {
+        self.world.read::<CellDweller>().get(self.cd_entity).unwrap().vertical_velocity
+    }
+
+    fn landings(&self) -> Vec<Landing> {
+        self.world.read_resource::<Landings>().landings.clone()
+    }
+}
+
+fn pos_in_front(cd: &CellDweller) -> GridPoint3 {
+    let mut pos = cd.pos;
+    let mut dir = cd.dir;
+    move_forward(&mut pos, &mut dir, cd.globe_spec.root_resolution)
+        .expect("CellDweller should have been in good state.");
+    pos
+}
+
+fn set_material(globe: &mut Globe, pos: GridPoint3, material: Material) {
+    let pos_in_owning_root = PosInOwningRoot::new(pos, globe.spec().root_resolution);
+    place_block(globe, pos_in_owning_root, material);
+}
+
+fn material_at(globe: &Globe, pos: GridPoint3) -> Material {
+    globe.authoritative_cell(PosInOwningRoot::new(pos, globe.spec().root_resolution)).material
+}
+
+#[test]
+fn blocks_can_be_placed_in_air() {
+    let (mut globe, cd) = cell_dweller_on_dry_land();
+    let front = pos_in_front(&cd);
+    set_material(&mut globe, front, Material::Air);
+
+    let target = placement_target(&cd, &globe).expect("Should be able to place a block in air");
+    assert_eq!(target, PosInOwningRoot::new(front, globe.spec().root_resolution));
+    assert!(can_place_block(&globe, target, Material::Sand, vec![&cd]));
+    // But there's no such thing as a block of air.
+    assert!(!can_place_block(&globe, target, Material::Air, vec![&cd]));
+
+    let replaced_cell = place_block(&mut globe, target, Material::Sand);
+    assert_eq!(replaced_cell.material, Material::Air);
+    assert_eq!(material_at(&globe, front), Material::Sand);
+}
+
+#[test]
+fn blocks_cant_be_placed_in_solid_cells() {
+    let (mut globe, cd) = cell_dweller_on_dry_land();
+    let front = pos_in_front(&cd);
+    set_material(&mut globe, front, Material::Dirt);
+
+    assert_eq!(placement_target(&cd, &globe), None);
+    let front_in_owning_root = PosInOwningRoot::new(front, globe.spec().root_resolution);
+    assert!(!can_place_block(&globe, front_in_owning_root, Material::Sand, vec![&cd]));
+}
+
+#[test]
+fn blocks_cant_be_placed_on_cell_dwellers() {
+    let (globe, cd) = cell_dweller_on_dry_land();
+    let own_pos = PosInOwningRoot::new(cd.pos, globe.spec().root_resolution);
+    assert_eq!(material_at(&globe, cd.pos), Material::Air);
+    assert!(!can_place_block(&globe, own_pos, Material::Dirt, vec![&cd]));
+    // It's only the cell dweller in the way.
+    assert!(can_place_block(&globe, own_pos, Material::Dirt, Vec::<&CellDweller>::new()));
+}
+
+#[test]
+fn dig_seconds_depends_on_material_and_tool() {
+    let (mut globe, cd) = cell_dweller_on_dry_land();
+    let front = pos_in_front(&cd);
+
+    set_material(&mut globe, front, Material::Dirt);
+    let bare_hands = dig_seconds(&cd, &globe, None).expect("Should be able to dig dirt");
+    assert_eq!(Some(bare_hands), Material::Dirt.hardness());
+    let with_shovel = dig_seconds(&cd, &globe, Some(Tool::Shovel)).unwrap();
+    assert_eq!(with_shovel, bare_hands / Tool::Shovel.dig_speed(Material::Dirt));
+    assert!(with_shovel < bare_hands);
+
+    set_material(&mut globe, front, Material::Gravel);
+    assert_eq!(dig_seconds(&cd, &globe, None), Material::Gravel.hardness());
+
+    // Nothing to dig.
+    set_material(&mut globe, front, Material::Air);
+    assert_eq!(dig_seconds(&cd, &globe, Some(Tool::Shovel)), None);
+}
+
+#[test]
+fn digging_has_to_take_long_enough() {
+    let (mut globe, cd) = cell_dweller_on_dry_land();
+    let front = pos_in_front(&cd);
+    set_material(&mut globe, front, Material::Dirt);
+
+    let required = dig_seconds(&cd, &globe, None).unwrap();
+    assert!(!has_dug_long_enough(&cd, &globe, None, 0.0));
+    assert!(!has_dug_long_enough(&cd, &globe, None, required - 2.0 * DIG_SECONDS_TOLERANCE));
+    assert!(has_dug_long_enough(&cd, &globe, None, required));
+    // A shovel makes it quicker.
+    let with_shovel = dig_seconds(&cd, &globe, Some(Tool::Shovel)).unwrap();
+    assert!(has_dug_long_enough(&cd, &globe, Some(Tool::Shovel), with_shovel));
+
+    // Water can't be dug at all, no matter how long you try.
+    set_material(&mut globe, front, Material::Water);
+    assert!(!has_dug_long_enough(&cd, &globe, Some(Tool::Shovel), 1000.0));
+}
+
+// A cell dweller on dry land with plenty of clear sky above it,
+// and a `MovementSystem` to push it around with.
+fn movement_test(
+    profile: MovementProfile,
+    build: &Fn(&mut Globe, &CellDweller),
+) -> (TestWorld, MovementSystem, mpsc::Sender<MovementEvent>, GridPoint3) {
+    let (mut globe, mut cd) = cell_dweller_on_dry_land();
+    cd.set_movement_profile(profile);
+    load_chunks_around(&mut globe, cd.pos, 8.0);
+    for dz in 0..6 {
+        set_material(&mut globe, cd.pos.with_z(cd.pos.z + dz), Material::Air);
+    }
+    build(&mut globe, &cd);
+    let start_pos = cd.pos;
+
+    let mut test_world = TestWorld::new(globe, cd);
+    let (movement_input_sender, movement_input_receiver) = mpsc::channel();
+    let mut movement_sys = MovementSystem::new(
+        &mut test_world.world,
+        movement_input_receiver,
+        &TestWorld::log(),
+    );
+    movement_sys.init(&mut test_world.world);
+    (test_world, movement_sys, movement_input_sender, start_pos)
+}
+
+// Put a wall of dirt in front of the cell dweller, this many cells high.
+fn build_wall(globe: &mut Globe, cd: &CellDweller, height: GridCoord) {
+    let front = pos_in_front(cd);
+    for dz in 0..6 {
+        let material = if dz < height { Material::Dirt } else { Material::Air };
+        set_material(globe, front.with_z(front.z + dz), material);
+    }
+}
+
+#[test]
+fn jumping_rises_no_higher_than_jump_height() {
+    let profile = MovementProfile {
+        jump_height: 2,
+        ..MovementProfile::default()
+    };
+    let (mut test_world, mut movement_sys, sender, start_pos) =
+        movement_test(profile, &|_globe, _cd| {});
+
+    sender.send(MovementEvent::Jump(true)).unwrap();
+    test_world.tick(&mut movement_sys, 1.0);
+    assert_eq!(test_world.cd_pos(), start_pos.with_z(start_pos.z + 2));
+
+    // There's nothing to jump off in mid-air.
+    test_world.tick(&mut movement_sys, 1.0);
+    assert_eq!(test_world.cd_pos(), start_pos.with_z(start_pos.z + 2));
+}
+
+#[test]
+fn jumping_stops_short_of_the_ceiling() {
+    let profile = MovementProfile {
+        jump_height: 3,
+        ..MovementProfile::default()
+    };
+    let (mut test_world, mut movement_sys, sender, start_pos) =
+        movement_test(profile, &|globe, cd| {
+            set_material(globe, cd.pos.with_z(cd.pos.z + 2), Material::Dirt);
+        });
+
+    sender.send(MovementEvent::Jump(true)).unwrap();
+    test_world.tick(&mut movement_sys, 1.0);
+    assert_eq!(test_world.cd_pos(), start_pos.with_z(start_pos.z + 1));
+}
+
+#[test]
+fn jumping_lets_cell_dwellers_step_up_higher() {
+    let profile = MovementProfile {
+        max_step_height: 1,
+        jump_height: 1,
+        ..MovementProfile::default()
+    };
+
+    // Too high to step up without jumping...
+    let (mut test_world, mut movement_sys, sender, start_pos) =
+        movement_test(profile, &|globe, cd| build_wall(globe, cd, 2));
+    sender.send(MovementEvent::StepForward(true)).unwrap();
+    test_world.tick(&mut movement_sys, 1.0);
+    assert_eq!(test_world.cd_pos(), start_pos);
+
+    // ...but not with a jump.
+    sender.send(MovementEvent::Jump(true)).unwrap();
+    test_world.tick(&mut movement_sys, 1.0);
+    assert_eq!(test_world.cd_pos().z, start_pos.z + 2);
+    assert_ne!(test_world.cd_pos().with_z(start_pos.z), start_pos);
+
+    // Any higher is too high, even when jumping.
+    let (mut test_world, mut movement_sys, sender, start_pos) =
+        movement_test(profile, &|globe, cd| build_wall(globe, cd, 3));
+    sender.send(MovementEvent::Jump(true)).unwrap();
+    sender.send(MovementEvent::StepForward(true)).unwrap();
+    test_world.tick(&mut movement_sys, 1.0);
+    assert_eq!(test_world.cd_pos(), start_pos);
+}
+
+#[test]
+fn only_climbers_can_climb_walls() {
+    let (mut test_world, mut movement_sys, sender, start_pos) =
+        movement_test(MovementProfile::default(), &|globe, cd| build_wall(globe, cd, 5));
+    sender.send(MovementEvent::StepForward(true)).unwrap();
+    test_world.tick(&mut movement_sys, 1.0);
+    assert_eq!(test_world.cd_pos(), start_pos);
+
+    let profile = MovementProfile {
+        can_climb: true,
+        ..MovementProfile::default()
+    };
+    let (mut test_world, mut movement_sys, sender, start_pos) =
+        movement_test(profile, &|globe, cd| build_wall(globe, cd, 5));
+    sender.send(MovementEvent::StepForward(true)).unwrap();
+    // Climb one cell at a time, hanging onto the wall in between.
+    test_world.tick(&mut movement_sys, 1.0);
+    assert_eq!(test_world.cd_pos(), start_pos.with_z(start_pos.z + 1));
+    test_world.tick(&mut movement_sys, 1.0);
+    assert_eq!(test_world.cd_pos(), start_pos.with_z(start_pos.z + 2));
+}
+
+#[test]
+fn climbing_needs_a_wall() {
+    let profile = MovementProfile {
+        can_climb: true,
+        ..MovementProfile::default()
+    };
+    // Leave the cell dweller hanging in mid-air, with nothing in front of it.
+    let (mut globe, mut cd) = cell_dweller_on_dry_land();
+    cd.set_movement_profile(profile);
+    load_chunks_around(&mut globe, cd.pos, 8.0);
+    let pos = cd.pos;
+    for dz in 0..6 {
+        set_material(&mut globe, pos.with_z(pos.z + dz), Material::Air);
+    }
+    cd.set_grid_point(pos.with_z(pos.z + 2));
+    build_wall(&mut globe, &cd, 0);
+    let start_pos = cd.pos;
+
+    let mut test_world = TestWorld::new(globe, cd);
+    let (sender, receiver) = mpsc::channel();
+    let mut movement_sys = MovementSystem::new(&mut test_world.world, receiver, &TestWorld::log());
+    movement_sys.init(&mut test_world.world);
+
+    sender.send(MovementEvent::StepForward(true)).unwrap();
+    test_world.tick(&mut movement_sys, 1.0);
+    assert_eq!(test_world.cd_pos(), start_pos);
+    sender.send(MovementEvent::StepForward(false)).unwrap();
+    sender.send(MovementEvent::Jump(true)).unwrap();
+    test_world.tick(&mut movement_sys, 1.0);
+    assert_eq!(test_world.cd_pos(), start_pos);
+}
+
+// A cell dweller hanging in the air the given number of cells above dry land,
+// with a `PhysicsSystem` to pull it down. Also returns where it should land.
+fn physics_test(
+    height: GridCoord,
+    gravity: f64,
+    terminal_velocity: f64,
+) -> (TestWorld, PhysicsSystem, GridPoint3) {
+    let (mut globe, mut cd) = cell_dweller_on_dry_land();
+    let ground_pos = cd.pos;
+    load_chunks_around(&mut globe, ground_pos.with_z(ground_pos.z + height / 2), height as f64 + 4.0);
+    for dz in 0..(height + 2) {
+        set_material(&mut globe, ground_pos.with_z(ground_pos.z + dz), Material::Air);
+    }
+    cd.set_grid_point(ground_pos.with_z(ground_pos.z + height));
+
+    let mut test_world = TestWorld::new(globe, cd);
+    let mut physics_sys = PhysicsSystem::new(
+        &mut test_world.world,
+        &TestWorld::log(),
+        gravity,
+        terminal_velocity,
+    );
+    physics_sys.init(&mut test_world.world);
+    (test_world, physics_sys, ground_pos)
+}
+
+#[test]
+fn falling_is_no_faster_than_terminal_velocity() {
+    let (mut test_world, mut physics_sys, _ground_pos) = physics_test(10, 20.0, 5.0);
+    let mut fastest_speed: f64 = 0.0;
+    for _ in 0..20 {
+        test_world.tick(&mut physics_sys, 0.05);
+        fastest_speed = fastest_speed.max(-test_world.cd_vertical_velocity());
+    }
+    assert_eq!(fastest_speed, 5.0);
+}
+
+#[test]
+fn landings_report_how_far_and_fast_cell_dwellers_fell() {
+    let gravity = 20.0;
+    let dt = 0.05;
+    let (mut test_world, mut physics_sys, ground_pos) = physics_test(6, gravity, 30.0);
+    let mut speed_before_landing = 0.0;
+    let mut landings = Vec::new();
+    for _ in 0..100 {
+        test_world.tick(&mut physics_sys, dt);
+        landings = test_world.landings();
+        if !landings.is_empty() {
+            break;
+        }
+        speed_before_landing = -test_world.cd_vertical_velocity();
+    }
+
+    assert_eq!(landings.len(), 1);
+    let landing = landings[0];
+    assert_eq!(landing.entity, test_world.cd_entity);
+    assert_eq!(landing.cells_fallen, 6);
+    assert!(!landing.in_water);
+    assert_eq!(test_world.cd_pos(), ground_pos);
+    // It either landed before speeding up any more this tick, or just after.
+    assert!(landing.impact_speed >= speed_before_landing);
+    assert!(landing.impact_speed <= speed_before_landing + gravity * dt + 1e-9);
+    // Falling six cells should take a while to get up to speed.
+    assert!(landing.impact_speed > 10.0);
+
+    // Now that it has landed, it should stay put.
+    assert_eq!(test_world.cd_vertical_velocity(), 0.0);
+    test_world.tick(&mut physics_sys, dt);
+    assert!(test_world.landings().is_empty());
+    assert_eq!(test_world.cd_pos(), ground_pos);
+}
+
+// A swimmer at the bottom of a column of water this many cells deep,
+// with open air above it, and systems to both move it around and let it float.
+// Also returns the position of the bottom water cell.
+fn swimming_test(depth: GridCoord) -> (TestWorld, MovementSystem, PhysicsSystem, mpsc::Sender<MovementEvent>, GridPoint3) {
+    let (mut globe, mut cd) = cell_dweller_on_dry_land();
+    cd.set_movement_profile(MovementProfile {
+        can_swim: true,
+        ..MovementProfile::default()
+    });
+    let bottom_pos = cd.pos;
+    load_chunks_around(&mut globe, bottom_pos.with_z(bottom_pos.z + depth / 2), depth as f64 + 4.0);
+    for dz in 0..(depth + 3) {
+        let material = if dz < depth { Material::Water } else { Material::Air };
+        set_material(&mut globe, bottom_pos.with_z(bottom_pos.z + dz), material);
+    }
+
+    let mut test_world = TestWorld::new(globe, cd);
+    let (movement_input_sender, movement_input_receiver) = mpsc::channel();
+    let mut movement_sys = MovementSystem::new(&mut test_world.world, movement_input_receiver, &TestWorld::log());
+    movement_sys.init(&mut test_world.world);
+    let mut physics_sys = PhysicsSystem::new(&mut test_world.world, &TestWorld::log(), 20.0, 30.0);
+    physics_sys.init(&mut test_world.world);
+    (test_world, movement_sys, physics_sys, movement_input_sender, bottom_pos)
+}
+
+fn swim_for(
+    seconds: TimeDelta,
+    test_world: &mut TestWorld,
+    movement_sys: &mut MovementSystem,
+    physics_sys: &mut PhysicsSystem,
+) {
+    let dt = 0.05;
+    let ticks = (seconds / dt).round() as usize;
+    for _ in 0..ticks {
+        test_world.tick(movement_sys, dt);
+        test_world.tick(physics_sys, dt);
+    }
+}
+
+#[test]
+fn swimmers_float_up_to_the_surface() {
+    let (mut test_world, mut movement_sys, mut physics_sys, _sender, bottom_pos) = swimming_test(4);
+    let surface_pos = bottom_pos.with_z(bottom_pos.z + 3);
+
+    // Floating is gradual...
+    swim_for(0.75, &mut test_world, &mut movement_sys, &mut physics_sys);
+    let pos = test_world.cd_pos();
+    assert!(pos.z > bottom_pos.z && pos.z < surface_pos.z);
+
+    // ...but it gets there eventually, and bobs along with its head out of the water.
+    swim_for(3.0, &mut test_world, &mut movement_sys, &mut physics_sys);
+    assert_eq!(test_world.cd_pos(), surface_pos);
+    assert!(test_world.landings().is_empty());
+}
+
+#[test]
+fn diving_swimmers_stay_down() {
+    let (mut test_world, mut movement_sys, mut physics_sys, sender, bottom_pos) = swimming_test(4);
+    sender.send(MovementEvent::Dive(true)).unwrap();
+    swim_for(3.0, &mut test_world, &mut movement_sys, &mut physics_sys);
+    assert_eq!(test_world.cd_pos(), bottom_pos);
+
+    // Letting go of dive lets it float back up.
+    sender.send(MovementEvent::Dive(false)).unwrap();
+    swim_for(3.0, &mut test_world, &mut movement_sys, &mut physics_sys);
+    assert_eq!(test_world.cd_pos(), bottom_pos.with_z(bottom_pos.z + 3));
+}
+
+#[test]
+fn swimmers_can_dive_down_and_swim_back_up() {
+    let (mut test_world, mut movement_sys, mut physics_sys, sender, bottom_pos) = swimming_test(4);
+    let surface_pos = bottom_pos.with_z(bottom_pos.z + 3);
+    swim_for(3.0, &mut test_world, &mut movement_sys, &mut physics_sys);
+    assert_eq!(test_world.cd_pos(), surface_pos);
+
+    // Dive one cell at a time, all the way to the bottom.
+    sender.send(MovementEvent::Dive(true)).unwrap();
+    swim_for(0.05, &mut test_world, &mut movement_sys, &mut physics_sys);
+    assert_eq!(test_world.cd_pos(), surface_pos.with_z(surface_pos.z - 1));
+    swim_for(3.0, &mut test_world, &mut movement_sys, &mut physics_sys);
+    assert_eq!(test_world.cd_pos(), bottom_pos);
+
+    // Swim back up, but not out of the water.
+    sender.send(MovementEvent::Dive(false)).unwrap();
+    sender.send(MovementEvent::Jump(true)).unwrap();
+    swim_for(0.05, &mut test_world, &mut movement_sys, &mut physics_sys);
+    assert_eq!(test_world.cd_pos(), bottom_pos.with_z(bottom_pos.z + 1));
+    swim_for(3.0, &mut test_world, &mut movement_sys, &mut physics_sys);
+    assert_eq!(test_world.cd_pos(), surface_pos);
+}
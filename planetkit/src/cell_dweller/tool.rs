@@ -0,0 +1,48 @@
+use specs;
+
+use globe::chunk::Material;
+
+/// A tool held by a cell dweller, making it quicker to dig.
+///
+/// Cell dwellers without one dig with their bare hands.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Tool {
+    Shovel,
+    Pickaxe,
+}
+
+impl Tool {
+    /// How many times faster this tool digs through the given material than bare hands.
+    pub fn dig_speed(&self, material: Material) -> f64 {
+        match (*self, material) {
+            (Tool::Shovel, Material::Dirt) => 3.0,
+            (Tool::Pickaxe, Material::Dirt) => 1.5,
+            (Tool::Shovel, Material::Sand) => 3.0,
+            (Tool::Shovel, Material::Gravel) => 2.0,
+            (Tool::Pickaxe, Material::Gravel) => 2.0,
+            _ => 1.0,
+        }
+    }
+}
+
+impl specs::Component for Tool {
+    type Storage = specs::HashMapStorage<Tool>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tools_are_best_at_their_own_materials() {
+        assert!(Tool::Shovel.dig_speed(Material::Dirt) > Tool::Pickaxe.dig_speed(Material::Dirt));
+        assert!(Tool::Shovel.dig_speed(Material::Sand) > Tool::Pickaxe.dig_speed(Material::Sand));
+        assert_eq!(Tool::Shovel.dig_speed(Material::Gravel), Tool::Pickaxe.dig_speed(Material::Gravel));
+        // No tool is ever worse than bare hands.
+        for tool in &[Tool::Shovel, Tool::Pickaxe] {
+            for material in &[Material::Air, Material::Dirt, Material::Water, Material::Sand, Material::Gravel] {
+                assert!(tool.dig_speed(*material) >= 1.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::vec_deque::{self, VecDeque};
+use std::iter::Skip;
+
+use specs;
+
+/// How many events an `EventChannel` keeps around unless told otherwise.
+pub const DEFAULT_EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// `World`-global resource for broadcasting events of a given type
+/// (e.g. "peer joined" or "cell changed") from whichever systems produce them
+/// to any number of systems that want to hear about them.
+///
+/// Each reader has its own `ReaderId`, which remembers how far through the
+/// events it has read, so readers don't consume events out from under each other.
+/// Reading only needs a shared reference to the channel, so several systems
+/// can read the same channel at once.
+///
+/// Only the most recent events are kept, so a reader that goes without
+/// reading for long enough will miss some. Readers that run every tick
+/// shouldn't need to worry about this unless the channel is very busy;
+/// see `with_capacity`.
+pub struct EventChannel<E> {
+    events: VecDeque<E>,
+    capacity: usize,
+    // Number of events ever written that have since been dropped
+    // to make room for newer ones; i.e. the index of the first
+    // event in `events` among all events ever written.
+    dropped_count: u64,
+}
+
+/// A single reader's position in an `EventChannel`.
+///
+/// Get one from `EventChannel::register_reader`.
+#[derive(Debug)]
+pub struct ReaderId {
+    // Index of the next event to read among all events ever written.
+    next_event: u64,
+}
+
+impl<E> EventChannel<E> {
+    pub fn new() -> EventChannel<E> {
+        EventChannel::with_capacity(DEFAULT_EVENT_CHANNEL_CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: usize) -> EventChannel<E> {
+        assert!(capacity > 0, "Event channel needs room for at least one event");
+        EventChannel {
+            events: VecDeque::with_capacity(capacity),
+            capacity: capacity,
+            dropped_count: 0,
+        }
+    }
+
+    /// Start reading from this channel. The new reader will
+    /// only see events written after it was registered.
+    pub fn register_reader(&self) -> ReaderId {
+        ReaderId { next_event: self.end() }
+    }
+
+    pub fn single_write(&mut self, event: E) {
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+            self.dropped_count += 1;
+        }
+        self.events.push_back(event);
+    }
+
+    pub fn iter_write<I: IntoIterator<Item = E>>(&mut self, events: I) {
+        for event in events {
+            self.single_write(event);
+        }
+    }
+
+    /// Make room for at least the given number of events, if there isn't already.
+    ///
+    /// Writers that can produce bursts of events can use this to make sure
+    /// a whole burst fits, so readers that keep up with them don't miss any.
+    pub fn ensure_capacity(&mut self, capacity: usize) {
+        if capacity > self.capacity {
+            let additional = capacity - self.events.len();
+            self.events.reserve(additional);
+            self.capacity = capacity;
+        }
+    }
+
+    /// Read every event written since this reader last read,
+    /// except for any that have already been dropped to make room for newer ones.
+    pub fn read(&self, reader_id: &mut ReaderId) -> Skip<vec_deque::Iter<E>> {
+        let first_unread = reader_id.next_event.max(self.dropped_count);
+        reader_id.next_event = self.end();
+        self.events.iter().skip((first_unread - self.dropped_count) as usize)
+    }
+
+    /// How many events the given reader has missed because it fell behind.
+    ///
+    /// Call this before `read` if it matters.
+    pub fn missed_count(&self, reader_id: &ReaderId) -> u64 {
+        self.dropped_count.saturating_sub(reader_id.next_event)
+    }
+
+    fn end(&self) -> u64 {
+        self.dropped_count + self.events.len() as u64
+    }
+}
+
+impl<E> Default for EventChannel<E> {
+    fn default() -> EventChannel<E> {
+        EventChannel::new()
+    }
+}
+
+impl<E: Send + Sync + 'static> ::AutoResource for EventChannel<E> {
+    fn new(_world: &mut specs::World) -> EventChannel<E> {
+        EventChannel::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readers_each_see_every_event() {
+        let mut channel = EventChannel::<u32>::new();
+        let mut early_reader = channel.register_reader();
+        channel.iter_write(vec![1, 2]);
+        let mut late_reader = channel.register_reader();
+        channel.single_write(3);
+
+        assert_eq!(channel.read(&mut early_reader).cloned().collect::<Vec<u32>>(), vec![1, 2, 3]);
+        assert_eq!(channel.read(&mut late_reader).cloned().collect::<Vec<u32>>(), vec![3]);
+        // Nothing new since last time.
+        assert_eq!(channel.read(&mut early_reader).count(), 0);
+
+        channel.single_write(4);
+        assert_eq!(channel.read(&mut late_reader).cloned().collect::<Vec<u32>>(), vec![4]);
+        assert_eq!(channel.read(&mut early_reader).cloned().collect::<Vec<u32>>(), vec![4]);
+    }
+
+    #[test]
+    fn slow_readers_miss_old_events() {
+        let mut channel = EventChannel::<u32>::with_capacity(2);
+        let mut reader = channel.register_reader();
+        channel.iter_write(vec![1, 2, 3]);
+        assert_eq!(channel.missed_count(&reader), 1);
+        assert_eq!(channel.read(&mut reader).cloned().collect::<Vec<u32>>(), vec![2, 3]);
+        assert_eq!(channel.missed_count(&reader), 0);
+    }
+
+    #[test]
+    fn channels_can_grow_to_fit_bursts() {
+        let mut channel = EventChannel::<u32>::with_capacity(2);
+        let mut reader = channel.register_reader();
+        channel.ensure_capacity(3);
+        channel.iter_write(vec![1, 2, 3]);
+        assert_eq!(channel.missed_count(&reader), 0);
+        assert_eq!(channel.read(&mut reader).cloned().collect::<Vec<u32>>(), vec![1, 2, 3]);
+
+        // Never shrinks.
+        channel.ensure_capacity(1);
+        channel.iter_write(vec![4, 5, 6]);
+        assert_eq!(channel.read(&mut reader).cloned().collect::<Vec<u32>>(), vec![4, 5, 6]);
+    }
+}
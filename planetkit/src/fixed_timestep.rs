@@ -0,0 +1,112 @@
+use specs;
+
+use types::*;
+
+/// How many times per second the simulation advances unless
+/// the game asks for something else; see `AppBuilder::with_tick_rate`.
+pub const DEFAULT_TICKS_PER_SECOND: f64 = 60.0;
+
+// If the simulation falls this far behind, give up on catching up.
+// It's better to run slower than real time than to spend ever longer
+// catching up each frame until we lock up entirely.
+const MAX_TICKS_PER_FRAME: u32 = 8;
+
+/// Turns the variable time between frames into a whole number of
+/// fixed-length simulation ticks, carrying over any time left over.
+///
+/// This keeps everything that integrates over time (timers, physics, etc.)
+/// independent of frame rate, so that the same inputs always produce
+/// the same results.
+pub struct FixedTimestep {
+    seconds_per_tick: TimeDelta,
+    unsimulated_seconds: TimeDelta,
+}
+
+impl FixedTimestep {
+    pub fn new(ticks_per_second: f64) -> FixedTimestep {
+        assert!(ticks_per_second > 0.0, "Tick rate must be positive");
+        FixedTimestep {
+            seconds_per_tick: 1.0 / ticks_per_second,
+            unsimulated_seconds: 0.0,
+        }
+    }
+
+    pub fn seconds_per_tick(&self) -> TimeDelta {
+        self.seconds_per_tick
+    }
+
+    /// Account for another frame's worth of real time passing.
+    ///
+    /// Returns how many ticks should be simulated to catch up.
+    pub fn advance(&mut self, dt: TimeDelta) -> u32 {
+        self.unsimulated_seconds += dt;
+        let mut ticks = 0;
+        while self.unsimulated_seconds >= self.seconds_per_tick {
+            self.unsimulated_seconds -= self.seconds_per_tick;
+            ticks += 1;
+            if ticks == MAX_TICKS_PER_FRAME {
+                // Drop whatever else we're behind by.
+                self.unsimulated_seconds = self.unsimulated_seconds.min(self.seconds_per_tick);
+                break;
+            }
+        }
+        ticks
+    }
+
+    /// How far real time has progressed from the last tick
+    /// toward the next one, from 0 to 1.
+    pub fn alpha(&self) -> f64 {
+        (self.unsimulated_seconds / self.seconds_per_tick).min(1.0)
+    }
+}
+
+/// `World`-global resource counting how many fixed-timestep
+/// ticks have been simulated since the app started.
+pub struct TickCounter {
+    pub ticks: u64,
+}
+
+impl ::AutoResource for TickCounter {
+    fn new(_world: &mut specs::World) -> TickCounter {
+        TickCounter { ticks: 0 }
+    }
+}
+
+/// `World`-global resource saying how far between the last tick and the next one
+/// the frame being drawn is, from 0 to 1.
+///
+/// Anything that gets drawn can use this to interpolate between its
+/// last two simulated states, so that motion looks smooth even when
+/// the frame rate doesn't match the tick rate.
+pub struct InterpolationAlpha {
+    pub alpha: f64,
+}
+
+impl ::AutoResource for InterpolationAlpha {
+    fn new(_world: &mut specs::World) -> InterpolationAlpha {
+        InterpolationAlpha { alpha: 0.0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leftover_time_carries_over() {
+        let mut fixed_timestep = FixedTimestep::new(10.0);
+        assert_eq!(fixed_timestep.advance(0.05), 0);
+        assert_relative_eq!(fixed_timestep.alpha(), 0.5);
+        assert_eq!(fixed_timestep.advance(0.26), 3);
+        assert_relative_eq!(fixed_timestep.alpha(), 0.1, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn gives_up_catching_up_after_long_frames() {
+        let mut fixed_timestep = FixedTimestep::new(10.0);
+        assert_eq!(fixed_timestep.advance(100.0), MAX_TICKS_PER_FRAME);
+        // Only one tick behind now.
+        assert_eq!(fixed_timestep.advance(0.0), 1);
+        assert_eq!(fixed_timestep.advance(0.0), 0);
+    }
+}
@@ -0,0 +1,75 @@
+use types::*;
+use app_builder::AppBuilder;
+use bundle::Bundle;
+use super::{
+    ChunkSystem,
+    FluidSystem,
+    FallingBlockSystem,
+    CellChangeSystem,
+    ChunkViewSystem,
+    LodSystem,
+};
+
+/// Loads, simulates, and draws the terrain of every `Globe`:
+/// chunk loading, flowing water, publishing `CellChanged` events,
+/// and building geometry for nearby chunks and distant terrain.
+///
+/// Sand and gravel only fall if `falling_blocks` is turned on.
+pub struct GlobeBundle {
+    pub fluid_seconds_between_ticks: TimeDelta,
+    pub fluid_max_chunks_per_tick: usize,
+    /// Whether to add a `FallingBlockSystem`.
+    pub falling_blocks: bool,
+    pub falling_block_seconds_between_ticks: TimeDelta,
+    pub falling_block_max_chunks_per_tick: usize,
+    pub chunk_view_seconds_between_geometry_creation: TimeDelta,
+    pub lod_seconds_between_updates: TimeDelta,
+}
+
+impl Default for GlobeBundle {
+    fn default() -> GlobeBundle {
+        GlobeBundle {
+            fluid_seconds_between_ticks: 0.2,
+            fluid_max_chunks_per_tick: 8,
+            falling_blocks: false,
+            falling_block_seconds_between_ticks: 0.1,
+            falling_block_max_chunks_per_tick: 8,
+            chunk_view_seconds_between_geometry_creation: 0.05,
+            lod_seconds_between_updates: 0.5,
+        }
+    }
+}
+
+impl Bundle for GlobeBundle {
+    fn add_to(self, mut app_builder: AppBuilder) -> AppBuilder {
+        let log = app_builder.root_log().clone();
+
+        let chunk_sys = ChunkSystem::new(&log);
+        let fluid_sys = FluidSystem::new(
+            &log,
+            self.fluid_seconds_between_ticks,
+            self.fluid_max_chunks_per_tick,
+        );
+        let cell_change_sys = CellChangeSystem::new(&log, app_builder.world_mut());
+        let chunk_view_sys = ChunkViewSystem::new(&log, self.chunk_view_seconds_between_geometry_creation);
+        let lod_sys = LodSystem::new(&log, self.lod_seconds_between_updates);
+
+        app_builder = app_builder
+            .add_auto_system(chunk_sys)
+            .add_auto_system(fluid_sys);
+        if self.falling_blocks {
+            let falling_block_sys = FallingBlockSystem::new(
+                &log,
+                self.falling_block_seconds_between_ticks,
+                self.falling_block_max_chunks_per_tick,
+            );
+            app_builder = app_builder.add_auto_system(falling_block_sys);
+        }
+        app_builder
+            .add_auto_system(cell_change_sys)
+            // There's no point building geometry more often than we draw it.
+            .add_auto_frame_system(chunk_view_sys)
+            // Likewise for distant terrain.
+            .add_auto_frame_system(lod_sys)
+    }
+}
@@ -0,0 +1,91 @@
+use specs;
+use specs::{Entities, FetchMut, WriteStorage};
+use slog::Logger;
+
+use event_channel::EventChannel;
+use super::{Globe, CellChange};
+use auto_system::{AutoSystem, ensure_component_registered};
+
+/// Event sent through an `EventChannel` for every authoritative
+/// change to a cell in any `Globe`.
+///
+/// The channel grows to fit every change made in a single tick, so readers
+/// that run every tick after `CellChangeSystem` won't miss any. Readers that
+/// run less often should check `EventChannel::missed_count`.
+#[derive(Clone, Copy, Debug)]
+pub struct CellChanged {
+    pub globe_entity: specs::Entity,
+    pub change: CellChange,
+}
+
+/// Drains the log of cell changes from every `Globe`, and passes
+/// them on through an `EventChannel<CellChanged>`, so that any
+/// number of other systems can react to terrain being edited.
+///
+/// This turns on logging of cell changes for every globe it sees,
+/// so changes made before it first runs aren't passed on.
+pub struct CellChangeSystem {
+    log: Logger,
+}
+
+impl CellChangeSystem {
+    pub fn new(parent_log: &Logger, world: &mut specs::World) -> CellChangeSystem {
+        use auto_resource::AutoResource;
+
+        // Ensure resources we use are present.
+        EventChannel::<CellChanged>::ensure(world);
+
+        CellChangeSystem {
+            log: parent_log.new(o!("system" => "cell_change")),
+        }
+    }
+}
+
+impl<'a> specs::System<'a> for CellChangeSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Globe>,
+        FetchMut<'a, EventChannel<CellChanged>>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        use specs::Join;
+
+        let (entities, mut globes, mut cell_changed_events) = data;
+        let mut events: Vec<CellChanged> = Vec::new();
+        for (globe, globe_entity) in (&mut globes, &*entities).join() {
+            globe.set_logging_cell_changes(true);
+            events.extend(globe.drain_cell_changes().map(|change| {
+                CellChanged {
+                    globe_entity: globe_entity,
+                    change: change,
+                }
+            }));
+        }
+        if events.is_empty() {
+            return;
+        }
+        trace!(self.log, "Passing on cell changes"; "count" => events.len());
+
+        // Don't let a big burst of changes (e.g. water pouring into a cave)
+        // push any of its own changes out of the channel before anyone has
+        // had a chance to read them.
+        cell_changed_events.ensure_capacity(events.len());
+        cell_changed_events.iter_write(events);
+    }
+}
+
+impl AutoSystem for CellChangeSystem {
+    fn name(&self) -> &'static str {
+        "cell_change"
+    }
+
+    fn dependencies(&self) -> Vec<&'static str> {
+        // Pass on changes from everything that edits globes in the same tick.
+        vec!["cd_mining", "fluid", "falling_block"]
+    }
+
+    fn init(&mut self, world: &mut specs::World) {
+        ensure_component_registered::<Globe>(world);
+    }
+}
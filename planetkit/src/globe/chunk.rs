@@ -4,11 +4,49 @@ use globe::ChunkOrigin;
 use globe::origin_of_chunk_owning;
 use globe::chunk_pair::PointPair;
 
-#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Copy, Clone, Debug)]
 pub enum Material {
     Air,
     Dirt,
     Water,
+    Sand,
+    Gravel,
+}
+
+impl Material {
+    /// Returns `true` if the material can't be moved or seen through.
+    ///
+    /// For now that's everything other than air and water.
+    pub fn is_solid(&self) -> bool {
+        match *self {
+            Material::Dirt | Material::Sand | Material::Gravel => true,
+            Material::Air | Material::Water => false,
+        }
+    }
+
+    /// Returns `true` if a cell of this material will fall
+    /// when there's nothing solid beneath it.
+    ///
+    /// See `FallingBlockSystem`.
+    pub fn falls(&self) -> bool {
+        match *self {
+            Material::Sand | Material::Gravel => true,
+            _ => false,
+        }
+    }
+
+    /// How many seconds it takes to dig out a cell of this material
+    /// with bare hands, or `None` if it can't be dug out at all.
+    pub fn hardness(&self) -> Option<f64> {
+        match *self {
+            Material::Dirt => Some(0.6),
+            Material::Sand => Some(0.4),
+            Material::Gravel => Some(0.8),
+            // There's nothing there to dig, or it would
+            // just flow back in again.
+            Material::Air | Material::Water => None,
+        }
+    }
 }
 
 // TODO: we should actually have multiple different
@@ -17,10 +55,17 @@ pub enum Material {
 // sized partition of the world that would be loaded and
 // unloaded into the world as a unit.
 
+/// How much fluid a cell holds when it's completely full.
+pub const MAX_FLUID_LEVEL: u8 = 8;
+
 #[derive(Clone, Copy, Debug)]
 pub struct Cell {
     pub material: Material,
     pub shade: f32,
+    /// How full of water the cell is, from 1 up to `MAX_FLUID_LEVEL`.
+    ///
+    /// Only meaningful for cells containing water; zero for everything else.
+    pub fluid_level: u8,
 }
 
 // Stores from (0, 0) to (chunk_resolution, chunk_resolution) _inclusive_.
@@ -50,6 +95,16 @@ pub struct Chunk {
     // Neighbors that share some cells on the border of this chunk but don't own them.
     pub downstream_neighbors: Vec<DownstreamNeighbor>,
     pub is_view_dirty: bool,
+    // Whether geometry has ever been built for this chunk's view.
+    // Until it has, level-of-detail terrain stands in for it; see `LodSystem`.
+    pub has_view_been_built: bool,
+    // Whether anything has happened in or around this chunk that might
+    // make its fluids flow. Chunks with nothing going on sleep until
+    // something nearby changes; see `FluidSystem`.
+    pub is_fluid_active: bool,
+    // Same deal, but for blocks that might have lost whatever was
+    // holding them up; see `FallingBlockSystem`.
+    pub is_falling_active: bool,
     // Chunks that are directly accessible from the given chunk via a single,
     // step between cells, including this chunk itself.
     //
@@ -85,6 +140,12 @@ impl Chunk {
             upstream_neighbors: Vec::new(),
             downstream_neighbors: Vec::new(),
             is_view_dirty: true,
+            has_view_been_built: false,
+            // Let any fluids settle after the chunk is first loaded.
+            is_fluid_active: true,
+            // Likewise, drop anything that was generated or saved
+            // without support.
+            is_falling_active: true,
             accessible_chunks: Self::list_accessible_chunks(
                 origin,
                 root_resolution,
@@ -245,6 +306,7 @@ impl Chunk {
     /// view has been updated since the chunk was last modified.
     pub fn mark_view_as_clean(&mut self) {
         self.is_view_dirty = false;
+        self.has_view_been_built = true;
     }
 
     fn list_accessible_chunks(
@@ -6,6 +6,7 @@ use slog::Logger;
 use grid::PosInOwningRoot;
 use super::{Globe, ChunkOrigin};
 use cell_dweller::CellDweller;
+use auto_system::{AutoSystem, ensure_component_registered};
 
 // NOTE: this is currently all pretty awful. See comments throughout.
 
@@ -212,3 +213,19 @@ impl<'a> specs::System<'a> for ChunkSystem {
         }
     }
 }
+
+impl AutoSystem for ChunkSystem {
+    fn name(&self) -> &'static str {
+        "chunk"
+    }
+
+    fn dependencies(&self) -> Vec<&'static str> {
+        // Load chunks around wherever cell dwellers ended up this tick.
+        vec!["cd_movement", "cd_mining"]
+    }
+
+    fn init(&mut self, world: &mut specs::World) {
+        ensure_component_registered::<Globe>(world);
+        ensure_component_registered::<CellDweller>(world);
+    }
+}
@@ -8,6 +8,7 @@ use types::*;
 use globe::{Globe, View, ChunkView};
 use render::{Visual, ProtoMesh, Vertex};
 use Spatial;
+use auto_system::{AutoSystem, ensure_component_registered};
 
 // For now, just creates up to 1 chunk view per tick,
 // until we have created views for all chunks.
@@ -270,3 +271,16 @@ impl<'a> specs::System<'a> for ChunkViewSystem {
         self.build_chunk_geometry(globes, visuals, chunk_views);
     }
 }
+
+impl AutoSystem for ChunkViewSystem {
+    fn name(&self) -> &'static str {
+        "chunk_view"
+    }
+
+    fn init(&mut self, world: &mut specs::World) {
+        ensure_component_registered::<Globe>(world);
+        ensure_component_registered::<Visual>(world);
+        ensure_component_registered::<Spatial>(world);
+        ensure_component_registered::<ChunkView>(world);
+    }
+}
@@ -0,0 +1,91 @@
+use slog::Logger;
+
+use grid::{GridPoint3, Root};
+use render::{ProtoMesh, Vertex};
+use super::{Globe, View, ChunkOrigin};
+
+/// Builds the same geometry for a single chunk that would be drawn for it in game,
+/// with vertex positions specified relative to the bottom-middle of the chunk origin cell.
+///
+/// Returns `None` if there's nothing in the chunk to see.
+pub fn make_chunk_mesh(globe: &Globe, origin: ChunkOrigin, parent_log: &Logger) -> Option<ProtoMesh> {
+    let globe_view = View::new(globe.spec(), parent_log);
+    let mut vertex_data: Vec<Vertex> = Vec::new();
+    let mut index_data: Vec<u32> = Vec::new();
+    globe_view.make_chunk_geometry(globe, origin, &mut vertex_data, &mut index_data);
+    if vertex_data.is_empty() || index_data.is_empty() {
+        return None;
+    }
+    Some(ProtoMesh::new(vertex_data, index_data))
+}
+
+/// Builds one big mesh out of all the chunks that are currently loaded,
+/// with vertex positions specified relative to the center of the globe.
+///
+/// Vertex positions are only single precision, so this is only really useful
+/// for globes much smaller than the earth.
+///
+/// Returns `None` if there's nothing loaded to see.
+pub fn make_loaded_chunks_mesh(globe: &Globe, parent_log: &Logger) -> Option<ProtoMesh> {
+    use super::globe::GlobeGuts;
+
+    let spec = globe.spec();
+    let globe_view = View::new(spec, parent_log);
+    let mut vertex_data: Vec<Vertex> = Vec::new();
+    let mut index_data: Vec<u32> = Vec::new();
+    // Keep the output stable from run to run.
+    let mut chunk_origins: Vec<ChunkOrigin> = globe.chunks().keys().cloned().collect();
+    chunk_origins.sort_by_key(|origin| {
+        let pos = origin.pos();
+        (pos.root.index, pos.z, pos.y, pos.x)
+    });
+    for origin in chunk_origins {
+        let first_vertex_index = vertex_data.len();
+        globe_view.make_chunk_geometry(globe, origin, &mut vertex_data, &mut index_data);
+        // Move this chunk's vertices out from its origin.
+        let chunk_origin_pos = spec.cell_bottom_center(*origin.pos());
+        for vertex in &mut vertex_data[first_vertex_index..] {
+            for axis in 0..3 {
+                vertex.a_pos[axis] += chunk_origin_pos[axis] as f32;
+            }
+        }
+    }
+    if vertex_data.is_empty() || index_data.is_empty() {
+        return None;
+    }
+    Some(ProtoMesh::new(vertex_data, index_data))
+}
+
+/// Loads every chunk that the surface of the land or ocean passes through
+/// in the given roots, so that the whole surface can be drawn or exported.
+///
+/// This is only practical for small globes; there are far too many chunks
+/// on the surface of an earth-sized globe.
+pub fn ensure_surface_chunks_present(globe: &mut Globe, roots: &[Root]) {
+    let spec = globe.spec();
+    let chunks_per_root_side = spec.chunks_per_root_side();
+    for root in roots {
+        for chunk_y in 0..chunks_per_root_side[1] {
+            for chunk_x in 0..chunks_per_root_side[0] {
+                let origin_x = chunk_x * spec.chunk_resolution[0];
+                let origin_y = chunk_y * spec.chunk_resolution[1];
+                let (min_surface_z, max_surface_z) = globe.surface_cell_z_range(*root, chunk_x, chunk_y);
+
+                // Include a cell either side, so that we know
+                // what's next to the surface when building geometry.
+                let min_z = (min_surface_z - 1).max(0);
+                let max_z = max_surface_z + 1;
+                let mut chunk_z = min_z / spec.chunk_resolution[2] * spec.chunk_resolution[2];
+                while chunk_z <= max_z {
+                    let origin = ChunkOrigin::new(
+                        GridPoint3::new(*root, origin_x, origin_y, chunk_z),
+                        spec.root_resolution,
+                        spec.chunk_resolution,
+                    );
+                    globe.ensure_chunk_present(origin);
+                    chunk_z += spec.chunk_resolution[2];
+                }
+            }
+        }
+    }
+}
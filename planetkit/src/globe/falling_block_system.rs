@@ -0,0 +1,313 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use specs;
+use specs::{Entities, Fetch, FetchMut, WriteStorage};
+use slog::Logger;
+
+use types::*;
+use grid::{GridPoint3, PosInOwningRoot};
+use super::{Globe, ChunkOrigin};
+use super::chunk::{Cell, Material};
+use auto_system::{AutoSystem, ensure_component_registered};
+use cell_dweller::{SendMessageQueue, CellDwellerMessage, SetFallenBlocksMessage};
+use net::{NodeResource, SendMessage, Destination, Transport};
+
+/// Makes blocks of loose materials like sand and gravel fall
+/// when there's nothing solid left underneath them.
+///
+/// Each tick, every unsupported block in a simulated chunk swaps places
+/// with the air or water directly beneath it, so it falls one cell
+/// per tick until it lands on something solid.
+///
+/// Like `FluidSystem`, only chunks that have had something change in or around
+/// them are simulated, and they go back to sleep once nothing in them has fallen
+/// for a whole tick. See `Globe::set_authoritative_cell`.
+///
+/// Only the master drops blocks; it tells everyone else
+/// about the cells that changed each tick.
+pub struct FallingBlockSystem {
+    log: Logger,
+    seconds_between_ticks: TimeDelta,
+    seconds_until_next_tick: TimeDelta,
+    max_chunks_per_tick: usize,
+    // Active chunks waiting their turn, per globe.
+    queues: HashMap<specs::Entity, VecDeque<ChunkOrigin>>,
+}
+
+impl FallingBlockSystem {
+    pub fn new(
+        parent_log: &Logger,
+        seconds_between_ticks: TimeDelta,
+        max_chunks_per_tick: usize,
+    ) -> FallingBlockSystem {
+        FallingBlockSystem {
+            log: parent_log.new(o!()),
+            seconds_between_ticks: seconds_between_ticks,
+            seconds_until_next_tick: 0.0,
+            max_chunks_per_tick: max_chunks_per_tick,
+            queues: HashMap::new(),
+        }
+    }
+
+    // Returns the positions of all cells that changed.
+    fn tick_globe(&mut self, globe_entity: specs::Entity, globe: &mut Globe) -> Vec<PosInOwningRoot> {
+        use super::globe::GlobeGuts;
+
+        let queue = self.queues.entry(globe_entity).or_insert_with(VecDeque::new);
+        if queue.is_empty() {
+            let mut active_origins: Vec<ChunkOrigin> = globe
+                .chunks()
+                .iter()
+                .filter(|&(_, chunk)| chunk.is_falling_active)
+                .map(|(origin, _)| *origin)
+                .collect();
+            // Same order as `FluidSystem`, so that blocks fall
+            // the same way every time.
+            active_origins.sort_by_key(|origin| {
+                let pos = origin.pos();
+                (pos.root.index, pos.z, pos.y, pos.x)
+            });
+            queue.extend(active_origins);
+        }
+
+        let mut changed_cells = Vec::new();
+
+        let mut chunks_simulated = 0;
+        while chunks_simulated < self.max_chunks_per_tick {
+            let chunk_origin = match queue.pop_front() {
+                Some(chunk_origin) => chunk_origin,
+                None => break,
+            };
+            // Put it to sleep first; anything that falls will wake it
+            // (and its neighbors) back up again.
+            match globe.chunks_mut().get_mut(&chunk_origin) {
+                Some(chunk) => {
+                    if !chunk.is_falling_active {
+                        continue;
+                    }
+                    chunk.is_falling_active = false;
+                }
+                // It's been unloaded since we queued it.
+                None => continue,
+            }
+            simulate_chunk(globe, chunk_origin, &mut changed_cells);
+            chunks_simulated += 1;
+        }
+
+        if !changed_cells.is_empty() {
+            trace!(self.log, "Dropped unsupported blocks"; "changed_cells" => changed_cells.len(), "chunks" => chunks_simulated);
+        }
+        changed_cells
+    }
+}
+
+impl<'a> specs::System<'a> for FallingBlockSystem {
+    type SystemData = (
+        Fetch<'a, TimeDeltaResource>,
+        Entities<'a>,
+        WriteStorage<'a, Globe>,
+        Fetch<'a, NodeResource>,
+        FetchMut<'a, SendMessageQueue>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        use specs::Join;
+
+        let (dt, entities, mut globes, node_resource, mut send_message_queue) = data;
+
+        // Peers find out what fell from the master.
+        if !node_resource.is_master {
+            return;
+        }
+
+        self.seconds_until_next_tick -= dt.0;
+        if self.seconds_until_next_tick > 0.0 {
+            return;
+        }
+        self.seconds_until_next_tick = self.seconds_between_ticks;
+
+        for (globe_entity, globe) in (&*entities, &mut globes).join() {
+            let changed_cells = self.tick_globe(globe_entity, globe);
+            if changed_cells.is_empty() || !send_message_queue.has_consumer {
+                continue;
+            }
+
+            // Tell everyone else where everything landed.
+            // TODO: identify the globe; see `RemoveBlockMessage`.
+            let mut seen = HashSet::new();
+            let cells: Vec<(GridPoint3, Material, u8)> = changed_cells
+                .into_iter()
+                .filter(|pos| seen.insert(*pos))
+                .map(|pos| {
+                    let cell = globe.authoritative_cell(pos);
+                    (pos.into(), cell.material, cell.fluid_level)
+                })
+                .collect();
+            send_message_queue.queue.push_back(SendMessage {
+                destination: Destination::EveryoneElse,
+                game_message: CellDwellerMessage::SetFallenBlocks(SetFallenBlocksMessage {
+                    cells: cells,
+                }),
+                transport: Transport::TCP,
+            });
+        }
+
+        // Forget about globes that no longer exist.
+        self.queues.retain(|globe_entity, _| entities.is_alive(*globe_entity));
+    }
+}
+
+// Drop every unsupported block owned by the chunk by one cell,
+// recording the position of every cell that changes.
+fn simulate_chunk(globe: &mut Globe, chunk_origin: ChunkOrigin, changed_cells: &mut Vec<PosInOwningRoot>) {
+    let spec = globe.spec();
+    let resolution = spec.root_resolution;
+
+    // Work from the bottom up, so that a whole stack
+    // of loose blocks falls together.
+    let falling_cells: Vec<PosInOwningRoot> = {
+        let chunk = match globe.chunk_at(chunk_origin) {
+            Some(chunk) => chunk,
+            None => return,
+        };
+        let origin = chunk_origin.pos();
+        let end_x = origin.x + spec.chunk_resolution[0];
+        let end_y = origin.y + spec.chunk_resolution[1];
+        let end_z = origin.z + spec.chunk_resolution[2] - 1;
+        let mut falling_cells = Vec::new();
+        for z in origin.z..(end_z + 1) {
+            for y in origin.y..(end_y + 1) {
+                for x in origin.x..(end_x + 1) {
+                    let grid_point = GridPoint3::new(origin.root, x, y, z);
+                    if !chunk.cell(grid_point).material.falls() {
+                        continue;
+                    }
+                    let pos = PosInOwningRoot::new(grid_point, resolution);
+                    // Leave cells we share with other chunks to their owners.
+                    if globe.origin_of_chunk_owning(pos) == chunk_origin {
+                        falling_cells.push(pos);
+                    }
+                }
+            }
+        }
+        falling_cells
+    };
+
+    for pos in falling_cells {
+        if let Some(below_pos) = fall_from(globe, pos) {
+            // Both cells swapped contents.
+            changed_cells.push(below_pos);
+            changed_cells.push(pos);
+        }
+    }
+}
+
+/// Drop the block at the given position by one cell if there's
+/// nothing to hold it up.
+///
+/// Returns where it landed, or `None` if it stayed put.
+fn fall_from(globe: &mut Globe, pos: PosInOwningRoot) -> Option<PosInOwningRoot> {
+    let cell = *globe.authoritative_cell(pos);
+    if !cell.material.falls() || pos.pos().z == 0 {
+        // Either something already moved it, or it's resting on bedrock.
+        return None;
+    }
+    let mut below_pos = pos;
+    below_pos.set_z(pos.pos().z - 1);
+    let below_cell = match loaded_cell(globe, below_pos) {
+        Some(below_cell) => below_cell,
+        // Wait until we know what's down there.
+        None => return None,
+    };
+    if below_cell.material.is_solid() {
+        return None;
+    }
+    // Swap places with whatever was underneath, so that
+    // water gets pushed up out of the way rather than lost.
+    globe.set_authoritative_cell(below_pos, cell);
+    globe.set_authoritative_cell(pos, below_cell);
+    Some(below_pos)
+}
+
+/// Set what's in the given cell, as decided by the master
+/// when blocks fell into or out of it.
+///
+/// Keeps the cell's own shade, so that a falling block
+/// looks the same as any other of its material.
+pub fn set_fallen_cell(globe: &mut Globe, pos: PosInOwningRoot, material: Material, fluid_level: u8) {
+    let mut cell = *globe.authoritative_cell(pos);
+    cell.material = material;
+    cell.fluid_level = fluid_level;
+    globe.set_authoritative_cell(pos, cell);
+}
+
+fn loaded_cell(globe: &Globe, pos: PosInOwningRoot) -> Option<Cell> {
+    let chunk_origin = globe.origin_of_chunk_owning(pos);
+    globe.chunk_at(chunk_origin).map(|chunk| *chunk.cell(pos.into()))
+}
+
+impl AutoSystem for FallingBlockSystem {
+    fn name(&self) -> &'static str {
+        "falling_block"
+    }
+
+    fn dependencies(&self) -> Vec<&'static str> {
+        // Both of these move cells around, so they can't run at the same time anyway.
+        vec!["fluid"]
+    }
+
+    fn init(&mut self, world: &mut specs::World) {
+        use ::AutoResource;
+        NodeResource::ensure(world);
+        SendMessageQueue::ensure(world);
+        ensure_component_registered::<Globe>(world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sand_falls_until_it_lands() {
+        use rand::{XorShiftRng, SeedableRng};
+
+        let mut globe = Globe::new_example();
+        let spec = globe.spec();
+        let resolution = spec.root_resolution;
+        // Find some dry land to drop sand onto.
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        let landing_pos = loop {
+            let column = ::grid::random_column(resolution, &mut rng);
+            if globe.gen.land_height(column) > spec.ocean_radius {
+                break globe.find_lowest_cell_containing(column.with_z(0), Material::Air);
+            }
+        };
+        let landing_pos = PosInOwningRoot::new(landing_pos, resolution);
+        let mut sand_pos = landing_pos;
+        sand_pos.set_z(landing_pos.pos().z + 3);
+        let chunk_origin = globe.origin_of_chunk_owning(sand_pos);
+        globe.ensure_chunk_present(chunk_origin);
+        let mut sand = *globe.authoritative_cell(sand_pos);
+        sand.material = Material::Sand;
+        globe.set_authoritative_cell(sand_pos, sand);
+
+        let mut falls = 0;
+        while let Some(new_pos) = fall_from(&mut globe, sand_pos) {
+            sand_pos = new_pos;
+            falls += 1;
+            assert!(falls <= 3, "Sand fell through the ground");
+        }
+        assert_eq!(falls, 3);
+        assert_eq!(sand_pos, landing_pos);
+        assert_eq!(globe.authoritative_cell(landing_pos).material, Material::Sand);
+    }
+
+    #[test]
+    fn only_loose_materials_fall() {
+        assert!(Material::Sand.falls());
+        assert!(Material::Gravel.falls());
+        assert!(!Material::Dirt.falls());
+        assert!(!Material::Water.falls());
+    }
+}
@@ -0,0 +1,366 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use specs;
+use specs::{Entities, Fetch, FetchMut, WriteStorage};
+use slog::Logger;
+
+use types::*;
+use grid::{GridPoint3, PosInOwningRoot, Neighbors};
+use super::{Globe, ChunkOrigin};
+use super::chunk::{Cell, Material, MAX_FLUID_LEVEL};
+use auto_system::{AutoSystem, ensure_component_registered};
+use cell_dweller::{SendMessageQueue, CellDwellerMessage, SetFluidLevelsMessage};
+use net::{NodeResource, SendMessage, Destination, Transport};
+
+/// Makes water flow down and sideways into empty space.
+///
+/// This is a simple cellular automaton: each tick, every cell of water in
+/// a simulated chunk pours as much as it can into the cell below it, and
+/// then shares what's left with any neighbors at the same height that have
+/// at least two levels less water than it.
+///
+/// Only chunks that have had something change in or around them are simulated;
+/// once nothing in a chunk flows for a whole tick, it goes to sleep until something
+/// nearby changes again. See `Globe::set_authoritative_cell`.
+///
+/// Only the master simulates fluids; it tells everyone else
+/// about the cells that changed each tick.
+pub struct FluidSystem {
+    log: Logger,
+    seconds_between_ticks: TimeDelta,
+    seconds_until_next_tick: TimeDelta,
+    // Spread the work of big floods out over several ticks.
+    max_chunks_per_tick: usize,
+    // Active chunks waiting their turn, per globe.
+    queues: HashMap<specs::Entity, VecDeque<ChunkOrigin>>,
+}
+
+impl FluidSystem {
+    pub fn new(
+        parent_log: &Logger,
+        seconds_between_ticks: TimeDelta,
+        max_chunks_per_tick: usize,
+    ) -> FluidSystem {
+        FluidSystem {
+            log: parent_log.new(o!()),
+            seconds_between_ticks: seconds_between_ticks,
+            seconds_until_next_tick: 0.0,
+            max_chunks_per_tick: max_chunks_per_tick,
+            queues: HashMap::new(),
+        }
+    }
+
+    // Returns the positions of all cells that changed.
+    fn tick_globe(&mut self, globe_entity: specs::Entity, globe: &mut Globe) -> Vec<PosInOwningRoot> {
+        use super::globe::GlobeGuts;
+
+        let queue = self.queues.entry(globe_entity).or_insert_with(VecDeque::new);
+        if queue.is_empty() {
+            // Everyone's had their turn; find out who's active now.
+            let mut active_origins: Vec<ChunkOrigin> = globe
+                .chunks()
+                .iter()
+                .filter(|&(_, chunk)| chunk.is_fluid_active)
+                .map(|(origin, _)| *origin)
+                .collect();
+            // Chunks are stored in a hash map, so put them in a consistent
+            // order to make sure water flows the same way every time.
+            active_origins.sort_by_key(|origin| {
+                let pos = origin.pos();
+                (pos.root.index, pos.z, pos.y, pos.x)
+            });
+            queue.extend(active_origins);
+        }
+
+        let mut changed_cells = Vec::new();
+
+        let mut chunks_simulated = 0;
+        while chunks_simulated < self.max_chunks_per_tick {
+            let chunk_origin = match queue.pop_front() {
+                Some(chunk_origin) => chunk_origin,
+                None => break,
+            };
+            // Put it to sleep before simulating; if anything flows in
+            // or around it, then that will wake it up again.
+            match globe.chunks_mut().get_mut(&chunk_origin) {
+                Some(chunk) => {
+                    if !chunk.is_fluid_active {
+                        continue;
+                    }
+                    chunk.is_fluid_active = false;
+                }
+                // It's been unloaded since we queued it.
+                None => continue,
+            }
+            simulate_chunk(globe, chunk_origin, &mut changed_cells);
+            chunks_simulated += 1;
+        }
+
+        if chunks_simulated > 0 {
+            trace!(self.log, "Simulated fluids"; "chunks" => chunks_simulated, "still_queued" => queue.len(), "changed_cells" => changed_cells.len());
+        }
+        changed_cells
+    }
+}
+
+impl<'a> specs::System<'a> for FluidSystem {
+    type SystemData = (
+        Fetch<'a, TimeDeltaResource>,
+        Entities<'a>,
+        WriteStorage<'a, Globe>,
+        Fetch<'a, NodeResource>,
+        FetchMut<'a, SendMessageQueue>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        use specs::Join;
+
+        let (dt, entities, mut globes, node_resource, mut send_message_queue) = data;
+
+        // Peers find out how water flowed from the master.
+        if !node_resource.is_master {
+            return;
+        }
+
+        // Count down until the next tick.
+        self.seconds_until_next_tick -= dt.0;
+        if self.seconds_until_next_tick > 0.0 {
+            return;
+        }
+        self.seconds_until_next_tick = self.seconds_between_ticks;
+
+        for (globe_entity, globe) in (&*entities, &mut globes).join() {
+            let changed_cells = self.tick_globe(globe_entity, globe);
+            if changed_cells.is_empty() || !send_message_queue.has_consumer {
+                continue;
+            }
+
+            // Tell everyone else where the water ended up.
+            // TODO: identify the globe; see `RemoveBlockMessage`.
+            let mut seen = HashSet::new();
+            let cells: Vec<(GridPoint3, u8)> = changed_cells
+                .into_iter()
+                .filter(|pos| seen.insert(*pos))
+                .map(|pos| (pos.into(), globe.authoritative_cell(pos).fluid_level))
+                .collect();
+            send_message_queue.queue.push_back(SendMessage {
+                destination: Destination::EveryoneElse,
+                game_message: CellDwellerMessage::SetFluidLevels(SetFluidLevelsMessage {
+                    cells: cells,
+                }),
+                transport: Transport::TCP,
+            });
+        }
+
+        // Forget about globes that no longer exist.
+        self.queues.retain(|globe_entity, _| entities.is_alive(*globe_entity));
+    }
+}
+
+// Let every cell of fluid owned by the chunk flow one step,
+// recording the position of every cell that changes.
+fn simulate_chunk(globe: &mut Globe, chunk_origin: ChunkOrigin, changed_cells: &mut Vec<PosInOwningRoot>) {
+    let spec = globe.spec();
+    let resolution = spec.root_resolution;
+
+    // Find all the fluid cells owned by this chunk before we start
+    // moving any of it around.
+    let fluid_cells: Vec<PosInOwningRoot> = {
+        let chunk = match globe.chunk_at(chunk_origin) {
+            Some(chunk) => chunk,
+            None => return,
+        };
+        let origin = chunk_origin.pos();
+        let end_x = origin.x + spec.chunk_resolution[0];
+        let end_y = origin.y + spec.chunk_resolution[1];
+        let end_z = origin.z + spec.chunk_resolution[2] - 1;
+        let mut fluid_cells = Vec::new();
+        for z in origin.z..(end_z + 1) {
+            for y in origin.y..(end_y + 1) {
+                for x in origin.x..(end_x + 1) {
+                    let grid_point = GridPoint3::new(origin.root, x, y, z);
+                    if chunk.cell(grid_point).material != Material::Water {
+                        continue;
+                    }
+                    let pos = PosInOwningRoot::new(grid_point, resolution);
+                    // Leave cells we share with other chunks to their owners.
+                    if globe.origin_of_chunk_owning(pos) == chunk_origin {
+                        fluid_cells.push(pos);
+                    }
+                }
+            }
+        }
+        fluid_cells
+    };
+
+    for pos in fluid_cells {
+        flow_from(globe, pos, changed_cells);
+    }
+}
+
+fn flow_from(globe: &mut Globe, pos: PosInOwningRoot, changed_cells: &mut Vec<PosInOwningRoot>) {
+    let resolution = globe.spec().root_resolution;
+    let cell = *globe.authoritative_cell(pos);
+    if cell.material != Material::Water {
+        // Something else already moved it.
+        return;
+    }
+    let mut level = cell.fluid_level;
+
+    // Pour as much as we can straight down.
+    let mut is_resting = true;
+    if pos.pos().z > 0 {
+        let mut below_pos = pos;
+        below_pos.set_z(pos.pos().z - 1);
+        if let Some(below_cell) = loaded_cell(globe, below_pos) {
+            let room = room_for_fluid(&below_cell);
+            if room > 0 {
+                let poured = room.min(level);
+                globe.set_authoritative_cell(below_pos, with_fluid_level(below_cell, below_cell.fluid_level + poured));
+                changed_cells.push(below_pos);
+                level -= poured;
+                is_resting = poured == room;
+            }
+        }
+    }
+
+    // Then spread sideways over whatever we're resting on, leaving
+    // a little behind so puddles eventually settle.
+    if is_resting {
+        let neighbors: Vec<PosInOwningRoot> = Neighbors::new(pos.into(), resolution)
+            .filter(|neighbor_pos| neighbor_pos.z == pos.pos().z)
+            .map(|neighbor_pos| PosInOwningRoot::new(neighbor_pos, resolution))
+            .collect();
+        for neighbor_pos in neighbors {
+            if level <= 1 {
+                break;
+            }
+            let neighbor_cell = match loaded_cell(globe, neighbor_pos) {
+                Some(neighbor_cell) => neighbor_cell,
+                // Don't flow into chunks that aren't loaded.
+                None => continue,
+            };
+            if room_for_fluid(&neighbor_cell) == 0 {
+                continue;
+            }
+            if neighbor_cell.fluid_level + 1 < level {
+                globe.set_authoritative_cell(neighbor_pos, with_fluid_level(neighbor_cell, neighbor_cell.fluid_level + 1));
+                changed_cells.push(neighbor_pos);
+                level -= 1;
+            }
+        }
+    }
+
+    if level != cell.fluid_level {
+        globe.set_authoritative_cell(pos, with_fluid_level(cell, level));
+        changed_cells.push(pos);
+    }
+}
+
+/// Set how much water is in the given cell, as decided by the master.
+///
+/// Leaves the cell alone if it holds something other than water or air;
+/// whatever put it there will have told us more recently than the water did.
+pub fn set_fluid_level(globe: &mut Globe, pos: PosInOwningRoot, level: u8) {
+    let cell = *globe.authoritative_cell(pos);
+    if cell.material != Material::Water && cell.material != Material::Air {
+        return;
+    }
+    globe.set_authoritative_cell(pos, with_fluid_level(cell, level));
+}
+
+fn loaded_cell(globe: &Globe, pos: PosInOwningRoot) -> Option<Cell> {
+    let chunk_origin = globe.origin_of_chunk_owning(pos);
+    globe.chunk_at(chunk_origin).map(|chunk| *chunk.cell(pos.into()))
+}
+
+// How much more fluid the cell could hold.
+fn room_for_fluid(cell: &Cell) -> u8 {
+    match cell.material {
+        Material::Air => MAX_FLUID_LEVEL,
+        Material::Water => MAX_FLUID_LEVEL - cell.fluid_level,
+        _ => 0,
+    }
+}
+
+fn with_fluid_level(mut cell: Cell, level: u8) -> Cell {
+    cell.fluid_level = level;
+    cell.material = if level > 0 { Material::Water } else { Material::Air };
+    cell
+}
+
+impl AutoSystem for FluidSystem {
+    fn name(&self) -> &'static str {
+        "fluid"
+    }
+
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec!["chunk", "cd_mining"]
+    }
+
+    fn init(&mut self, world: &mut specs::World) {
+        use ::AutoResource;
+        NodeResource::ensure(world);
+        SendMessageQueue::ensure(world);
+        ensure_component_registered::<Globe>(world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn water_pours_into_hole() {
+        use rand::{XorShiftRng, SeedableRng};
+        use globe::globe::GlobeGuts;
+
+        let mut globe = Globe::new_example();
+        let spec = globe.spec();
+        let resolution = spec.root_resolution;
+        // Find a spot on the sea floor, and put a hole in it.
+        let mut rng = XorShiftRng::from_seed([5, 6, 7, 8]);
+        let (floor_pos, water_pos) = loop {
+            let column = ::grid::random_column(resolution, &mut rng);
+            if globe.gen.land_height(column) > spec.ocean_radius - spec.block_height * 3.0 {
+                // Not far enough under water.
+                continue;
+            }
+            let water_pos = globe.find_lowest_cell_containing(column.with_z(0), Material::Water);
+            if water_pos.z > 0 {
+                break (water_pos.with_z(water_pos.z - 1), water_pos);
+            }
+        };
+        let floor_pos = PosInOwningRoot::new(floor_pos, resolution);
+        let water_pos = PosInOwningRoot::new(water_pos, resolution);
+        let mut air = *globe.authoritative_cell(floor_pos);
+        air.material = Material::Air;
+        air.fluid_level = 0;
+        globe.set_authoritative_cell(floor_pos, air);
+
+        let mut changed_cells = Vec::new();
+        flow_from(&mut globe, water_pos, &mut changed_cells);
+        assert_eq!(changed_cells, vec![floor_pos, water_pos]);
+        let hole = *globe.authoritative_cell(floor_pos);
+        assert_eq!(hole.material, Material::Water);
+        assert_eq!(hole.fluid_level, MAX_FLUID_LEVEL);
+        assert_eq!(globe.authoritative_cell(water_pos).material, Material::Air);
+
+        // The chunk we changed should be awake.
+        let chunk_origin = globe.origin_of_chunk_owning(floor_pos);
+        assert!(globe.chunks()[&chunk_origin].is_fluid_active);
+    }
+
+    #[test]
+    fn fluid_levels_decide_material() {
+        let cell = Cell {
+            material: Material::Water,
+            shade: 1.0,
+            fluid_level: 3,
+        };
+        assert_eq!(room_for_fluid(&cell), MAX_FLUID_LEVEL - 3);
+        let drained = with_fluid_level(cell, 0);
+        assert_eq!(drained.material, Material::Air);
+        assert_eq!(room_for_fluid(&drained), MAX_FLUID_LEVEL);
+    }
+}
@@ -2,7 +2,16 @@ use noise;
 
 use grid::{GridPoint2, GridPoint3};
 use super::spec::Spec;
-use super::chunk::{Cell, Material};
+use super::chunk::{Cell, Material, MAX_FLUID_LEVEL};
+
+// How many blocks deep the sand or gravel on top of land near sea level goes.
+const SURFACE_LAYER_BLOCKS: f64 = 2.0;
+// Land whose surface is less than this many blocks above sea level is beach.
+const BEACH_BLOCKS_ABOVE_SEA_LEVEL: f64 = 1.5;
+// Sea floor less than this many blocks below sea level is sandy, like beaches...
+const SANDY_SEABED_BLOCKS: f64 = 2.0;
+// ...and then gravelly down to this many blocks below sea level.
+const GRAVELLY_SEABED_BLOCKS: f64 = 6.0;
 
 // TODO: turn this into a component that we can slap onto a Globe
 // or other globe-oid (distant point?).
@@ -84,7 +93,7 @@ impl Gen {
         // TEMP: ...
         let cell_height = cell_pt3.coords.norm();
         let material = if cell_height < land_height {
-            Material::Dirt
+            self.land_material(land_height, land_height - cell_height)
         } else if cell_height < self.spec.ocean_radius {
             Material::Water
         } else {
@@ -103,6 +112,27 @@ impl Gen {
             // kinda nice, but this probably isn't a great
             // long-term solution...
             shade: 1.0,
+            fluid_level: if material == Material::Water { MAX_FLUID_LEVEL } else { 0 },
+        }
+    }
+
+    // What land at the given depth below the surface is made of:
+    // sand on beaches and shallow seabed, gravel on the seabed
+    // a bit further out, and dirt everywhere else.
+    fn land_material(&self, land_height: f64, depth: f64) -> Material {
+        let block_height = self.spec.block_height;
+        if depth >= SURFACE_LAYER_BLOCKS * block_height {
+            return Material::Dirt;
+        }
+        let blocks_above_sea_level = (land_height - self.spec.ocean_radius) / block_height;
+        if blocks_above_sea_level >= BEACH_BLOCKS_ABOVE_SEA_LEVEL {
+            Material::Dirt
+        } else if blocks_above_sea_level > -SANDY_SEABED_BLOCKS {
+            Material::Sand
+        } else if blocks_above_sea_level > -GRAVELLY_SEABED_BLOCKS {
+            Material::Gravel
+        } else {
+            Material::Dirt
         }
     }
 }
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::vec;
 
 use specs;
 
@@ -20,14 +21,27 @@ pub struct Globe {
     pub gen: Gen,
     // Map chunk origins to chunks.
     //
-    // TODO: you'll probably also want to store some lower-res
-    // pseudo-chunks for rendering planets at a distance.
-    // But maybe you can put that off? Or maybe that's an entirely
-    // different type of Globe-oid?
+    // Terrain too far away to have chunks loaded is drawn at lower
+    // resolution straight from `gen`; see `LodSystem`.
     chunks: HashMap<ChunkOrigin, Chunk>,
     // Track which chunks are up-to-date with authoritative data for cells
     // they share with a neighbor.
     chunk_pairs: HashMap<ChunkPairOrigins, ChunkPair>,
+    // Every authoritative cell change since this was last drained,
+    // if anything has asked for them to be logged.
+    is_logging_cell_changes: bool,
+    cell_changes: Vec<CellChange>,
+}
+
+/// Record of a single authoritative change to a cell in a `Globe`.
+///
+/// See `Globe::drain_cell_changes`.
+#[derive(Clone, Copy, Debug)]
+pub struct CellChange {
+    /// Position of the cell in its owning root.
+    pub pos: GridPoint3,
+    pub old_cell: Cell,
+    pub new_cell: Cell,
 }
 
 // Allowing sibling modules to reach into semi-private parts
@@ -54,6 +68,8 @@ impl Globe {
             gen: Gen::new(spec),
             chunks: HashMap::new(),
             chunk_pairs: HashMap::new(),
+            is_logging_cell_changes: false,
+            cell_changes: Vec::new(),
         }
     }
 
@@ -276,6 +292,87 @@ impl Globe {
         }
     }
 
+    /// Replace the authoritative cell at the given position, and keep everything
+    /// that depends on it up to date: copies of the cell in neighboring chunks,
+    /// chunk views, and fluid and falling block simulation. The change is also
+    /// logged for anything else that's interested, if logging has been turned on;
+    /// see `set_logging_cell_changes`.
+    ///
+    /// Returns the cell that was there before.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the chunk owning the cell isn't loaded.
+    pub fn set_authoritative_cell(&mut self, pos: PosInOwningRoot, new_cell: Cell) -> Cell {
+        use super::is_point_on_chunk_edge;
+
+        let old_cell = {
+            let cell = self.authoritative_cell_mut(pos);
+            let old_cell = *cell;
+            *cell = new_cell;
+            old_cell
+        };
+        // Some extra stuff is only relevant if the cell is on the edge of its chunk.
+        if is_point_on_chunk_edge(*pos.pos(), self.spec.chunk_resolution) {
+            // Bump version of owned shared cells.
+            self.increment_chunk_owned_edge_version_for_cell(pos);
+            // Propagate change to neighbouring chunks.
+            let chunk_origin = self.origin_of_chunk_owning(pos);
+            self.push_shared_cells_for_chunk(chunk_origin);
+        }
+        // Mark the view for the containing chunk and those containing each cell surrounding
+        // it as being dirty. (This cell might affect the visibility of cells in those chunks.)
+        self.mark_chunk_views_affected_by_cell_as_dirty(pos.into());
+        self.wake_simulations_around_cell(pos);
+        if self.is_logging_cell_changes {
+            self.cell_changes.push(CellChange {
+                pos: *pos.pos(),
+                old_cell: old_cell,
+                new_cell: new_cell,
+            });
+        }
+        old_cell
+    }
+
+    /// Start or stop logging changes made through `set_authoritative_cell`;
+    /// see `drain_cell_changes`. Logging is off by default.
+    ///
+    /// Changes pile up until they're drained, so only turn this on if something
+    /// is going to drain them regularly; in a running app that's `CellChangeSystem`,
+    /// which turns it on for every globe it sees. Turning it off throws away
+    /// any changes that haven't been drained yet.
+    pub fn set_logging_cell_changes(&mut self, is_logging_cell_changes: bool) {
+        self.is_logging_cell_changes = is_logging_cell_changes;
+        if !is_logging_cell_changes {
+            self.cell_changes.clear();
+        }
+    }
+
+    /// Take every change made through `set_authoritative_cell` since
+    /// this was last called, in the order they were made.
+    pub fn drain_cell_changes(&mut self) -> vec::Drain<CellChange> {
+        self.cell_changes.drain(..)
+    }
+
+    // Any fluid in or next to this cell might now be able to flow,
+    // and any falling block might have lost its support.
+    fn wake_simulations_around_cell(&mut self, pos: PosInOwningRoot) {
+        let resolution = self.spec.root_resolution;
+        let mut cells = vec![pos];
+        cells.extend(
+            Neighbors::new(pos.into(), resolution)
+                .map(|neighbor_pos| PosInOwningRoot::new(neighbor_pos, resolution)),
+        );
+        for cell_pos in cells {
+            let chunk_origin = self.origin_of_chunk_owning(cell_pos);
+            // It's fine for the chunk to not be loaded.
+            if let Some(chunk) = self.chunks.get_mut(&chunk_origin) {
+                chunk.is_fluid_active = true;
+                chunk.is_falling_active = true;
+            }
+        }
+    }
+
     pub fn increment_chunk_owned_edge_version_for_cell(&mut self, pos: PosInOwningRoot) {
         let chunk_origin = self.origin_of_chunk_owning(pos.into());
         let chunk = self.chunks.get_mut(&chunk_origin).expect(
@@ -303,6 +400,32 @@ impl Globe {
         if self.chunks.insert(chunk_origin, chunk).is_some() {
             panic!("There was already a chunk loaded at the same origin!");
         }
+
+        self.mark_views_above_and_below_as_dirty(chunk_origin);
+    }
+
+    /// Chunks don't share any cells in the z-direction, so the chunks directly
+    /// above and below a newly loaded chunk have been drawing the faces that
+    /// border it as hidden. Make sure their views get rebuilt now that we know
+    /// what's actually there.
+    fn mark_views_above_and_below_as_dirty(&mut self, chunk_origin: ChunkOrigin) {
+        let spec = self.spec();
+        let pos = *chunk_origin.pos();
+        let chunk_height = spec.chunk_resolution[2];
+        let mut neighbor_z_values = vec![pos.z + chunk_height];
+        if pos.z >= chunk_height {
+            neighbor_z_values.push(pos.z - chunk_height);
+        }
+        for z in neighbor_z_values {
+            let neighbor_origin = ChunkOrigin::new(
+                pos.with_z(z),
+                spec.root_resolution,
+                spec.chunk_resolution,
+            );
+            if let Some(neighbor) = self.chunks.get_mut(&neighbor_origin) {
+                neighbor.mark_view_as_dirty();
+            }
+        }
     }
 
     /// Remove the chunk at the given chunk origin. Returns the removed chunk.
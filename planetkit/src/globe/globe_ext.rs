@@ -3,7 +3,7 @@
 
 use rand::Rng;
 
-use grid::{GridPoint2, GridPoint3, PosInOwningRoot, GridCoord};
+use grid::{GridPoint2, GridPoint3, PosInOwningRoot, GridCoord, Root};
 use grid::random_column;
 use super::chunk::Material;
 use super::CursorMut;
@@ -66,7 +66,7 @@ impl Globe {
                     let cell = cursor.cell().expect(
                         "We just ensured the chunk is present, but apparently it's not. Kaboom!",
                     );
-                    if cell.material != Material::Dirt {
+                    if !cell.material.is_solid() {
                         continue;
                     }
                 }
@@ -172,4 +172,35 @@ impl Globe {
             cursor.set_pos(new_pos);
         }
     }
+
+    /// How low and high (in cells) the surface of the land or ocean gets
+    /// over all the columns of cells in a column of chunks, identified by the
+    /// number of chunks from the north corner of the root along each axis.
+    ///
+    /// This is worked out from the globe's generator, so it doesn't need any
+    /// chunks to be loaded, and doesn't know about any changes made since.
+    pub fn surface_cell_z_range(
+        &self,
+        root: Root,
+        chunk_x: GridCoord,
+        chunk_y: GridCoord,
+    ) -> (GridCoord, GridCoord) {
+        let spec = self.spec();
+        let origin_x = chunk_x * spec.chunk_resolution[0];
+        let origin_y = chunk_y * spec.chunk_resolution[1];
+        let mut min_surface = ::std::f64::INFINITY;
+        let mut max_surface = ::std::f64::NEG_INFINITY;
+        for y in origin_y..(origin_y + spec.chunk_resolution[1] + 1) {
+            for x in origin_x..(origin_x + spec.chunk_resolution[0] + 1) {
+                let column = GridPoint2::new(root, x, y);
+                let surface = self.gen.land_height(column).max(spec.ocean_radius);
+                min_surface = min_surface.min(surface);
+                max_surface = max_surface.max(surface);
+            }
+        }
+        (
+            spec.approx_cell_z_from_radius(min_surface),
+            spec.approx_cell_z_from_radius(max_surface),
+        )
+    }
 }
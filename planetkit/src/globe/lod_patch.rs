@@ -0,0 +1,130 @@
+use specs;
+
+use grid::{GridCoord, GridPoint2, Root, ROOTS};
+use super::spec::Spec;
+
+/// Identifies a square patch of a root quad, covering a power-of-two
+/// number of chunk columns along each side, for drawing terrain
+/// at a lower level of detail than we'd get from loading its chunks.
+///
+/// Level 0 patches cover a single column of chunks; each level up
+/// covers twice as many columns in each direction. `x` and `y` count
+/// patches of the same level from the north corner of the root.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub struct LodPatchId {
+    pub root: Root,
+    pub level: u8,
+    pub x: GridCoord,
+    pub y: GridCoord,
+}
+
+impl LodPatchId {
+    /// The coarsest patches, which between them cover the whole globe.
+    pub fn top_level(spec: &Spec) -> Vec<LodPatchId> {
+        let chunks_per_root_side = spec.chunks_per_root_side();
+        let mut level = 0;
+        while (1 << level) < chunks_per_root_side[0] {
+            level += 1;
+        }
+        let side = 1 << level;
+        let patches_x = (chunks_per_root_side[0] + side - 1) / side;
+        let patches_y = (chunks_per_root_side[1] + side - 1) / side;
+        let mut patches = Vec::new();
+        for root in ROOTS.iter() {
+            for y in 0..patches_y {
+                for x in 0..patches_x {
+                    patches.push(LodPatchId {
+                        root: *root,
+                        level: level,
+                        x: x,
+                        y: y,
+                    });
+                }
+            }
+        }
+        patches
+    }
+
+    /// How many chunk columns this patch covers along each side,
+    /// if it doesn't hang off the edge of its root.
+    pub fn side_in_chunks(&self) -> GridCoord {
+        1 << self.level
+    }
+
+    /// The four patches at the next level down covering the same area,
+    /// leaving out any that would lie entirely off the edge of the root.
+    ///
+    /// Panics if called on a level 0 patch.
+    pub fn children(&self, spec: &Spec) -> Vec<LodPatchId> {
+        assert!(self.level > 0, "Level 0 patches have no children");
+        let chunks_per_root_side = spec.chunks_per_root_side();
+        let child_side = self.side_in_chunks() / 2;
+        let mut children = Vec::with_capacity(4);
+        for dy in 0..2 {
+            for dx in 0..2 {
+                let child = LodPatchId {
+                    root: self.root,
+                    level: self.level - 1,
+                    x: self.x * 2 + dx,
+                    y: self.y * 2 + dy,
+                };
+                if child.x * child_side < chunks_per_root_side[0] &&
+                    child.y * child_side < chunks_per_root_side[1]
+                {
+                    children.push(child);
+                }
+            }
+        }
+        children
+    }
+
+    /// Whether this patch covers the given chunk column, identified by the
+    /// number of chunks from the north corner of the root along each axis.
+    pub fn contains_chunk_column(&self, root: Root, chunk_x: GridCoord, chunk_y: GridCoord) -> bool {
+        let side = self.side_in_chunks();
+        root == self.root &&
+            chunk_x >= self.x * side && chunk_x < (self.x + 1) * side &&
+            chunk_y >= self.y * side && chunk_y < (self.y + 1) * side
+    }
+
+    /// The column of cells at the north corner of the patch.
+    pub fn origin(&self, spec: &Spec) -> GridPoint2 {
+        let side = self.side_in_chunks();
+        GridPoint2::new(
+            self.root,
+            self.x * side * spec.chunk_resolution[0],
+            self.y * side * spec.chunk_resolution[1],
+        )
+    }
+
+    /// The column of cells at the south corner of the patch,
+    /// which it shares with its neighbors.
+    pub fn end(&self, spec: &Spec) -> GridPoint2 {
+        let side = self.side_in_chunks();
+        GridPoint2::new(
+            self.root,
+            ((self.x + 1) * side * spec.chunk_resolution[0]).min(spec.root_resolution[0]),
+            ((self.y + 1) * side * spec.chunk_resolution[1]).min(spec.root_resolution[1]),
+        )
+    }
+}
+
+/// A patch of a globe's terrain that is drawn as a coarse heightmap,
+/// standing in for chunks that aren't loaded. See `LodSystem`.
+pub struct LodPatch {
+    pub globe_entity: specs::Entity,
+    pub id: LodPatchId,
+}
+
+impl LodPatch {
+    pub fn new(globe_entity: specs::Entity, id: LodPatchId) -> LodPatch {
+        LodPatch {
+            globe_entity: globe_entity,
+            id: id,
+        }
+    }
+}
+
+impl specs::Component for LodPatch {
+    type Storage = specs::HashMapStorage<LodPatch>;
+}
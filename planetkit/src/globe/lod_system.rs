@@ -0,0 +1,411 @@
+use std::collections::{HashMap, HashSet};
+
+use na;
+use specs;
+use specs::{Entities, Fetch, ReadStorage, WriteStorage};
+use slog::Logger;
+
+use types::*;
+use grid::{GridCoord, GridPoint3, Root};
+use super::{Globe, View, ChunkOrigin, LodPatch, LodPatchId};
+use super::spec::Spec;
+use render::{Visual, ProtoMesh, Vertex};
+use Spatial;
+use auto_system::{AutoSystem, ensure_component_registered};
+
+/// Draws coarse terrain for parts of each `Globe` that don't have chunks loaded,
+/// so that the horizon isn't empty.
+///
+/// Each root quad is covered by a quadtree of `LodPatch`es, built straight from the
+/// globe's generator. Patches get split into smaller, more detailed ones around
+/// loaded chunks, and the smallest patches are removed altogether once geometry
+/// has been built for the chunks that the surface passes through in their column.
+/// That way distant terrain is drawn coarsely, and nearby terrain gets swapped out
+/// for the real thing as it loads.
+pub struct LodSystem {
+    log: Logger,
+    seconds_between_updates: TimeDelta,
+    seconds_until_next_update: TimeDelta,
+    // Which chunks the surface passes through in each column of chunks.
+    // This only depends on the globe's generator, so it never goes stale.
+    surface_chunks: HashMap<(specs::Entity, ChunkColumn), Vec<ChunkOrigin>>,
+}
+
+impl LodSystem {
+    pub fn new(parent_log: &Logger, seconds_between_updates: TimeDelta) -> LodSystem {
+        LodSystem {
+            log: parent_log.new(o!()),
+            seconds_between_updates: seconds_between_updates,
+            seconds_until_next_update: 0.0,
+            surface_chunks: HashMap::new(),
+        }
+    }
+
+    fn update_globe<'a>(
+        &mut self,
+        entities: &Entities<'a>,
+        globe: &Globe,
+        globe_entity: specs::Entity,
+        lod_patches: &mut WriteStorage<'a, LodPatch>,
+        visuals: &mut WriteStorage<'a, Visual>,
+        spatials: &mut WriteStorage<'a, Spatial>,
+    ) {
+        use specs::Join;
+
+        let spec = globe.spec();
+        let loaded_columns = loaded_chunk_columns(globe);
+        let built_columns: HashSet<ChunkColumn> = loaded_columns
+            .iter()
+            .cloned()
+            .filter(|&column| self.is_surface_built(globe, globe_entity, column))
+            .collect();
+        let wanted: HashSet<LodPatchId> = choose_patches(&spec, &loaded_columns, &built_columns)
+            .into_iter()
+            .collect();
+
+        // Get rid of patches we no longer want; either they've been
+        // split into smaller ones, or real chunks are being drawn instead.
+        let mut existing: HashMap<LodPatchId, specs::Entity> = HashMap::new();
+        let mut entities_to_remove: Vec<specs::Entity> = Vec::new();
+        for (lod_patch, lod_patch_ent) in (&*lod_patches, &**entities).join() {
+            if lod_patch.globe_entity != globe_entity {
+                continue;
+            }
+            if wanted.contains(&lod_patch.id) {
+                existing.insert(lod_patch.id, lod_patch_ent);
+            } else {
+                entities_to_remove.push(lod_patch_ent);
+            }
+        }
+        for lod_patch_ent in entities_to_remove {
+            remove_patch(entities, lod_patch_ent, lod_patches, visuals);
+        }
+
+        // Make any new patches we need.
+        let globe_view = View::new(spec, &self.log);
+        for id in wanted {
+            if existing.contains_key(&id) {
+                continue;
+            }
+            trace!(self.log, "Making an LOD patch"; "patch" => format!("{:?}", id));
+            let mut vertex_data: Vec<Vertex> = Vec::new();
+            let mut index_data: Vec<u32> = Vec::new();
+            globe_view.make_lod_patch_geometry(globe, id, &mut vertex_data, &mut index_data);
+
+            // The geometry is relative to the north corner of the patch at sea level.
+            let patch_origin_pos = spec.cell_center_on_unit_sphere(id.origin(&spec)) * spec.ocean_radius;
+            let patch_transform = Iso3::new(patch_origin_pos.coords, na::zero());
+
+            let mut visual = Visual::new_empty();
+            visual.proto_mesh = ProtoMesh::new(vertex_data, index_data).into();
+            let new_ent = entities.create();
+            lod_patches.insert(new_ent, LodPatch::new(globe_entity, id));
+            visuals.insert(new_ent, visual);
+            spatials.insert(new_ent, Spatial::new(globe_entity, patch_transform));
+        }
+    }
+
+    // Whether geometry has been built for all the chunks that the surface
+    // passes through in the given column, so that there would be no hole
+    // left in the terrain if we stopped drawing the patch covering it.
+    fn is_surface_built(&mut self, globe: &Globe, globe_entity: specs::Entity, column: ChunkColumn) -> bool {
+        let surface_chunks = self.surface_chunks
+            .entry((globe_entity, column))
+            .or_insert_with(|| surface_chunk_origins(globe, column));
+        surface_chunks.iter().all(|&origin| {
+            globe
+                .chunk_at(origin)
+                .map(|chunk| chunk.has_view_been_built)
+                .unwrap_or(false)
+        })
+    }
+}
+
+impl<'a> specs::System<'a> for LodSystem {
+    type SystemData = (
+        Entities<'a>,
+        Fetch<'a, TimeDeltaResource>,
+        ReadStorage<'a, Globe>,
+        WriteStorage<'a, LodPatch>,
+        WriteStorage<'a, Visual>,
+        WriteStorage<'a, Spatial>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        use specs::Join;
+
+        let (entities, dt, globes, mut lod_patches, mut visuals, mut spatials) = data;
+
+        // Chunks come and go fairly slowly; there's no need
+        // to rework the patches every frame.
+        self.seconds_until_next_update -= dt.0;
+        if self.seconds_until_next_update > 0.0 {
+            return;
+        }
+        self.seconds_until_next_update = self.seconds_between_updates;
+
+        for (globe, globe_entity) in (&globes, &*entities).join() {
+            self.update_globe(
+                &entities,
+                globe,
+                globe_entity,
+                &mut lod_patches,
+                &mut visuals,
+                &mut spatials,
+            );
+        }
+
+        // Clean up patches belonging to globes that no longer exist.
+        let orphans: Vec<specs::Entity> = (&lod_patches, &*entities)
+            .join()
+            .filter(|&(lod_patch, _)| !entities.is_alive(lod_patch.globe_entity))
+            .map(|(_, lod_patch_ent)| lod_patch_ent)
+            .collect();
+        for lod_patch_ent in orphans {
+            remove_patch(&entities, lod_patch_ent, &mut lod_patches, &mut visuals);
+        }
+        self.surface_chunks.retain(|&(globe_entity, _), _| entities.is_alive(globe_entity));
+    }
+}
+
+// Remove the patch's `Visual` along with everything else, so that its
+// pointer to the patch's mesh is dropped; the mesh repository then frees
+// the mesh the next time it collects garbage.
+fn remove_patch<'a>(
+    entities: &Entities<'a>,
+    lod_patch_ent: specs::Entity,
+    lod_patches: &mut WriteStorage<'a, LodPatch>,
+    visuals: &mut WriteStorage<'a, Visual>,
+) {
+    visuals.remove(lod_patch_ent);
+    lod_patches.remove(lod_patch_ent);
+    entities.delete(lod_patch_ent).expect("Somehow tried to use an entity with the wrong generation!");
+}
+
+type ChunkColumn = (Root, GridCoord, GridCoord);
+
+// Find which columns of chunks have any chunks loaded.
+fn loaded_chunk_columns(globe: &Globe) -> HashSet<ChunkColumn> {
+    use super::globe::GlobeGuts;
+
+    let spec = globe.spec();
+    globe
+        .chunks()
+        .keys()
+        .map(|chunk_origin| {
+            let pos = chunk_origin.pos();
+            (
+                pos.root,
+                pos.x / spec.chunk_resolution[0],
+                pos.y / spec.chunk_resolution[1],
+            )
+        })
+        .collect()
+}
+
+// Find the chunks that the surface of the land or ocean passes through in a column.
+fn surface_chunk_origins(globe: &Globe, column: ChunkColumn) -> Vec<ChunkOrigin> {
+    let spec = globe.spec();
+    let (root, chunk_x, chunk_y) = column;
+    let (min_z, max_z) = globe.surface_cell_z_range(root, chunk_x, chunk_y);
+    let mut origins = Vec::new();
+    let mut chunk_z = min_z.max(0) / spec.chunk_resolution[2] * spec.chunk_resolution[2];
+    while chunk_z <= max_z {
+        origins.push(ChunkOrigin::new(
+            GridPoint3::new(
+                root,
+                chunk_x * spec.chunk_resolution[0],
+                chunk_y * spec.chunk_resolution[1],
+                chunk_z,
+            ),
+            spec.root_resolution,
+            spec.chunk_resolution,
+        ));
+        chunk_z += spec.chunk_resolution[2];
+    }
+    origins
+}
+
+// Pick the set of patches to draw, given which columns of chunks
+// are loaded and which of those are already being drawn in full.
+//
+// Starting from the coarsest patches, split any patch that covers loaded chunks
+// into smaller ones. Level 0 patches are left out altogether once the surface
+// chunks in their column are being drawn, but kept until then so there's no gap
+// while they're built. (Chunks built deep underground don't count.)
+fn choose_patches(
+    spec: &Spec,
+    loaded_columns: &HashSet<ChunkColumn>,
+    built_columns: &HashSet<ChunkColumn>,
+) -> Vec<LodPatchId> {
+    let mut chosen = Vec::new();
+    let mut to_visit = LodPatchId::top_level(spec);
+    while let Some(patch) = to_visit.pop() {
+        let covers_loaded_chunks = loaded_columns
+            .iter()
+            .any(|&(root, x, y)| patch.contains_chunk_column(root, x, y));
+        if !covers_loaded_chunks {
+            chosen.push(patch);
+        } else if patch.level > 0 {
+            to_visit.extend(patch.children(spec));
+        } else if !built_columns.contains(&(patch.root, patch.x, patch.y)) {
+            chosen.push(patch);
+        }
+    }
+    chosen
+}
+
+impl AutoSystem for LodSystem {
+    fn name(&self) -> &'static str {
+        "lod"
+    }
+
+    fn init(&mut self, world: &mut specs::World) {
+        ensure_component_registered::<Globe>(world);
+        ensure_component_registered::<LodPatch>(world);
+        ensure_component_registered::<Visual>(world);
+        ensure_component_registered::<Spatial>(world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use grid::ROOTS;
+
+    // Total number of chunk columns covered by the given patches,
+    // making sure that none of them overlap.
+    fn columns_covered(spec: &Spec, patches: &[LodPatchId]) -> usize {
+        let chunks_per_root_side = spec.chunks_per_root_side();
+        let mut covered = 0;
+        for root in ROOTS.iter() {
+            for y in 0..chunks_per_root_side[1] {
+                for x in 0..chunks_per_root_side[0] {
+                    let count = patches
+                        .iter()
+                        .filter(|patch| patch.contains_chunk_column(*root, x, y))
+                        .count();
+                    assert!(count <= 1, "LOD patches should never overlap");
+                    covered += count;
+                }
+            }
+        }
+        covered
+    }
+
+    #[test]
+    fn coarse_patches_cover_whole_globe() {
+        let spec = Globe::new_example().spec();
+        let chunks_per_root_side = spec.chunks_per_root_side();
+        let all_columns = (5 * chunks_per_root_side[0] * chunks_per_root_side[1]) as usize;
+
+        let patches = choose_patches(&spec, &HashSet::new(), &HashSet::new());
+        assert_eq!(columns_covered(&spec, &patches), all_columns);
+        // With nothing loaded, there's no reason to draw anything in detail.
+        assert_eq!(patches.len(), LodPatchId::top_level(&spec).len());
+    }
+
+    #[test]
+    fn patches_make_way_for_loaded_chunks() {
+        let spec = Globe::new_example().spec();
+        let chunks_per_root_side = spec.chunks_per_root_side();
+        let all_columns = (5 * chunks_per_root_side[0] * chunks_per_root_side[1]) as usize;
+        let column: ChunkColumn = (ROOTS[2], 1, 3);
+        let mut loaded_columns = HashSet::new();
+        loaded_columns.insert(column);
+
+        // Keep drawing the column until its chunks have been built...
+        let patches = choose_patches(&spec, &loaded_columns, &HashSet::new());
+        assert_eq!(columns_covered(&spec, &patches), all_columns);
+        assert!(patches.contains(&LodPatchId {
+            root: ROOTS[2],
+            level: 0,
+            x: 1,
+            y: 3,
+        }));
+        assert!(patches.len() > LodPatchId::top_level(&spec).len());
+
+        // ...and then leave a hole for them.
+        let patches = choose_patches(&spec, &loaded_columns, &loaded_columns);
+        assert_eq!(columns_covered(&spec, &patches), all_columns - 1);
+    }
+
+    #[test]
+    fn patches_stay_until_surface_chunks_are_built() {
+        use specs::{Join, RunNow};
+        use super::super::globe::GlobeGuts;
+
+        let log = Logger::root(::slog::Discard, o!());
+        let mut world = specs::World::new();
+        world.add_resource(TimeDeltaResource(0.0));
+        let mut lod_sys = LodSystem::new(&log, 0.0);
+        lod_sys.init(&mut world);
+
+        let globe = Globe::new_example();
+        let spec = globe.spec();
+        let column: ChunkColumn = (ROOTS[2], 1, 3);
+        let surface_chunks = surface_chunk_origins(&globe, column);
+        assert!(!surface_chunks.is_empty());
+        let underground_chunk = ChunkOrigin::new(
+            GridPoint3::new(ROOTS[2], spec.chunk_resolution[0], spec.chunk_resolution[1] * 3, 0),
+            spec.root_resolution,
+            spec.chunk_resolution,
+        );
+        assert!(!surface_chunks.contains(&underground_chunk));
+        let globe_entity = world.create_entity().with(globe).build();
+
+        let build_chunk = |world: &mut specs::World, origin: ChunkOrigin| {
+            let mut globes = world.write::<Globe>();
+            let globe = globes.get_mut(globe_entity).unwrap();
+            globe.ensure_chunk_present(origin);
+            globe.chunks_mut().get_mut(&origin).unwrap().mark_view_as_clean();
+        };
+        let level_0_patch = LodPatchId {
+            root: ROOTS[2],
+            level: 0,
+            x: 1,
+            y: 3,
+        };
+        let mut patch_entities = |world: &mut specs::World| -> Vec<specs::Entity> {
+            lod_sys.run_now(&world.res);
+            world.maintain();
+            let lod_patches = world.read::<LodPatch>();
+            let entities = world.entities();
+            let mut patch_ents = Vec::new();
+            for (lod_patch, ent) in (&lod_patches, &*entities).join() {
+                if lod_patch.id == level_0_patch {
+                    patch_ents.push(ent);
+                }
+            }
+            patch_ents
+        };
+
+        // Building geometry for a chunk deep underground
+        // shouldn't leave a hole in the surface.
+        build_chunk(&mut world, underground_chunk);
+        let patch_ents = patch_entities(&mut world);
+        assert_eq!(patch_ents.len(), 1);
+
+        // Once the surface is built, the patch and its visual should be gone.
+        for &origin in &surface_chunks {
+            build_chunk(&mut world, origin);
+        }
+        assert!(patch_entities(&mut world).is_empty());
+        assert!(world.read::<Visual>().get(patch_ents[0]).is_none());
+    }
+
+    #[test]
+    fn patch_geometry_is_a_grid_of_samples() {
+        let globe = Globe::new_example();
+        let spec = globe.spec();
+        let log = Logger::root(::slog::Discard, o!());
+        let globe_view = View::new(spec, &log);
+        for patch in LodPatchId::top_level(&spec) {
+            let mut vertex_data: Vec<Vertex> = Vec::new();
+            let mut index_data: Vec<u32> = Vec::new();
+            globe_view.make_lod_patch_geometry(&globe, patch, &mut vertex_data, &mut index_data);
+            assert_eq!(vertex_data.len(), 9 * 9);
+            assert_eq!(index_data.len(), 8 * 8 * 6);
+        }
+    }
+}
@@ -10,11 +10,20 @@ mod gen;
 mod chunk_view;
 mod chunk_view_system;
 mod chunk_system;
+mod fluid_system;
+mod falling_block_system;
+mod cell_change_system;
+mod lod_patch;
+mod lod_system;
+mod export;
 mod cursor;
 mod chunk_origin;
 mod iters;
 mod chunk_shared_points;
 mod chunk_pair;
+mod raycast;
+mod region_query;
+mod bundle;
 
 #[cfg(test)]
 mod tests;
@@ -22,18 +31,27 @@ mod tests;
 use types::*;
 
 // TODO: be selective in what you export; no wildcards!
-pub use self::globe::Globe;
+pub use self::globe::{Globe, CellChange};
 pub use self::spec::*;
 pub use self::view::*;
 pub use self::chunk_view::*;
 pub use self::chunk_view_system::*;
 pub use self::chunk_system::ChunkSystem;
+pub use self::fluid_system::{FluidSystem, set_fluid_level};
+pub use self::falling_block_system::{FallingBlockSystem, set_fallen_cell};
+pub use self::cell_change_system::{CellChangeSystem, CellChanged};
+pub use self::lod_patch::{LodPatch, LodPatchId};
+pub use self::lod_system::LodSystem;
+pub use self::export::{make_chunk_mesh, make_loaded_chunks_mesh, ensure_surface_chunks_present};
 pub use self::cursor::{Cursor, CursorMut};
 pub use self::chunk_origin::*;
 pub use self::iters::*;
 pub use self::chunk_shared_points::ChunkSharedPoints;
+pub use self::raycast::{RaycastHit, RaycastResult};
+pub use self::region_query::CellsInRegion;
+pub use self::bundle::GlobeBundle;
 
-use grid::{GridCoord, GridPoint3, Root, PosInOwningRoot};
+use grid::{GridCoord, GridPoint3, Root, ROOTS, PosInOwningRoot};
 
 // TODO: move project into icosahedron module.
 
@@ -107,6 +125,131 @@ pub fn project(root: Root, mut pt_in_root_quad: Pt2) -> Pt3 {
     //             (1, 2)
     //              3_0
     //
+    let vertices = root_quad_vertices(root);
+    let a = vertices[0];
+    let b = vertices[1];
+    let c = vertices[2];
+    let d = vertices[3];
+    let e = vertices[4];
+    let f = vertices[5];
+
+    // Triangle 0
+    let ab = b - a;
+    let ac = c - a;
+    // Triangle 1
+    let db = b - d;
+    let dc = c - d;
+    // Triangle 2
+    let cd = d - c;
+    let ce = e - c;
+    // Triangle 3
+    let fd = d - f;
+    let fe = e - f;
+
+    // It'll be easier to do the math we need here if the positions
+    // lie between (0, 0) and (1, 2).
+    pt_in_root_quad[1] *= 2.0;
+
+    // Decide which triangle we're in.
+    let pos_on_icosahedron = if pt_in_root_quad[0] + pt_in_root_quad[1] < 1.0 {
+        // In triangle 0.
+        a + ab * pt_in_root_quad[0] + ac * pt_in_root_quad[1]
+    } else if pt_in_root_quad[1] < 1.0 {
+        // In triangle 1.
+        d + dc * (1.0 - pt_in_root_quad[0]) + db * (1.0 - pt_in_root_quad[1])
+    } else if pt_in_root_quad[0] + pt_in_root_quad[1] < 2.0 {
+        // In triangle 2.
+        // Bring the y-value back into [0, 1] so we can just repeat the math from above.
+        pt_in_root_quad[1] -= 1.0;
+        c + cd * pt_in_root_quad[0] + ce * pt_in_root_quad[1]
+    } else {
+        // In triangle 3.
+        // Bring the y-value back into [0, 1] so we can just repeat the math from above.
+        pt_in_root_quad[1] -= 1.0;
+        f + fe * (1.0 - pt_in_root_quad[0]) + fd * (1.0 - pt_in_root_quad[1])
+    };
+    Pt3::from_coordinates(pos_on_icosahedron.coords.normalize())
+}
+
+/// Inverse of `project`: find the root quad containing the given point, and
+/// where in that root quad it lies.
+///
+/// The point need not lie on the unit sphere; only its direction from the
+/// centre of the globe matters. The position returned is in the same space
+/// as that accepted by `project`, i.e. with one corner of the root quad at (0, 0)
+/// and the opposite corner at (1, 1).
+///
+/// Points on the edge of a root quad may be returned relative to any root
+/// that contains them.
+///
+/// Returns `None` if given the origin, because it has no direction.
+pub fn unproject(pt: Pt3) -> Option<(Root, Pt2)> {
+    // Allow a little bit of slop so that points exactly on the edge
+    // of a triangle don't slip through the cracks between them.
+    const EPSILON: f64 = 1e-9;
+
+    let ray_dir = pt.coords;
+    if ray_dir.norm_squared() == 0.0 {
+        return None;
+    }
+
+    for root in ROOTS.iter() {
+        // See the diagram in `project` for what each of these vertices are,
+        // and how each triangle is parameterised.
+        let vertices = root_quad_vertices(*root);
+        let a = vertices[0];
+        let b = vertices[1];
+        let c = vertices[2];
+        let d = vertices[3];
+        let e = vertices[4];
+        let f = vertices[5];
+        let triangles = [
+            (a, b - a, c - a),
+            (d, c - d, b - d),
+            (c, d - c, e - c),
+            (f, e - f, d - f),
+        ];
+        for (triangle_index, &(tri_origin, edge_1, edge_2)) in triangles.iter().enumerate() {
+            // Möller–Trumbore intersection of the ray from the centre of the
+            // globe through `pt` with this triangle.
+            let p = ray_dir.cross(&edge_2);
+            let det = edge_1.dot(&p);
+            if det.abs() < EPSILON {
+                // Ray is parallel to the triangle.
+                continue;
+            }
+            let inv_det = 1.0 / det;
+            let to_ray_origin = -tri_origin.coords;
+            let u = to_ray_origin.dot(&p) * inv_det;
+            let q = to_ray_origin.cross(&edge_1);
+            let v = ray_dir.dot(&q) * inv_det;
+            let t = edge_2.dot(&q) * inv_det;
+            let is_in_triangle = t > 0.0 && u >= -EPSILON && v >= -EPSILON &&
+                u + v <= 1.0 + EPSILON;
+            if !is_in_triangle {
+                continue;
+            }
+
+            // Undo the parameterisation used for this triangle in `project`.
+            let (x, y) = match triangle_index {
+                0 => (u, v),
+                1 => (1.0 - u, 1.0 - v),
+                2 => (u, 1.0 + v),
+                _ => (1.0 - u, 2.0 - v),
+            };
+            // `project` works with y-values between 0 and 2.
+            return Some((*root, Pt2::new(x, y / 2.0)));
+        }
+    }
+
+    // Every direction passes through _some_ face of the icosahedron,
+    // so we should never get here.
+    None
+}
+
+// Find the vertices of the four triangles making up the given root quad,
+// in the order `[a, b, c, d, e, f]` as labelled in the diagram in `project`.
+fn root_quad_vertices(root: Root) -> [Pt3; 6] {
     // TODO: cache all this stuff somewhere. It's tiny, and we'll use it heaps.
     use self::icosahedron::{FACES, VERTICES};
     let triangle_indices = [
@@ -151,43 +294,7 @@ pub fn project(root: Root, mut pt_in_root_quad: Pt2) -> Pt3 {
         VERTICES[faces[3][0]][1],
         VERTICES[faces[3][0]][2],
     );
-
-    // Triangle 0
-    let ab = b - a;
-    let ac = c - a;
-    // Triangle 1
-    let db = b - d;
-    let dc = c - d;
-    // Triangle 2
-    let cd = d - c;
-    let ce = e - c;
-    // Triangle 3
-    let fd = d - f;
-    let fe = e - f;
-
-    // It'll be easier to do the math we need here if the positions
-    // lie between (0, 0) and (1, 2).
-    pt_in_root_quad[1] *= 2.0;
-
-    // Decide which triangle we're in.
-    let pos_on_icosahedron = if pt_in_root_quad[0] + pt_in_root_quad[1] < 1.0 {
-        // In triangle 0.
-        a + ab * pt_in_root_quad[0] + ac * pt_in_root_quad[1]
-    } else if pt_in_root_quad[1] < 1.0 {
-        // In triangle 1.
-        d + dc * (1.0 - pt_in_root_quad[0]) + db * (1.0 - pt_in_root_quad[1])
-    } else if pt_in_root_quad[0] + pt_in_root_quad[1] < 2.0 {
-        // In triangle 2.
-        // Bring the y-value back into [0, 1] so we can just repeat the math from above.
-        pt_in_root_quad[1] -= 1.0;
-        c + cd * pt_in_root_quad[0] + ce * pt_in_root_quad[1]
-    } else {
-        // In triangle 3.
-        // Bring the y-value back into [0, 1] so we can just repeat the math from above.
-        pt_in_root_quad[1] -= 1.0;
-        f + fe * (1.0 - pt_in_root_quad[0]) + fd * (1.0 - pt_in_root_quad[1])
-    };
-    Pt3::from_coordinates(pos_on_icosahedron.coords.normalize())
+    [a, b, c, d, e, f]
 }
 
 /// Calculate the origin of a chunk that contains the given `pos`,
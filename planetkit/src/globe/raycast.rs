@@ -0,0 +1,175 @@
+// Casting rays through the cells of a `Globe` in real space.
+//
+// Like `globe_ext`, this doesn't use any private details of `Globe`;
+// it's only exposed on its inherent impl for convenience.
+
+use types::*;
+use grid::{GridPoint3, PosInOwningRoot};
+use super::globe::Globe;
+
+// How many times to halve the interval between the last point known to be
+// inside a cell and the first point known to be outside it when working out
+// where exactly the ray leaves the cell. Any cell that the ray spends more than
+// `1 / 2^REFINEMENT_ITERATIONS` of a step inside will be found.
+const REFINEMENT_ITERATIONS: usize = 20;
+
+/// A solid cell struck by a ray cast through a `Globe`.
+#[derive(Clone, Copy, Debug)]
+pub struct RaycastHit {
+    /// The cell that was hit.
+    ///
+    /// This might not be expressed in its owning root.
+    pub pos: GridPoint3,
+    /// Unit vector pointing out of the face of the cell that the ray entered through,
+    /// expressed relative to the globe.
+    pub normal: Vec3,
+    /// Distance along the ray from its origin to where it entered the cell.
+    pub distance: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum RaycastResult {
+    /// The ray hit a solid cell.
+    Hit(RaycastHit),
+    /// The ray entered a cell in a chunk that isn't loaded before hitting
+    /// anything solid, so we can't tell what it would have hit beyond there.
+    Unloaded { pos: GridPoint3, distance: f64 },
+    /// Nothing solid was found within the maximum distance.
+    Miss,
+}
+
+impl Globe {
+    /// Walk the cells along a ray in real space, and find the first solid cell that it hits.
+    ///
+    /// `origin` is relative to the center of the globe, and `direction` need not be normalised.
+    ///
+    /// The ray is walked from cell to cell, by finding where it leaves each cell and then
+    /// which cell it enters next, so it doesn't skip cells where it only clips a corner.
+    /// If the ray passes below the floor radius of the globe, then it is considered to have missed.
+    pub fn raycast(&self, origin: Pt3, direction: Vec3, max_distance: f64) -> RaycastResult {
+        if direction.norm_squared() == 0.0 {
+            return RaycastResult::Miss;
+        }
+        let direction = direction.normalize();
+        let spec = self.spec();
+        let step = self.raycast_step_length();
+        let cell_at = |distance: f64| spec.approx_cell_containing_point(origin + direction * distance);
+
+        let mut pos = match cell_at(0.0) {
+            Some(pos) => pos,
+            // Below the floor of the globe; there's nothing to hit down there.
+            None => return RaycastResult::Miss,
+        };
+        let mut prev_pos: Option<GridPoint3> = None;
+        let mut entry_distance = 0.0;
+        loop {
+            let is_solid = match self.maybe_non_authoritative_cell(pos) {
+                Ok(cell) => cell.material.is_solid(),
+                Err(_) => {
+                    return RaycastResult::Unloaded {
+                        pos: pos,
+                        distance: entry_distance,
+                    }
+                }
+            };
+            if is_solid {
+                let normal = match prev_pos {
+                    Some(prev_pos) => self.face_normal_between(prev_pos, pos),
+                    // We started inside a solid cell; there's no face
+                    // to speak of, so just point back along the ray.
+                    None => -direction,
+                };
+                return RaycastResult::Hit(RaycastHit {
+                    pos: pos,
+                    normal: normal,
+                    distance: entry_distance,
+                });
+            }
+
+            // Step along the ray until we find a point outside this cell...
+            let mut inside_distance = entry_distance;
+            let outside_distance;
+            loop {
+                if inside_distance >= max_distance {
+                    return RaycastResult::Miss;
+                }
+                let distance = (inside_distance + step).min(max_distance);
+                let is_inside = cell_at(distance)
+                    .map(|sample_pos| self.is_same_cell(sample_pos, pos))
+                    .unwrap_or(false);
+                if is_inside {
+                    inside_distance = distance;
+                } else {
+                    outside_distance = distance;
+                    break;
+                }
+            }
+
+            // ...and then narrow down where exactly the ray left it. Whatever is
+            // just past there is the next cell, even if the ray only clips its corner
+            // on the way to wherever that outside point was.
+            entry_distance = self.refine_exit_distance(origin, direction, pos, inside_distance, outside_distance);
+            prev_pos = Some(pos);
+            pos = match cell_at(entry_distance) {
+                Some(pos) => pos,
+                None => return RaycastResult::Miss,
+            };
+        }
+    }
+
+    // Pick a distance between samples that should be small enough
+    // that the ray can't leave a cell and come back into it in one step.
+    fn raycast_step_length(&self) -> f64 {
+        let spec = self.spec();
+        spec.approx_min_cell_width().min(spec.block_height) / 4.0
+    }
+
+    fn is_same_cell(&self, a: GridPoint3, b: GridPoint3) -> bool {
+        let root_resolution = self.spec().root_resolution;
+        PosInOwningRoot::new(a, root_resolution) == PosInOwningRoot::new(b, root_resolution)
+    }
+
+    // Binary search between a distance along the ray known to be inside
+    // the given cell, and one known to be outside it. Returns the first distance
+    // found to be outside it.
+    fn refine_exit_distance(
+        &self,
+        origin: Pt3,
+        direction: Vec3,
+        pos: GridPoint3,
+        mut inside_distance: f64,
+        mut outside_distance: f64,
+    ) -> f64 {
+        let spec = self.spec();
+        for _ in 0..REFINEMENT_ITERATIONS {
+            let mid_distance = (inside_distance + outside_distance) / 2.0;
+            let is_inside = spec.approx_cell_containing_point(origin + direction * mid_distance)
+                .map(|mid_pos| self.is_same_cell(mid_pos, pos))
+                .unwrap_or(false);
+            if is_inside {
+                inside_distance = mid_distance;
+            } else {
+                outside_distance = mid_distance;
+            }
+        }
+        outside_distance
+    }
+
+    // Normal of the face of the cell at `pos` that faces the cell at `from_pos`.
+    fn face_normal_between(&self, from_pos: GridPoint3, pos: GridPoint3) -> Vec3 {
+        let spec = self.spec();
+        let center = spec.cell_center_center(pos);
+        let up = center.coords.normalize();
+        let is_same_column = self.is_same_cell(from_pos.with_z(0), pos.with_z(0));
+        if is_same_column {
+            // Came in through the top or bottom.
+            if from_pos.z > pos.z { up } else { -up }
+        } else {
+            // Came in through a side; take the direction to the cell
+            // we came from, and flatten it against the globe's surface.
+            let to_from_pos = spec.cell_center_center(from_pos) - center;
+            let sideways = to_from_pos - up * to_from_pos.dot(&up);
+            sideways.normalize()
+        }
+    }
+}
@@ -0,0 +1,100 @@
+// Finding cells of a `Globe` within regions defined in real space.
+//
+// Like `globe_ext`, this doesn't use any private details of `Globe`;
+// it's only exposed on its inherent impl for convenience.
+
+use types::*;
+use grid::{GridCoord, PosInOwningRoot, HexDisk};
+use super::globe::Globe;
+
+/// Cells found within a region of a `Globe`.
+///
+/// All cells are expressed in their owning root, and none appear twice.
+#[derive(Clone, Debug, Default)]
+pub struct CellsInRegion {
+    /// Cells in chunks that are currently loaded.
+    pub loaded: Vec<PosInOwningRoot>,
+    /// Cells in chunks that aren't loaded, so we don't know what's in them.
+    pub unloaded: Vec<PosInOwningRoot>,
+}
+
+impl Globe {
+    /// Find all cells whose centers lie within `radius` of `center`.
+    ///
+    /// `center` is relative to the center of the globe.
+    pub fn cells_in_sphere(&self, center: Pt3, radius: f64) -> CellsInRegion {
+        let radius_squared = radius * radius;
+        self.cells_in_region(center, radius, radius, |offset, _up| {
+            offset.norm_squared() <= radius_squared
+        })
+    }
+
+    /// Find all cells whose centers lie within a cylinder standing upright
+    /// on the surface of the globe, i.e., aligned with the line from the
+    /// center of the globe through `center`.
+    ///
+    /// The cylinder extends `half_height` above and below `center`.
+    pub fn cells_in_cylinder(&self, center: Pt3, radius: f64, half_height: f64) -> CellsInRegion {
+        let radius_squared = radius * radius;
+        self.cells_in_region(center, radius, half_height, |offset, up| {
+            let height = offset.dot(&up);
+            let sideways = offset - up * height;
+            height.abs() <= half_height && sideways.norm_squared() <= radius_squared
+        })
+    }
+
+    // Visit every cell that might be within the given horizontal and vertical
+    // distances of `center`, and keep those whose centers pass `is_inside`.
+    //
+    // `is_inside` is given the offset from `center` to the cell center,
+    // and the unit vector pointing up from the globe at `center`.
+    fn cells_in_region<F>(
+        &self,
+        center: Pt3,
+        horizontal_extent: f64,
+        vertical_extent: f64,
+        is_inside: F,
+    ) -> CellsInRegion
+    where
+        F: Fn(Vec3, Vec3) -> bool,
+    {
+        let mut cells = CellsInRegion::default();
+        let spec = self.spec();
+        let center_radius = center.coords.norm();
+        if center_radius == 0.0 {
+            // No idea which way is up.
+            return cells;
+        }
+        let up = center.coords / center_radius;
+
+        // Find the column containing the center, even if the center itself is
+        // below the floor of the globe; the region might still reach above it.
+        let column_radius = center_radius.max(spec.floor_radius + spec.block_height / 2.0);
+        let column_pos = match spec.approx_cell_containing_point(Pt3::from_coordinates(up * column_radius)) {
+            Some(pos) => pos,
+            None => return cells,
+        };
+
+        // Be generous; cells are only ever wider than the narrowest one,
+        // and we'll filter out anything that's too far away anyway.
+        let steps = (horizontal_extent / spec.approx_min_cell_width()).ceil() as GridCoord + 1;
+        let min_z = spec.approx_cell_z_from_radius(center_radius - vertical_extent) - 1;
+        let max_z = spec.approx_cell_z_from_radius(center_radius + vertical_extent) + 1;
+
+        let candidates = HexDisk::new_extruded(column_pos, steps, min_z..(max_z + 1), spec.root_resolution);
+        for pos in candidates {
+            let offset = spec.cell_center_center(pos) - center;
+            if !is_inside(offset, up) {
+                continue;
+            }
+            let pos = PosInOwningRoot::new(pos, spec.root_resolution);
+            let chunk_origin = self.origin_of_chunk_owning(pos);
+            if self.chunk_at(chunk_origin).is_some() {
+                cells.loaded.push(pos);
+            } else {
+                cells.unloaded.push(pos);
+            }
+        }
+        cells
+    }
+}
@@ -129,4 +129,67 @@ impl Spec {
     pub fn approx_cell_z_from_radius(&self, radius: f64) -> GridCoord {
         ((radius - self.floor_radius) / self.block_height) as GridCoord
     }
+
+    /// Distance between the centers of neighboring cells at the floor radius of the globe,
+    /// near the corners of root quads where cells are narrowest.
+    ///
+    /// Cells higher up, or elsewhere on the globe, are at least this wide.
+    pub fn approx_min_cell_width(&self) -> f64 {
+        let a = self.cell_center_on_unit_sphere(GridPoint2::new(0.into(), 1, 1));
+        let b = self.cell_center_on_unit_sphere(GridPoint2::new(0.into(), 2, 1));
+        (b - a).norm() * self.floor_radius
+    }
+
+    /// Find the cell containing a point in real space, expressed relative
+    /// to the center of the globe.
+    ///
+    /// This is only approximate near the boundaries between cells, because
+    /// cells are treated as regular hexagons in the space of each face of
+    /// the icosahedron rather than on the surface of the sphere.
+    ///
+    /// Returns `None` if the point lies below the floor radius of the globe.
+    pub fn approx_cell_containing_point(&self, pt: Pt3) -> Option<GridPoint3> {
+        let radius = pt.coords.norm();
+        if radius < self.floor_radius {
+            return None;
+        }
+        let (root, pt_in_root_quad) = match super::unproject(pt) {
+            Some(root_and_pt) => root_and_pt,
+            None => return None,
+        };
+        // Scale up into the same space as cell centers;
+        // see `cell_center_on_unit_sphere`.
+        let (x, y) = nearest_cell_center(
+            pt_in_root_quad.x * self.root_resolution[0] as f64,
+            pt_in_root_quad.y * self.root_resolution[1] as f64,
+        );
+        // Rounding may have nudged us just outside the root.
+        let x = x.max(0).min(self.root_resolution[0]);
+        let y = y.max(0).min(self.root_resolution[1]);
+        let z = self.approx_cell_z_from_radius(radius);
+        Some(GridPoint3::new(root, x, y, z))
+    }
+}
+
+// Round a position in the (x, y) plane of a root quad to the center of the
+// hexagonal cell containing it.
+//
+// Cell centers lie on a triangular lattice whose neighbors are at the offsets
+// listed in `cell_shape::NEIGHBOR_OFFSETS`, which are axial hex coordinates.
+// So we can round in cube coordinates `(x, y, -x - y)`, and then fix up
+// whichever coordinate was rounded the furthest.
+fn nearest_cell_center(x: f64, y: f64) -> (GridCoord, GridCoord) {
+    let z = -x - y;
+    let mut rx = x.round();
+    let mut ry = y.round();
+    let rz = z.round();
+    let dx = (rx - x).abs();
+    let dy = (ry - y).abs();
+    let dz = (rz - z).abs();
+    if dx > dy && dx > dz {
+        rx = -ry - rz;
+    } else if dy > dz {
+        ry = -rx - rz;
+    }
+    (rx as GridCoord, ry as GridCoord)
 }
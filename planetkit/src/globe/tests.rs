@@ -30,6 +30,411 @@ fn find_spawn_points() {
     assert!(successes < TRIALS - 5);
 }
 
+#[test]
+fn approx_cell_containing_point_finds_cell_centers() {
+    use grid::ROOTS;
+
+    let spec = Globe::new_example().spec();
+    let res = spec.root_resolution;
+    // Sample a spread of cells, including some on root edges and pentagons.
+    for root in ROOTS.iter() {
+        for x in (0..(res[0] + 1)).filter(|x| x % 7 == 0 || *x == res[0]) {
+            for y in (0..(res[1] + 1)).filter(|y| y % 13 == 0 || *y == res[1]) {
+                let pos = GridPoint3::new(*root, x, y, 3);
+                let found_pos = spec.approx_cell_containing_point(spec.cell_center_center(pos))
+                    .expect("Cell center should have been above the floor of the globe");
+                assert_eq!(
+                    PosInOwningRoot::new(found_pos, res),
+                    PosInOwningRoot::new(pos, res)
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn raycast_down_hits_top_of_land() {
+    use rand::{XorShiftRng, SeedableRng};
+
+    let mut globe = Globe::new_example();
+    let spec = globe.spec();
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let air_pos = globe
+        .air_above_random_surface_dry_land(
+            &mut rng,
+            2, // Min air cells above
+            5, // Max distance from starting point
+            5, // Max attempts
+        )
+        .expect("Should have been able to find some land");
+    let land_pos = air_pos.with_z(air_pos.z - 1);
+
+    // Start in the middle of the second cell of air above the land,
+    // and look straight down.
+    let origin = spec.cell_center_center(air_pos.with_z(air_pos.z + 1));
+    let up = origin.coords.normalize();
+    let hit = match globe.raycast(origin, -up, 10.0) {
+        RaycastResult::Hit(hit) => hit,
+        other => panic!("Expected to hit land, but got {:?}", other),
+    };
+    assert_eq!(
+        PosInOwningRoot::new(hit.pos, spec.root_resolution),
+        PosInOwningRoot::new(land_pos, spec.root_resolution)
+    );
+    // We should have come in through the top of the cell.
+    assert!(hit.normal.dot(&up) > 0.99);
+    assert!((hit.distance - spec.block_height * 1.5).abs() < 0.01);
+
+    // Looking up instead, there should be nothing to hit
+    // within the cells of air we know are there.
+    let origin = spec.cell_center_center(air_pos);
+    match globe.raycast(origin, up, spec.block_height) {
+        RaycastResult::Miss => (),
+        other => panic!("Expected to miss, but got {:?}", other),
+    }
+}
+
+#[test]
+fn raycast_finds_cells_that_rays_only_clip_the_corner_of() {
+    use rand::{Rng, XorShiftRng, SeedableRng};
+    use types::{Pt3, Vec3};
+    use grid::cell_shape;
+    use globe::chunk::Material;
+
+    // Hang a single block in the air, well clear of the ground.
+    let mut globe = Globe::new_example();
+    let spec = globe.spec();
+    let mut rng = XorShiftRng::from_seed([9, 10, 11, 12]);
+    let air_pos = globe
+        .air_above_random_surface_dry_land(
+            &mut rng,
+            8, // Min air cells above
+            5, // Max distance from starting point
+            5, // Max attempts
+        )
+        .expect("Should have been able to find some land");
+    let block_pos = air_pos.with_z(air_pos.z + 4);
+    let block_center = spec.cell_center_center(block_pos);
+    for cell_pos in globe.cells_in_sphere(block_center, 8.0).unloaded {
+        let chunk_origin = globe.origin_of_chunk_owning(cell_pos);
+        globe.ensure_chunk_present(chunk_origin);
+    }
+    let block_pos_in_owning_root = PosInOwningRoot::new(block_pos, spec.root_resolution);
+    let mut block = *globe.authoritative_cell(block_pos_in_owning_root);
+    block.material = Material::Dirt;
+    globe.set_authoritative_cell(block_pos_in_owning_root, block);
+
+    // Find the first solid cell along a ray the slow way, by sampling it very finely.
+    const SAMPLE_STEP: f64 = 0.001;
+    let first_solid_sampled = |origin: Pt3, direction: Vec3, max_distance: f64| -> Option<PosInOwningRoot> {
+        let samples = (max_distance / SAMPLE_STEP) as usize;
+        for i in 0..(samples + 1) {
+            let pos = match spec.approx_cell_containing_point(origin + direction * (i as f64 * SAMPLE_STEP)) {
+                Some(pos) => pos,
+                None => return None,
+            };
+            match globe.maybe_non_authoritative_cell(pos) {
+                Ok(cell) if cell.material.is_solid() => {
+                    return Some(PosInOwningRoot::new(pos, spec.root_resolution))
+                }
+                Ok(_) => (),
+                Err(_) => return None,
+            }
+        }
+        None
+    };
+
+    // Aim at points near the block's corners from a few cells away, and make sure
+    // that every ray found to touch the block by sampling also hits it in a raycast.
+    const MAX_DISTANCE: f64 = 6.0;
+    let offsets = cell_shape::FULL_HEX.top_outline_dir_offsets;
+    let mut rays_touching_block = 0;
+    for _ in 0..100 {
+        let offset = offsets[rng.gen_range(0, offsets.len())];
+        let corner = if rng.gen::<bool>() {
+            spec.cell_top_vertex(block_pos, offset)
+        } else {
+            spec.cell_bottom_vertex(block_pos, offset)
+        };
+        let target = corner + Vec3::new(
+            rng.gen_range(-0.05, 0.05),
+            rng.gen_range(-0.05, 0.05),
+            rng.gen_range(-0.05, 0.05),
+        );
+        let away = Vec3::new(
+            rng.gen_range(-1.0, 1.0),
+            rng.gen_range(-1.0, 1.0),
+            rng.gen_range(-1.0, 1.0),
+        );
+        if away.norm() < 0.1 {
+            continue;
+        }
+        let origin = target + away.normalize() * MAX_DISTANCE / 2.0;
+        let direction = (target - origin).normalize();
+
+        if first_solid_sampled(origin, direction, MAX_DISTANCE) != Some(block_pos_in_owning_root) {
+            continue;
+        }
+        rays_touching_block += 1;
+        match globe.raycast(origin, direction, MAX_DISTANCE) {
+            RaycastResult::Hit(hit) => {
+                assert_eq!(PosInOwningRoot::new(hit.pos, spec.root_resolution), block_pos_in_owning_root)
+            }
+            other => panic!("Expected to hit the block, but got {:?}", other),
+        }
+    }
+    // Make sure we actually tested something.
+    assert!(rays_touching_block > 10);
+}
+
+#[test]
+fn hidden_faces_are_culled_from_chunk_geometry() {
+    use rand::{XorShiftRng, SeedableRng};
+    use slog;
+    use render::Vertex;
+
+    let mut globe = Globe::new_example();
+    let spec = globe.spec();
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let air_pos = globe
+        .air_above_random_surface_dry_land(
+            &mut rng,
+            2, // Min air cells above
+            5, // Max distance from starting point
+            5, // Max attempts
+        )
+        .expect("Should have been able to find some land");
+    let land_pos = air_pos.with_z(air_pos.z - 1);
+    let land_chunk_origin = globe.origin_of_chunk_owning(PosInOwningRoot::new(land_pos, spec.root_resolution));
+    let air_chunk_origin = globe.origin_of_chunk_owning(PosInOwningRoot::new(air_pos, spec.root_resolution));
+    globe.ensure_chunk_present(land_chunk_origin);
+    globe.ensure_chunk_present(air_chunk_origin);
+
+    let log = slog::Logger::root(slog::Discard, o!());
+    let mut globe_view = View::new(spec, &log);
+    let triangle_count = |globe_view: &View| {
+        let mut vertex_data: Vec<Vertex> = Vec::new();
+        let mut index_data: Vec<u32> = Vec::new();
+        globe_view.make_chunk_geometry(&globe, land_chunk_origin, &mut vertex_data, &mut index_data);
+        index_data.len() / 3
+    };
+
+    let culled_triangles = triangle_count(&globe_view);
+    globe_view.set_cull_hidden_faces(false);
+    let all_triangles = triangle_count(&globe_view);
+
+    // We should still draw the surface, but a lot less than everything.
+    assert!(culled_triangles > 0);
+    assert!(culled_triangles < all_triangles);
+}
+
+#[test]
+fn chunk_geometry_has_normals_and_occlusion() {
+    use slog;
+    use render::Vertex;
+    use grid::cell_shape;
+    use globe::chunk::Material;
+
+    // Flatten the ground in and around a chunk, and then put
+    // a single block on top of it to make a step.
+    let mut globe = Globe::new_example();
+    let spec = globe.spec();
+    let res = spec.chunk_resolution;
+    let origin_at = |x, y| {
+        ChunkOrigin::new(GridPoint3::new(2.into(), x, y, res[2] * 16), spec.root_resolution, res)
+    };
+    let origin = origin_at(res[0], res[1]);
+    for dy in -1..2 {
+        for dx in -1..2 {
+            globe.ensure_chunk_present(origin_at(origin.pos().x + dx * res[0], origin.pos().y + dy * res[1]));
+        }
+    }
+    let ground_z = origin.pos().z + 1;
+    let set_material = |globe: &mut Globe, pos: GridPoint3, material: Material| {
+        let pos = PosInOwningRoot::new(pos, spec.root_resolution);
+        let mut cell = *globe.authoritative_cell(pos);
+        cell.material = material;
+        cell.fluid_level = 0;
+        globe.set_authoritative_cell(pos, cell);
+    };
+    for z in origin.pos().z..(origin.pos().z + res[2]) {
+        for y in (origin.pos().y - 1)..(origin.pos().y + res[1] + 2) {
+            for x in (origin.pos().x - 1)..(origin.pos().x + res[0] + 2) {
+                let material = if z <= ground_z { Material::Dirt } else { Material::Air };
+                set_material(&mut globe, GridPoint3::new(2.into(), x, y, z), material);
+            }
+        }
+    }
+    let step_base = GridPoint3::new(2.into(), origin.pos().x + 8, origin.pos().y + 8, ground_z);
+    set_material(&mut globe, step_base.with_z(ground_z + 1), Material::Dirt);
+
+    let log = slog::Logger::root(slog::Discard, o!());
+    let globe_view = View::new(spec, &log);
+    let mut vertex_data: Vec<Vertex> = Vec::new();
+    let mut index_data: Vec<u32> = Vec::new();
+    globe_view.make_chunk_geometry(&globe, origin, &mut vertex_data, &mut index_data);
+    assert!(!vertex_data.is_empty());
+
+    for vertex in &vertex_data {
+        assert_relative_eq!(vertex.normal().norm(), 1.0, epsilon = 1e-5);
+        assert!(vertex.occlusion() >= 0.0 && vertex.occlusion() <= 1.0);
+    }
+
+    // Geometry is relative to the chunk origin.
+    let chunk_origin_pos = spec.cell_bottom_center(*origin.pos());
+    let top_vertices_at = |grid_point: GridPoint3, offset: [i64; 2]| -> Vec<Vertex> {
+        let up = spec.cell_center_on_unit_sphere(grid_point.rxy).coords;
+        let corner = spec.cell_top_vertex(grid_point, offset) - chunk_origin_pos;
+        vertex_data
+            .iter()
+            .filter(|vertex| vertex.normal().dot(&up) > 0.5)
+            .filter(|vertex| (vertex.pos().coords - corner).norm() < 1e-3)
+            .cloned()
+            .collect()
+    };
+
+    // Away from the step, every cell sharing a corner should agree
+    // on its normal, and it should point straight up.
+    let flat_cell = step_base.with_x(step_base.x - 4);
+    let up = spec.cell_center_on_unit_sphere(flat_cell.rxy).coords;
+    for offset in cell_shape::FULL_HEX.top_outline_dir_offsets {
+        let vertices = top_vertices_at(flat_cell, *offset);
+        // Each corner of a hexagon is shared by three cells.
+        assert_eq!(vertices.len(), 3);
+        for vertex in &vertices {
+            assert!((vertex.normal() - vertices[0].normal()).norm() < 1e-5);
+            assert!(vertex.normal().dot(&up) > 0.999);
+            assert_eq!(vertex.occlusion(), 0.0);
+        }
+    }
+
+    // Where the ground meets the step, the corners should be darkened.
+    for offset in cell_shape::FULL_HEX.top_outline_dir_offsets {
+        let vertices = top_vertices_at(step_base, *offset);
+        // The step covers the top of the cell underneath it,
+        // so only the two cells beside it have a top face here.
+        assert_eq!(vertices.len(), 2);
+        for vertex in &vertices {
+            assert!((vertex.normal() - vertices[0].normal()).norm() < 1e-5);
+            assert!(vertex.occlusion() > 0.0);
+        }
+    }
+}
+
+#[test]
+fn loading_a_chunk_dirties_the_views_above_and_below_it() {
+    use super::globe::GlobeGuts;
+
+    let mut globe = Globe::new_example();
+    let spec = globe.spec();
+    let chunk_height = spec.chunk_resolution[2];
+    let origin_at_z = |z| ChunkOrigin::new(GridPoint3::new(3.into(), 0, 0, z), spec.root_resolution, spec.chunk_resolution);
+    let below = origin_at_z(chunk_height * 2);
+    let middle = origin_at_z(chunk_height * 3);
+    let above = origin_at_z(chunk_height * 4);
+    for &origin in &[below, above] {
+        globe.ensure_chunk_present(origin);
+        globe.chunks_mut().get_mut(&origin).unwrap().mark_view_as_clean();
+    }
+
+    globe.ensure_chunk_present(middle);
+
+    let is_view_dirty = |origin| globe.chunk_at(origin).unwrap().is_view_dirty;
+    assert!(is_view_dirty(below));
+    assert!(is_view_dirty(middle));
+    assert!(is_view_dirty(above));
+}
+
+#[test]
+fn export_whole_root() {
+    use slog;
+    use grid::ROOTS;
+    use render::{write_mesh, MeshFormat};
+
+    let mut globe = Globe::new_example();
+    ensure_surface_chunks_present(&mut globe, &ROOTS[0..1]);
+    let log = slog::Logger::root(slog::Discard, o!());
+    let mesh = make_loaded_chunks_mesh(&globe, &log).expect("Surface of a root should have something to see");
+
+    // Everything should be somewhere near the surface of the globe.
+    let spec = globe.spec();
+    for vertex in &mesh.vertexes {
+        let radius = vertex.pos().coords.norm();
+        assert!(radius > spec.floor_radius);
+        assert!(radius < spec.ocean_radius + (spec.ocean_radius - spec.floor_radius));
+    }
+
+    let mut out: Vec<u8> = Vec::new();
+    write_mesh(&mesh, MeshFormat::Obj, &mut out).unwrap();
+    assert!(!out.is_empty());
+}
+
+#[test]
+fn raycast_reports_unloaded_chunks() {
+    // No chunks are loaded in a fresh globe.
+    let globe = Globe::new_example();
+    let spec = globe.spec();
+    let origin = spec.cell_center_center(GridPoint3::new(2.into(), 10, 20, 70));
+    match globe.raycast(origin, -origin.coords, 10.0) {
+        RaycastResult::Unloaded { distance, .. } => assert_eq!(distance, 0.0),
+        other => panic!("Expected to find unloaded chunk, but got {:?}", other),
+    }
+}
+
+#[test]
+fn sphere_query_finds_cells_within_radius() {
+    use std::collections::HashSet;
+
+    let mut globe = Globe::new_example();
+    let spec = globe.spec();
+    let center_pos = GridPoint3::new(1.into(), 20, 30, 60);
+    let center = spec.cell_center_center(center_pos);
+    let radius = 2.5;
+
+    // Nothing is loaded yet.
+    let cells = globe.cells_in_sphere(center, radius);
+    assert!(cells.loaded.is_empty());
+    assert!(!cells.unloaded.is_empty());
+
+    // Load everything we found, and then we should get the same cells back as loaded.
+    for pos in &cells.unloaded {
+        let chunk_origin = globe.origin_of_chunk_owning(*pos);
+        globe.ensure_chunk_present(chunk_origin);
+    }
+    let loaded_cells = globe.cells_in_sphere(center, radius);
+    assert!(loaded_cells.unloaded.is_empty());
+    assert_eq!(loaded_cells.loaded.len(), cells.unloaded.len());
+
+    let unique: HashSet<PosInOwningRoot> = loaded_cells.loaded.iter().cloned().collect();
+    assert_eq!(unique.len(), loaded_cells.loaded.len());
+    assert!(unique.contains(&PosInOwningRoot::new(center_pos, spec.root_resolution)));
+    for pos in &loaded_cells.loaded {
+        let distance = (spec.cell_center_center((*pos).into()) - center).norm();
+        assert!(distance <= radius);
+    }
+    // Cells just above and below should be in there; the column
+    // is much taller than one block height.
+    for dz in &[-2, 2] {
+        let pos = center_pos.with_z(center_pos.z + dz);
+        assert!(unique.contains(&PosInOwningRoot::new(pos, spec.root_resolution)));
+    }
+}
+
+#[test]
+fn cylinder_query_is_taller_than_sphere() {
+    let globe = Globe::new_example();
+    let spec = globe.spec();
+    let center = spec.cell_center_center(GridPoint3::new(3.into(), 40, 70, 60));
+    let sphere = globe.cells_in_sphere(center, 2.0);
+    let cylinder = globe.cells_in_cylinder(center, 2.0, 4.0);
+    assert!(cylinder.unloaded.len() > sphere.unloaded.len());
+    let up = center.coords.normalize();
+    for pos in &cylinder.unloaded {
+        let offset = spec.cell_center_center((*pos).into()) - center;
+        assert!(offset.dot(&up).abs() <= 4.0);
+    }
+}
+
 #[cfg(feature = "nightly")]
 pub mod benches {
     use test::Bencher;
@@ -111,3 +516,86 @@ pub mod benches {
         });
     }
 }
+
+#[test]
+fn cell_changes_are_logged_on_request_until_drained() {
+    use rand::{XorShiftRng, SeedableRng};
+    use globe::chunk::Material;
+
+    let mut globe = Globe::new_example();
+    let spec = globe.spec();
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+    let air_pos = globe
+        .air_above_random_surface_dry_land(
+            &mut rng,
+            2, // Min air cells above
+            5, // Max distance from starting point
+            5, // Max attempts
+        )
+        .expect("Should have been able to find some land");
+    let air_pos_in_owning_root = PosInOwningRoot::new(air_pos, spec.root_resolution);
+    let air = *globe.authoritative_cell(air_pos_in_owning_root);
+    let mut dirt = air;
+    dirt.material = Material::Dirt;
+
+    // Nothing gets logged until something asks for it.
+    globe.set_authoritative_cell(air_pos_in_owning_root, dirt);
+    globe.set_authoritative_cell(air_pos_in_owning_root, air);
+    assert_eq!(globe.drain_cell_changes().count(), 0);
+
+    globe.set_logging_cell_changes(true);
+    globe.set_authoritative_cell(air_pos_in_owning_root, dirt);
+
+    let changes: Vec<CellChange> = globe.drain_cell_changes().collect();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].pos, *air_pos_in_owning_root.pos());
+    assert_eq!(changes[0].old_cell.material, Material::Air);
+    assert_eq!(changes[0].new_cell.material, Material::Dirt);
+
+    // Nothing new since then.
+    assert_eq!(globe.drain_cell_changes().count(), 0);
+}
+
+#[test]
+fn only_solid_materials_can_be_dug() {
+    use globe::chunk::Material;
+
+    for material in &[Material::Air, Material::Dirt, Material::Water, Material::Sand, Material::Gravel] {
+        assert_eq!(material.hardness().is_some(), material.is_solid());
+    }
+    assert!(Material::Sand.hardness() < Material::Dirt.hardness());
+    assert!(Material::Dirt.hardness() < Material::Gravel.hardness());
+}
+
+#[test]
+fn sand_and_gravel_are_generated_near_sea_level() {
+    use rand::{XorShiftRng, SeedableRng};
+    use grid::random_column;
+    use globe::chunk::Material;
+
+    let globe = Globe::new_example();
+    let spec = globe.spec();
+    let mut rng = XorShiftRng::from_seed([9, 10, 11, 12]);
+    let mut top_materials = Vec::new();
+    for _ in 0..2000 {
+        let column = random_column(spec.root_resolution, &mut rng);
+        let mut z = 0;
+        while globe.gen.cell_at(column.with_z(z + 1)).material.is_solid() {
+            z += 1;
+        }
+        let top_material = globe.gen.cell_at(column.with_z(z)).material;
+        let land_height = globe.gen.land_height(column);
+        let blocks_above_sea_level = (land_height - spec.ocean_radius) / spec.block_height;
+        match top_material {
+            Material::Sand => assert!(blocks_above_sea_level > -3.0 && blocks_above_sea_level < 2.0),
+            Material::Gravel => assert!(blocks_above_sea_level < 0.0),
+            _ => (),
+        }
+        if !top_materials.contains(&top_material) {
+            top_materials.push(top_material);
+        }
+    }
+    assert!(top_materials.contains(&Material::Dirt));
+    assert!(top_materials.contains(&Material::Sand));
+    assert!(top_materials.contains(&Material::Gravel));
+}
@@ -1,11 +1,13 @@
+use std::collections::HashMap;
+
 use slog::Logger;
 
-use grid::GridPoint3;
+use grid::{GridCoord, GridPoint3};
 use grid::cell_shape;
 use super::spec::Spec;
-use super::{Globe, Cursor, ChunkOrigin};
+use super::{Globe, Cursor, ChunkOrigin, LodPatchId};
 use super::chunk::Material;
-use types::Pt3;
+use types::{Pt3, Vec3};
 use render;
 
 // TODO: between this and "draw" we now have some confusing names.
@@ -25,6 +27,7 @@ use render;
 pub struct View {
     spec: Spec,
     log: Logger,
+    cull_hidden_faces: bool,
 }
 
 impl View {
@@ -32,9 +35,17 @@ impl View {
         View {
             spec: globe_spec,
             log: parent_log.new(o!()),
+            cull_hidden_faces: true,
         }
     }
 
+    /// Whether to leave out faces of cells that are hidden behind other cells.
+    ///
+    /// This is on by default; turning it off is mostly useful for debugging.
+    pub fn set_cull_hidden_faces(&mut self, cull_hidden_faces: bool) {
+        self.cull_hidden_faces = cull_hidden_faces;
+    }
+
     /// Creates chunk geometry with vertex positions specified
     /// relative to the bottom-middle of the chunk origin cell.
 
@@ -67,6 +78,9 @@ impl View {
         let chunk_origin_pos = self.spec.cell_bottom_center(*origin.pos());
 
         let mut cursor = Cursor::new_in_chunk(globe, origin);
+        let mut neighbor_cursor = Cursor::new_in_chunk(globe, origin);
+        // Cells near the edges get asked about by several of their neighbors.
+        let mut surface_normals: HashMap<GridPoint3, Vec3> = HashMap::new();
 
         // Include cells _on_ the far edge of the chunk;
         // even though we don't own them we'll need to draw part of them.
@@ -87,7 +101,7 @@ impl View {
                         continue;
                     }
 
-                    let mut cell_color = {
+                    let cell_color = {
                         // Eww... can I please have non-lexical borrow scopes? :)
                         let cell = cursor.cell().expect("We shouldn't be trying to build geometry for a chunk that isn't loaded.");
 
@@ -99,6 +113,12 @@ impl View {
                         } else if cell.material == Material::Water {
                             // Ocean blue
                             [0.0, 0.1, 0.7]
+                        } else if cell.material == Material::Sand {
+                            // Beachy yellow
+                            [0.8, 0.7, 0.4]
+                        } else if cell.material == Material::Gravel {
+                            // Stony grey
+                            [0.45, 0.45, 0.4]
                         } else {
                             // Don't draw air or anything else we don't understand.
                             continue;
@@ -110,10 +130,6 @@ impl View {
                         inner_cell_color
                     };
 
-                    // TODO: use functions that return just the bit they care
-                    // about and... maths. This is silly.
-                    let first_top_vertex_index = vertex_data.len() as u32;
-
                     // TODO: don't switch; split all this out into calls
                     // over different ranges of cells.
                     //
@@ -137,73 +153,318 @@ impl View {
                     } else {
                         cell_shape::FULL_HEX
                     };
-
-                    // Emit each top vertex of whatever shape we're using for this cell.
                     let offsets = &cell_shape.top_outline_dir_offsets;
-                    for offset in offsets.iter() {
-                        let vertex_pt3 = Pt3::from_coordinates(
-                            self.spec.cell_top_vertex(grid_point, *offset) -
-                                chunk_origin_pos,
-                        );
-                        vertex_data.push(render::Vertex::new_from_pt3(vertex_pt3, cell_color));
-                    }
 
-                    // Emit triangles for the top of the cell. All triangles
-                    // will contain the first vertex, plus two others.
-                    for i in 1..(offsets.len() as u32 - 1) {
-                        index_data.extend_from_slice(
-                            &[
-                                first_top_vertex_index,
-                                first_top_vertex_index + i,
-                                first_top_vertex_index + i + 1,
-                            ],
-                        );
-                    }
+                    // Figure out which faces can actually be seen.
+                    let is_top_visible = !self.cull_hidden_faces ||
+                        self.is_face_visible(&mut neighbor_cursor, Some(grid_point.with_z(cell_z + 1)));
+                    let is_bottom_visible = self.cull_hidden_faces && cell_z > 0 &&
+                        self.is_face_visible(&mut neighbor_cursor, Some(grid_point.with_z(cell_z - 1)));
+                    let visible_sides: Vec<bool> = (0..offsets.len())
+                        .map(|ab_i| {
+                            if !self.cull_hidden_faces {
+                                return true;
+                            }
+                            let a = offsets[ab_i];
+                            let b = offsets[(ab_i + 1) % offsets.len()];
+                            match cell_shape::neighbor_offset_across_side(a, b) {
+                                Some(neighbor_offset) => {
+                                    let neighbor_pos = self.neighbor_in_same_root(grid_point, neighbor_offset);
+                                    self.is_face_visible(&mut neighbor_cursor, neighbor_pos)
+                                }
+                                // This side cuts through the middle of the cell where it's
+                                // split between chunks; the other chunk draws the rest.
+                                None => false,
+                            }
+                        })
+                        .collect();
 
-                    // Emit each top vertex of whatever shape we're using for this cell
-                    // AGAIN for the top of the sides, so they can have a different colour.
-                    // Darken the top of the sides slightly to fake lighting.
-                    for color_channel in &mut cell_color {
-                        *color_channel *= 0.9;
-                    }
-                    let first_side_top_vertex_index = first_top_vertex_index + offsets.len() as u32;
-                    for offset in offsets.iter() {
-                        let vertex_pt3 = Pt3::from_coordinates(
-                            self.spec.cell_top_vertex(grid_point, *offset) -
-                                chunk_origin_pos,
-                        );
-                        vertex_data.push(render::Vertex::new_from_pt3(vertex_pt3, cell_color));
-                    }
+                    let up = self.spec.cell_center_on_unit_sphere(grid_point.rxy).coords;
 
-                    // Emit each bottom vertex of whatever shape we're using for this cell.
-                    // Darken the bottom of the sides substantially to fake lighting.
-                    for color_channel in &mut cell_color {
-                        *color_channel *= 0.5;
-                    }
-                    let first_side_bottom_vertex_index = first_side_top_vertex_index +
-                        offsets.len() as u32;
-                    for offset in offsets.iter() {
-                        let vertex_pt3 = Pt3::from_coordinates(
-                            self.spec.cell_bottom_vertex(grid_point, *offset) -
-                                chunk_origin_pos,
-                        );
-                        vertex_data.push(render::Vertex::new_from_pt3(vertex_pt3, cell_color));
+                    // Each visible face gets its own vertices; we don't merge coplanar
+                    // faces of neighboring cells. Every cell is tinted by its own random
+                    // shade, so even neighbors of the same material with matching normals
+                    // and occlusion would almost never have the same color. And the outline
+                    // of a run of hexagons isn't convex, so it couldn't be drawn as a fan
+                    // like a single top face is.
+                    if is_top_visible {
+                        // Emit each top vertex of whatever shape we're using for this cell,
+                        // with normals smoothed across the surrounding terrain.
+                        let first_top_vertex_index = vertex_data.len() as u32;
+                        for offset in offsets.iter() {
+                            let vertex_pt3 = Pt3::from_coordinates(
+                                self.spec.cell_top_vertex(grid_point, *offset) -
+                                    chunk_origin_pos,
+                            );
+                            let normal = self.top_vertex_normal(&mut neighbor_cursor, &mut surface_normals, grid_point, *offset);
+                            let occlusion = self.top_vertex_occlusion(&mut neighbor_cursor, grid_point, *offset);
+                            vertex_data.push(
+                                render::Vertex::new_from_pt3(vertex_pt3, cell_color)
+                                    .with_normal(normal)
+                                    .with_occlusion(occlusion),
+                            );
+                        }
+
+                        // Emit triangles for the top of the cell. All triangles
+                        // will contain the first vertex, plus two others.
+                        for i in 1..(offsets.len() as u32 - 1) {
+                            index_data.extend_from_slice(
+                                &[
+                                    first_top_vertex_index,
+                                    first_top_vertex_index + i,
+                                    first_top_vertex_index + i + 1,
+                                ],
+                            );
+                        }
                     }
 
-                    // Emit triangles for the cell sides.
-                    for ab_i in 0..(offsets.len() as u32) {
-                        let cd_i = (ab_i + 1) % offsets.len() as u32;
-                        let a_i = first_side_top_vertex_index + ab_i;
-                        let b_i = first_side_bottom_vertex_index + ab_i;
-                        let c_i = first_side_bottom_vertex_index + cd_i;
-                        let d_i = first_side_top_vertex_index + cd_i;
+                    // Emit each side that can be seen as its own quad,
+                    // so that it can have a flat normal facing outward.
+                    for ab_i in 0..offsets.len() {
+                        if !visible_sides[ab_i] {
+                            continue;
+                        }
+                        let a = offsets[ab_i];
+                        let b = offsets[(ab_i + 1) % offsets.len()];
+                        let top_a = self.spec.cell_top_vertex(grid_point, a);
+                        let top_b = self.spec.cell_top_vertex(grid_point, b);
+                        let bottom_a = self.spec.cell_bottom_vertex(grid_point, a);
+                        let bottom_b = self.spec.cell_bottom_vertex(grid_point, b);
+                        // Outlines run counterclockwise when seen from above.
+                        let normal = (top_b - top_a).cross(&up).normalize();
+                        // Darken the bottom of sides where they meet the ground.
+                        let bottom_occlusion = cell_shape::neighbor_offset_across_side(a, b)
+                            .and_then(|neighbor_offset| self.neighbor_in_same_root(grid_point, neighbor_offset))
+                            .map(|neighbor_pos| {
+                                if self.is_solid(&mut neighbor_cursor, Some(neighbor_pos.with_z(cell_z - 1))) {
+                                    0.5
+                                } else {
+                                    0.0
+                                }
+                            })
+                            .unwrap_or(0.0);
+
+                        let first_side_vertex_index = vertex_data.len() as u32;
+                        for &(corner, occlusion) in &[(top_a, 0.0), (bottom_a, bottom_occlusion), (bottom_b, bottom_occlusion), (top_b, 0.0)] {
+                            let vertex_pt3 = Pt3::from_coordinates(corner - chunk_origin_pos);
+                            vertex_data.push(
+                                render::Vertex::new_from_pt3(vertex_pt3, cell_color)
+                                    .with_normal(normal)
+                                    .with_occlusion(occlusion),
+                            );
+                        }
+                        let a_i = first_side_vertex_index;
+                        let b_i = a_i + 1;
+                        let c_i = a_i + 2;
+                        let d_i = a_i + 3;
                         index_data.extend_from_slice(&[a_i, b_i, d_i, d_i, b_i, c_i]);
                     }
+
+                    // Emit the bottom of the cell, winding
+                    // the other way so it faces down.
+                    if is_bottom_visible {
+                        let first_bottom_vertex_index = vertex_data.len() as u32;
+                        for offset in offsets.iter() {
+                            let vertex_pt3 = Pt3::from_coordinates(
+                                self.spec.cell_bottom_vertex(grid_point, *offset) -
+                                    chunk_origin_pos,
+                            );
+                            vertex_data.push(render::Vertex::new_from_pt3(vertex_pt3, cell_color).with_normal(-up));
+                        }
+                        for i in 1..(offsets.len() as u32 - 1) {
+                            index_data.extend_from_slice(
+                                &[
+                                    first_bottom_vertex_index,
+                                    first_bottom_vertex_index + i + 1,
+                                    first_bottom_vertex_index + i,
+                                ],
+                            );
+                        }
+                    }
                 }
             }
         }
     }
 
+    // Average the surface normals of this cell and any neighbors sharing the vertex
+    // whose tops are level with this one, so that lighting is smooth across gentle
+    // slopes but still shows up the edges of cliffs.
+    fn top_vertex_normal(
+        &self,
+        neighbor_cursor: &mut Cursor,
+        surface_normals: &mut HashMap<GridPoint3, Vec3>,
+        grid_point: GridPoint3,
+        offset: [i64; 2],
+    ) -> Vec3 {
+        let mut normal = self.surface_normal(neighbor_cursor, surface_normals, grid_point);
+        for neighbor_offset in cell_shape::neighbor_offsets_touching(offset) {
+            let neighbor_pos = match self.neighbor_in_same_root(grid_point, neighbor_offset) {
+                Some(neighbor_pos) => neighbor_pos,
+                None => continue,
+            };
+            let is_level = self.is_solid(neighbor_cursor, Some(neighbor_pos)) &&
+                !self.is_solid(neighbor_cursor, Some(neighbor_pos.with_z(neighbor_pos.z + 1)));
+            if is_level {
+                normal += self.surface_normal(neighbor_cursor, surface_normals, neighbor_pos);
+            }
+        }
+        normal.normalize()
+    }
+
+    // Estimate which way the surface faces at the top of the given cell,
+    // from how much higher or lower the terrain is in each neighboring column.
+    fn surface_normal(
+        &self,
+        neighbor_cursor: &mut Cursor,
+        surface_normals: &mut HashMap<GridPoint3, Vec3>,
+        grid_point: GridPoint3,
+    ) -> Vec3 {
+        if let Some(normal) = surface_normals.get(&grid_point) {
+            return *normal;
+        }
+
+        let up = self.spec.cell_center_on_unit_sphere(grid_point.rxy).coords;
+        let center = self.spec.cell_top_vertex(grid_point, [0, 0]);
+        let mut gradient = Vec3::new(0.0, 0.0, 0.0);
+        for neighbor_offset in cell_shape::NEIGHBOR_OFFSETS.iter() {
+            let neighbor_pos = match self.neighbor_in_same_root(grid_point, *neighbor_offset) {
+                Some(neighbor_pos) => neighbor_pos,
+                None => continue,
+            };
+            let height_difference = if self.is_solid(neighbor_cursor, Some(neighbor_pos.with_z(grid_point.z + 1))) {
+                1.0
+            } else if self.is_solid(neighbor_cursor, Some(neighbor_pos)) {
+                0.0
+            } else {
+                -1.0
+            };
+            let toward_neighbor = self.spec.cell_top_vertex(neighbor_pos, [0, 0]) - center;
+            let toward_neighbor = toward_neighbor - up * toward_neighbor.dot(&up);
+            let distance = toward_neighbor.norm();
+            gradient += toward_neighbor / distance * (height_difference * self.spec.block_height / distance);
+        }
+        // Six evenly spread neighbors each contribute a
+        // third of the slope in their direction.
+        let normal = (up - gradient / 3.0).normalize();
+        surface_normals.insert(grid_point, normal);
+        normal
+    }
+
+    // Darken vertices in corners, where they're hemmed in by
+    // neighboring cells rising above this one.
+    fn top_vertex_occlusion(&self, neighbor_cursor: &mut Cursor, grid_point: GridPoint3, offset: [i64; 2]) -> f32 {
+        let walls = cell_shape::neighbor_offsets_touching(offset)
+            .into_iter()
+            .filter_map(|neighbor_offset| self.neighbor_in_same_root(grid_point, neighbor_offset))
+            .filter(|neighbor_pos| self.is_solid(neighbor_cursor, Some(neighbor_pos.with_z(grid_point.z + 1))))
+            .count();
+        // A vertex is touched by at most two neighbors.
+        walls as f32 / 2.0
+    }
+
+    // Whether the cell at the given position is known to be solid.
+    fn is_solid(&self, neighbor_cursor: &mut Cursor, pos: Option<GridPoint3>) -> bool {
+        let pos = match pos {
+            // There's nothing below the bottom of the globe.
+            Some(pos) if pos.z >= 0 => pos,
+            _ => return false,
+        };
+        neighbor_cursor.set_pos(pos);
+        neighbor_cursor
+            .cell()
+            .map(|cell| cell.material.is_solid())
+            .unwrap_or(false)
+    }
+
+    /// Creates a coarse heightmap mesh for a patch of the globe's terrain,
+    /// straight from its generator, with vertex positions specified relative
+    /// to the north corner of the patch at sea level.
+    ///
+    /// This doesn't look at any chunks, so it shows what the terrain was
+    /// generated as rather than any changes made to it since.
+    pub fn make_lod_patch_geometry(
+        &self,
+        globe: &Globe,
+        patch: LodPatchId,
+        vertex_data: &mut Vec<render::Vertex>,
+        index_data: &mut Vec<u32>,
+    ) {
+        trace!(self.log, "Building LOD patch geometry"; "patch" => format!("{:?}", patch));
+
+        let origin = patch.origin(&self.spec);
+        let end = patch.end(&self.spec);
+        let patch_origin_pos = self.spec.cell_center_on_unit_sphere(origin) * self.spec.ocean_radius;
+
+        // Coarser patches cover more cells with the same number of samples.
+        let step_x = ((end.x - origin.x) / LOD_PATCH_SAMPLES_PER_SIDE).max(1);
+        let step_y = ((end.y - origin.y) / LOD_PATCH_SAMPLES_PER_SIDE).max(1);
+        let xs = sample_coords(origin.x, end.x, step_x);
+        let ys = sample_coords(origin.y, end.y, step_y);
+
+        let first_vertex_index = vertex_data.len() as u32;
+        for y in &ys {
+            for x in &xs {
+                let column = origin.with_x(*x).with_y(*y);
+                let land_height = globe.gen.land_height(column);
+                // Match the colors used for full-resolution chunks.
+                let (radius, color) = if land_height > self.spec.ocean_radius {
+                    (land_height, [0.0, 0.4, 0.0])
+                } else {
+                    (self.spec.ocean_radius, [0.0, 0.1, 0.7])
+                };
+                // Sink the patch a little below the real terrain so that it doesn't
+                // poke through any neighboring chunks that are drawn in full.
+                let radius = radius - self.spec.block_height;
+                let up = self.spec.cell_center_on_unit_sphere(column);
+                let vertex_pt3 = Pt3::from_coordinates(up * radius - patch_origin_pos);
+                // It's too coarse for slopes to be worth lighting.
+                vertex_data.push(render::Vertex::new_from_pt3(vertex_pt3, color).with_normal(up.coords));
+            }
+        }
+
+        // Emit two triangles for each square of samples,
+        // wound the same way as the tops of cells.
+        let row_len = xs.len() as u32;
+        for j in 0..(ys.len() as u32 - 1) {
+            for i in 0..(row_len - 1) {
+                let a = first_vertex_index + j * row_len + i;
+                let b = a + 1;
+                let c = a + row_len;
+                let d = c + 1;
+                index_data.extend_from_slice(&[a, b, c, b, d, c]);
+            }
+        }
+    }
+
+    // Whether the face of a cell bordering the cell at `neighbor_pos` can be seen.
+    // If we don't know what's there, then assume it's hidden; the face will
+    // get drawn when this chunk's geometry is rebuilt after that one loads.
+    // (`Globe::add_chunk` marks the views of the chunks above and below as dirty.)
+    fn is_face_visible(&self, neighbor_cursor: &mut Cursor, neighbor_pos: Option<GridPoint3>) -> bool {
+        let neighbor_pos = match neighbor_pos {
+            Some(neighbor_pos) => neighbor_pos,
+            None => return false,
+        };
+        neighbor_cursor.set_pos(neighbor_pos);
+        neighbor_cursor
+            .cell()
+            .map(|neighbor| is_transparent(neighbor.material))
+            .unwrap_or(false)
+    }
+
+    // The neighbor across a side of a cell we're drawing never lies outside the
+    // root that we're drawing it in, because cells on the edge of a root are
+    // only drawn in part. But just in case, don't go looking outside the root.
+    fn neighbor_in_same_root(&self, pos: GridPoint3, offset: (GridCoord, GridCoord)) -> Option<GridPoint3> {
+        let x = pos.x + offset.0;
+        let y = pos.y + offset.1;
+        let resolution = self.spec.root_resolution;
+        if x < 0 || y < 0 || x > resolution[0] || y > resolution[1] {
+            return None;
+        }
+        Some(pos.with_x(x).with_y(y))
+    }
+
     fn cull_cell(&self, cursor: &Cursor) -> bool {
         use grid::Neighbors;
 
@@ -230,3 +491,26 @@ impl View {
         true
     }
 }
+
+// How many squares of terrain to draw along each side of an LOD patch,
+// regardless of how much of the globe it covers.
+const LOD_PATCH_SAMPLES_PER_SIDE: GridCoord = 8;
+
+// Evenly spaced coordinates from `start` to `end`, always including both.
+fn sample_coords(start: GridCoord, end: GridCoord, step: GridCoord) -> Vec<GridCoord> {
+    let mut coords = Vec::new();
+    let mut coord = start;
+    while coord < end {
+        coords.push(coord);
+        coord += step;
+    }
+    coords.push(end);
+    coords
+}
+
+// Whether cells behind a cell of this material can be seen through it.
+//
+// TODO: water should be see-through, too, once we can draw it that way.
+fn is_transparent(material: Material) -> bool {
+    material == Material::Air
+}
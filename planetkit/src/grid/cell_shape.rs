@@ -236,3 +236,74 @@ pub const SOUTH_EAST_PORTION: CellShape = CellShape {
 //              ↓
 pub static NEIGHBOR_OFFSETS: [(GridCoord, GridCoord); 6] =
     [(1, 0), (0, 1), (-1, 1), (-1, 0), (0, -1), (1, -1)];
+
+/// Finds the offset to the neighbouring cell on the other side of the
+/// side of a cell running from `a` to `b`, where `a` and `b` are taken from
+/// `DIR_OFFSETS` (or are the cell's center).
+///
+/// Returns `None` if the side doesn't lie along the outside of the hexagon,
+/// i.e. it cuts through the cell where it is split between chunks.
+pub fn neighbor_offset_across_side(a: [i64; 2], b: [i64; 2]) -> Option<(GridCoord, GridCoord)> {
+    for edge_i in 0..6 {
+        // Each side lies along an edge, from a vertex or the
+        // middle of the edge to another point on that same edge.
+        let middle = edge_i * 2;
+        let along_edge = [
+            DIR_OFFSETS[(middle + 11) % 12],
+            DIR_OFFSETS[middle],
+            DIR_OFFSETS[(middle + 1) % 12],
+        ];
+        if a != b && along_edge.contains(&a) && along_edge.contains(&b) {
+            return Some(NEIGHBOR_OFFSETS[edge_i]);
+        }
+    }
+    None
+}
+
+/// Finds the offsets to all neighbouring cells that share the given point
+/// on the outline of a cell, where the point is taken from `DIR_OFFSETS`.
+///
+/// Vertices are shared with two neighbours, and the middles of edges with one.
+/// Any other point (e.g. the cell's center) isn't shared with any neighbours.
+pub fn neighbor_offsets_touching(point: [i64; 2]) -> Vec<(GridCoord, GridCoord)> {
+    match DIR_OFFSETS.iter().position(|dir_offset| *dir_offset == point) {
+        Some(dir_i) if dir_i % 2 == 0 => vec![NEIGHBOR_OFFSETS[dir_i / 2]],
+        Some(dir_i) => vec![NEIGHBOR_OFFSETS[dir_i / 2], NEIGHBOR_OFFSETS[(dir_i / 2 + 1) % 6]],
+        None => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_hex_sides_all_have_neighbors() {
+        let offsets = FULL_HEX.top_outline_dir_offsets;
+        for i in 0..offsets.len() {
+            let a = offsets[i];
+            let b = offsets[(i + 1) % offsets.len()];
+            let neighbor_offset = neighbor_offset_across_side(a, b).expect("Every side of a full hex should face a neighbor");
+            // The neighbor is in the direction of the middle of the side.
+            assert_eq!(
+                (a[0] + b[0], a[1] + b[1]),
+                (neighbor_offset.0 * 6, neighbor_offset.1 * 6)
+            );
+        }
+    }
+
+    #[test]
+    fn cut_sides_have_no_neighbors() {
+        assert_eq!(neighbor_offset_across_side([0, 0], DIR_OFFSETS[0]), None);
+        assert_eq!(neighbor_offset_across_side(DIR_OFFSETS[6], DIR_OFFSETS[0]), None);
+        assert_eq!(neighbor_offset_across_side(DIR_OFFSETS[0], DIR_OFFSETS[1]), Some((1, 0)));
+    }
+
+    #[test]
+    fn vertices_are_shared_by_three_cells() {
+        assert!(neighbor_offsets_touching([0, 0]).is_empty());
+        assert_eq!(neighbor_offsets_touching(DIR_OFFSETS[2]), vec![(0, 1)]);
+        assert_eq!(neighbor_offsets_touching(DIR_OFFSETS[1]), vec![(1, 0), (0, 1)]);
+        assert_eq!(neighbor_offsets_touching(DIR_OFFSETS[11]), vec![(1, -1), (1, 0)]);
+    }
+}
@@ -7,6 +7,7 @@ mod grid_point3;
 mod neighbors;
 mod dir;
 mod equivalent_points;
+mod region;
 
 // TODO: be selective in what you export; no wildcards!
 pub use self::root::*;
@@ -15,6 +16,7 @@ pub use self::grid_point3::*;
 pub use self::neighbors::*;
 pub use self::dir::*;
 pub use self::equivalent_points::*;
+pub use self::region::{HexDisk, HexRing};
 
 pub type GridCoord = i64;
 
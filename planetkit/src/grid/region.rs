@@ -0,0 +1,276 @@
+use std::collections::HashSet;
+use std::iter::Take;
+use std::mem;
+use std::ops::Range;
+use std::vec;
+
+use super::{GridCoord, GridPoint3, PosInOwningRoot, Neighbors};
+
+/// Iterator over every cell within a given number of steps of a center cell,
+/// i.e. a filled hexagon (or pentagon, near the corners of root quads) of cells.
+///
+/// Distance is measured in steps between cells sharing an edge, so it is correct
+/// across the edges of root quads and around pentagons. All cells are yielded
+/// in their owning root, and no cell is yielded twice.
+///
+/// Cells are yielded in order of increasing distance from the center.
+pub struct HexDisk {
+    rings: Take<Rings>,
+    z_range: Range<GridCoord>,
+    cells: ExtrudedColumns,
+}
+
+impl HexDisk {
+    pub fn new(center: GridPoint3, radius: GridCoord, resolution: [GridCoord; 2]) -> HexDisk {
+        let z_range = center.z..(center.z + 1);
+        HexDisk::new_extruded(center, radius, z_range, resolution)
+    }
+
+    /// Like `new`, but yields every cell in each column for the given range of z-coordinates,
+    /// instead of only the cells at the same z-coordinate as the center.
+    ///
+    /// Any part of the range below zero is ignored.
+    pub fn new_extruded(
+        center: GridPoint3,
+        radius: GridCoord,
+        z_range: Range<GridCoord>,
+        resolution: [GridCoord; 2],
+    ) -> HexDisk {
+        debug_assert!(radius >= 0);
+        HexDisk {
+            rings: Rings::new(center, resolution).take((radius + 1) as usize),
+            z_range: z_range.clone(),
+            cells: ExtrudedColumns::new(Vec::new(), z_range),
+        }
+    }
+}
+
+impl Iterator for HexDisk {
+    type Item = GridPoint3;
+
+    fn next(&mut self) -> Option<GridPoint3> {
+        loop {
+            if let Some(cell) = self.cells.next() {
+                return Some(cell);
+            }
+            // Move on to the next ring out, if there is one.
+            let ring = match self.rings.next() {
+                Some(ring) => ring,
+                None => return None,
+            };
+            self.cells = ExtrudedColumns::new(ring, self.z_range.clone());
+        }
+    }
+}
+
+/// Iterator over every cell exactly a given number of steps from a center cell.
+///
+/// See `HexDisk` for how distance is measured, and the guarantees about cells yielded.
+pub struct HexRing {
+    cells: ExtrudedColumns,
+}
+
+impl HexRing {
+    pub fn new(center: GridPoint3, radius: GridCoord, resolution: [GridCoord; 2]) -> HexRing {
+        let z_range = center.z..(center.z + 1);
+        HexRing::new_extruded(center, radius, z_range, resolution)
+    }
+
+    /// Like `new`, but yields every cell in each column for the given range of z-coordinates,
+    /// instead of only the cells at the same z-coordinate as the center.
+    ///
+    /// Any part of the range below zero is ignored.
+    pub fn new_extruded(
+        center: GridPoint3,
+        radius: GridCoord,
+        z_range: Range<GridCoord>,
+        resolution: [GridCoord; 2],
+    ) -> HexRing {
+        debug_assert!(radius >= 0);
+        // Rings run out if the radius is big enough to cover the whole globe.
+        let ring = Rings::new(center, resolution)
+            .nth(radius as usize)
+            .unwrap_or_else(Vec::new);
+        HexRing { cells: ExtrudedColumns::new(ring, z_range) }
+    }
+}
+
+impl Iterator for HexRing {
+    type Item = GridPoint3;
+
+    fn next(&mut self) -> Option<GridPoint3> {
+        self.cells.next()
+    }
+}
+
+// Successive rings of cells at increasing numbers of steps from a center cell,
+// all at the same z-coordinate as the center.
+//
+// This is just a breadth-first search over `Neighbors` that only remembers the
+// last two rings; a cell one step out from the current ring is in the next ring
+// if it isn't in the current or previous ring.
+struct Rings {
+    resolution: [GridCoord; 2],
+    prev_ring: HashSet<PosInOwningRoot>,
+    ring: Vec<PosInOwningRoot>,
+}
+
+impl Rings {
+    fn new(center: GridPoint3, resolution: [GridCoord; 2]) -> Rings {
+        Rings {
+            resolution: resolution,
+            prev_ring: HashSet::new(),
+            ring: vec![PosInOwningRoot::new(center, resolution)],
+        }
+    }
+}
+
+impl Iterator for Rings {
+    type Item = Vec<GridPoint3>;
+
+    fn next(&mut self) -> Option<Vec<GridPoint3>> {
+        if self.ring.is_empty() {
+            // We've covered the whole globe.
+            return None;
+        }
+
+        let ring = mem::replace(&mut self.ring, Vec::new());
+        let ring_set: HashSet<PosInOwningRoot> = ring.iter().cloned().collect();
+        let mut next_ring_set: HashSet<PosInOwningRoot> = HashSet::new();
+        for pos in &ring {
+            let z = pos.pos().z;
+            for neighbor in Neighbors::new((*pos).into(), self.resolution) {
+                // Only interested in neighbors in the same layer.
+                if neighbor.z != z {
+                    continue;
+                }
+                let neighbor = PosInOwningRoot::new(neighbor, self.resolution);
+                if ring_set.contains(&neighbor) || self.prev_ring.contains(&neighbor) {
+                    continue;
+                }
+                // Push in the order we find them, rather than collecting the set,
+                // so that iteration order is deterministic.
+                if next_ring_set.insert(neighbor) {
+                    self.ring.push(neighbor);
+                }
+            }
+        }
+        self.prev_ring = ring_set;
+
+        Some(ring.into_iter().map(|pos| pos.into()).collect())
+    }
+}
+
+// Every cell in each of the given columns within a range of z-coordinates.
+struct ExtrudedColumns {
+    columns: vec::IntoIter<GridPoint3>,
+    z_range: Range<GridCoord>,
+    current_column: Option<GridPoint3>,
+    next_z: GridCoord,
+}
+
+impl ExtrudedColumns {
+    fn new(columns: Vec<GridPoint3>, z_range: Range<GridCoord>) -> ExtrudedColumns {
+        ExtrudedColumns {
+            columns: columns.into_iter(),
+            current_column: None,
+            next_z: z_range.start.max(0),
+            z_range: z_range,
+        }
+    }
+}
+
+impl Iterator for ExtrudedColumns {
+    type Item = GridPoint3;
+
+    fn next(&mut self) -> Option<GridPoint3> {
+        loop {
+            if let Some(column) = self.current_column {
+                if self.next_z < self.z_range.end {
+                    let z = self.next_z;
+                    self.next_z += 1;
+                    return Some(column.with_z(z));
+                }
+            }
+            self.current_column = self.columns.next();
+            if self.current_column.is_none() {
+                return None;
+            }
+            self.next_z = self.z_range.start.max(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    const RESOLUTION: [GridCoord; 2] = [32, 64];
+
+    fn assert_no_duplicates(cells: &[GridPoint3]) {
+        let unique: HashSet<PosInOwningRoot> = cells
+            .iter()
+            .map(|cell| PosInOwningRoot::new(*cell, RESOLUTION))
+            .collect();
+        assert_eq!(unique.len(), cells.len());
+    }
+
+    #[test]
+    fn disk_and_ring_away_from_root_edges() {
+        let center = GridPoint3::new(1.into(), 10, 20, 5);
+
+        let disk: Vec<GridPoint3> = HexDisk::new(center, 0, RESOLUTION).collect();
+        assert_eq!(disk, vec![center]);
+
+        let disk: Vec<GridPoint3> = HexDisk::new(center, 2, RESOLUTION).collect();
+        assert_eq!(disk.len(), 19);
+        assert_eq!(disk[0], center);
+        assert_no_duplicates(&disk);
+
+        let ring: Vec<GridPoint3> = HexRing::new(center, 2, RESOLUTION).collect();
+        assert_eq!(ring.len(), 12);
+        assert_no_duplicates(&ring);
+        for cell in &ring {
+            assert!(disk.contains(cell));
+        }
+    }
+
+    #[test]
+    fn disk_straddling_root_edge() {
+        // On the north-east edge of a root, well away from any pentagons.
+        let center = GridPoint3::new(0.into(), 0, 10, 5);
+        let disk: Vec<GridPoint3> = HexDisk::new(center, 3, RESOLUTION).collect();
+        assert_eq!(disk.len(), 37);
+        assert_no_duplicates(&disk);
+        // Some of those should have come from the neighboring root.
+        assert!(disk.iter().any(|cell| cell.root.index != 0));
+    }
+
+    #[test]
+    fn ring_around_pentagon() {
+        // North pole.
+        let center = GridPoint3::new(0.into(), 0, 0, 5);
+        let ring: Vec<GridPoint3> = HexRing::new(center, 1, RESOLUTION).collect();
+        assert_eq!(ring.len(), 5);
+        assert_no_duplicates(&ring);
+
+        let disk: Vec<GridPoint3> = HexDisk::new(center, 2, RESOLUTION).collect();
+        assert_eq!(disk.len(), 1 + 5 + 10);
+        assert_no_duplicates(&disk);
+    }
+
+    #[test]
+    fn extruded_disk() {
+        let center = GridPoint3::new(1.into(), 10, 20, 5);
+        let disk: Vec<GridPoint3> = HexDisk::new_extruded(center, 1, 4..7, RESOLUTION).collect();
+        assert_eq!(disk.len(), 7 * 3);
+        assert_no_duplicates(&disk);
+
+        // Don't yield anything below z = 0.
+        let disk: Vec<GridPoint3> = HexDisk::new_extruded(center, 1, -2..2, RESOLUTION).collect();
+        assert_eq!(disk.len(), 7 * 2);
+        assert!(disk.iter().all(|cell| cell.z >= 0));
+    }
+}
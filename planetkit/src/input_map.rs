@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+
+use piston::input::{Button, ButtonState, Input};
+use serde_json;
+use specs;
+
+/// Bindings from named actions (e.g. "step_forward") to the keys,
+/// mouse buttons, or controller buttons that trigger them.
+///
+/// Input adapters look up which action an input event is bound to
+/// instead of matching on specific keys, so that players can rebind them.
+///
+/// Each button triggers at most one action, but an action may be
+/// bound to any number of buttons.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct InputMap {
+    actions: BTreeMap<String, Vec<Button>>,
+}
+
+impl InputMap {
+    pub fn new() -> InputMap {
+        InputMap { actions: BTreeMap::new() }
+    }
+
+    /// Read bindings from JSON like:
+    ///
+    /// ```json
+    /// {
+    ///     "jump": [{ "Keyboard": "Space" }, { "Controller": { "id": 0, "button": 0 } }],
+    ///     "pick_up": [{ "Mouse": "Left" }]
+    /// }
+    /// ```
+    ///
+    /// An action listed with no buttons is deliberately left unbound.
+    pub fn read<R: Read>(reader: R) -> io::Result<InputMap> {
+        let actions = serde_json::from_reader(reader)?;
+        Ok(InputMap { actions: actions })
+    }
+
+    /// Write bindings out in the same format `read` expects.
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        serde_json::to_writer_pretty(writer, &self.actions)?;
+        Ok(())
+    }
+
+    /// Bind a button to an action, taking it away from
+    /// any other action it was bound to.
+    pub fn bind(&mut self, action: &str, button: Button) {
+        self.unbind(button);
+        self.actions.entry(action.to_string()).or_insert_with(Vec::new).push(button);
+    }
+
+    /// Stop a button from triggering any action.
+    pub fn unbind(&mut self, button: Button) {
+        for buttons in self.actions.values_mut() {
+            buttons.retain(|&bound_button| bound_button != button);
+        }
+    }
+
+    /// Bind buttons to an action, unless the action has already been bound
+    /// (or deliberately left unbound), e.g. by loading the player's own bindings.
+    ///
+    /// Buttons that are already bound to some other action are skipped.
+    ///
+    /// Input adapters should use this to set up their default bindings.
+    pub fn set_default(&mut self, action: &str, buttons: &[Button]) {
+        if self.actions.contains_key(action) {
+            return;
+        }
+        let free_buttons: Vec<Button> = buttons
+            .iter()
+            .cloned()
+            .filter(|&button| self.action_for(button).is_none())
+            .collect();
+        self.actions.insert(action.to_string(), free_buttons);
+    }
+
+    /// Replace the bindings for every action mentioned in `other`,
+    /// leaving any others alone.
+    pub fn override_with(&mut self, other: InputMap) {
+        for (action, buttons) in other.actions {
+            for &button in &buttons {
+                self.unbind(button);
+            }
+            self.actions.insert(action, buttons);
+        }
+    }
+
+    pub fn buttons_for(&self, action: &str) -> &[Button] {
+        self.actions.get(action).map(|buttons| &buttons[..]).unwrap_or(&[])
+    }
+
+    pub fn action_for(&self, button: Button) -> Option<&str> {
+        self.actions
+            .iter()
+            .find(|&(_, buttons)| buttons.contains(&button))
+            .map(|(action, _)| action.as_str())
+    }
+
+    /// If the event is a button being pressed or released, find the action
+    /// it is bound to, and whether the button is now down.
+    pub fn action_for_input(&self, input_event: &Input) -> Option<(&str, bool)> {
+        if let &Input::Button(button_args) = input_event {
+            let is_down = match button_args.state {
+                ButtonState::Press => true,
+                ButtonState::Release => false,
+            };
+            return self.action_for(button_args.button).map(|action| (action, is_down));
+        }
+        None
+    }
+}
+
+/// `World`-global resource for the bindings shared by all input adapters.
+///
+/// Input adapters keep their own handle to the same `InputMap`,
+/// so changes made through this resource apply immediately.
+pub struct InputMapResource {
+    pub input_map: Arc<Mutex<InputMap>>,
+}
+
+impl ::AutoResource for InputMapResource {
+    fn new(_world: &mut specs::World) -> InputMapResource {
+        InputMapResource {
+            input_map: Arc::new(Mutex::new(InputMap::new())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use piston::input::{ButtonArgs, Key, MouseButton};
+
+    use super::*;
+
+    fn release(button: Button) -> Input {
+        Input::Button(ButtonArgs {
+            state: ButtonState::Release,
+            button: button,
+            scancode: None,
+        })
+    }
+
+    #[test]
+    fn buttons_trigger_one_action_at_most() {
+        let mut input_map = InputMap::new();
+        input_map.bind("jump", Button::Keyboard(Key::Space));
+        input_map.bind("shoot", Button::Keyboard(Key::Space));
+        input_map.bind("shoot", Button::Mouse(MouseButton::Left));
+        assert!(input_map.buttons_for("jump").is_empty());
+        assert_eq!(input_map.action_for(Button::Keyboard(Key::Space)), Some("shoot"));
+        assert_eq!(
+            input_map.action_for_input(&release(Button::Mouse(MouseButton::Left))),
+            Some(("shoot", false))
+        );
+    }
+
+    #[test]
+    fn defaults_dont_clobber_loaded_bindings() {
+        let json = r#"{ "jump": [{ "Keyboard": "J" }], "dive": [] }"#;
+        let mut input_map = InputMap::new();
+        input_map.override_with(InputMap::read(json.as_bytes()).unwrap());
+        input_map.set_default("jump", &[Button::Keyboard(Key::Z)]);
+        input_map.set_default("dive", &[Button::Keyboard(Key::X)]);
+        // J is already taken, so the default for turning left
+        // has to make do without it.
+        input_map.set_default("turn_left", &[Button::Keyboard(Key::Left), Button::Keyboard(Key::J)]);
+        assert_eq!(input_map.buttons_for("jump"), &[Button::Keyboard(Key::J)]);
+        assert!(input_map.buttons_for("dive").is_empty());
+        assert_eq!(input_map.buttons_for("turn_left"), &[Button::Keyboard(Key::Left)]);
+
+        // And it should survive a round trip.
+        let mut out: Vec<u8> = Vec::new();
+        input_map.write(&mut out).unwrap();
+        assert_eq!(InputMap::read(&out[..]).unwrap(), input_map);
+    }
+}
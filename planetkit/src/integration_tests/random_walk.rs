@@ -5,6 +5,7 @@ use globe;
 use specs;
 use cell_dweller;
 use types::*;
+use AutoSystem;
 
 // TODO: make a proper test harness using `App`, `piston::window::NoWindow`,
 // and some custom systems to drive the tests.
@@ -24,31 +25,25 @@ impl Walker {
         // Create Specs `World`.
         let mut world = specs::World::new();
 
-        // Register all component types.
-        world.register::<::cell_dweller::CellDweller>();
-        world.register::<::Spatial>();
-        world.register::<::globe::Globe>();
-        world.register::<::net::NetMarker>();
-
         // Initialize common resources.
         world.add_resource(TimeDeltaResource(0.0));
 
         // Create systems.
-        let chunk_sys = globe::ChunkSystem::new(&root_log);
+        let mut chunk_sys = globe::ChunkSystem::new(&root_log);
+        chunk_sys.init(&mut world);
 
         let (movement_input_sender, movement_input_receiver) = mpsc::channel();
         let mut movement_sys =
             cell_dweller::MovementSystem::new(&mut world, movement_input_receiver, &root_log);
         movement_sys.init(&mut world);
-        // Stop the player from getting stuck on cliffs; we want to test what
-        // happens when they walk really aggressively all around the world, not what
-        // happens when they fall into a hole and don't move anywhere.
-        movement_sys.set_step_height(100);
 
-        let physics_sys = cell_dweller::PhysicsSystem::new(
+        let mut physics_sys = cell_dweller::PhysicsSystem::new(
+            &mut world,
             &root_log,
-            0.1, // Seconds between falls
+            20.0, // Gravity, in cells per second squared
+            30.0, // Terminal velocity, in cells per second
         );
+        physics_sys.init(&mut world);
 
         // Make a dispatcher and add all our systems.
         let dispatcher = specs::DispatcherBuilder::new()
@@ -76,15 +71,24 @@ impl Walker {
             globe.find_lowest_cell_containing(guy_pos, Material::Air)
         };
 
+        // Stop the player from getting stuck on cliffs; we want to test what
+        // happens when they walk really aggressively all around the world, not what
+        // happens when they fall into a hole and don't move anywhere.
+        let movement_profile = cell_dweller::MovementProfile {
+            max_step_height: 100,
+            ..cell_dweller::MovementProfile::default()
+        };
         let guy_entities: Vec<_> = (0..walker_count).map(|_| {
+            let mut cd = cell_dweller::CellDweller::new(
+                guy_pos,
+                Dir::default(),
+                globe_spec,
+                Some(globe_entity),
+            );
+            cd.set_movement_profile(movement_profile);
             world
                 .create_entity()
-                .with(cell_dweller::CellDweller::new(
-                    guy_pos,
-                    Dir::default(),
-                    globe_spec,
-                    Some(globe_entity),
-                ))
+                .with(cd)
                 .with(::Spatial::new_root())
                 .build()
         }).collect();
@@ -83,6 +83,8 @@ extern crate serde_json;
 #[cfg(all(feature = "nightly", test))] extern crate test;
 
 pub mod input_adapter;
+mod input_map;
+pub use input_map::{InputMap, InputMapResource};
 pub mod grid;
 pub mod globe;
 pub mod types;
@@ -103,9 +105,24 @@ pub use spatial::SpatialStorage;
 mod auto_resource;
 pub use auto_resource::AutoResource;
 
+mod auto_system;
+pub use auto_system::{AutoSystem, ensure_component_registered};
+
+mod bundle;
+pub use bundle::Bundle;
+
 mod log_resource;
 pub use log_resource::LogResource;
 
+mod event_channel;
+pub use event_channel::{EventChannel, ReaderId, DEFAULT_EVENT_CHANNEL_CAPACITY};
+
+mod fixed_timestep;
+pub use fixed_timestep::{FixedTimestep, TickCounter, InterpolationAlpha, DEFAULT_TICKS_PER_SECOND};
+
+mod replay;
+pub use replay::{Replay, Recorder, Recording, RecordingHeader, RecordedEvent, RecordedEventKind};
+
 mod app_builder;
 pub use app_builder::AppBuilder;
 
@@ -0,0 +1,48 @@
+use std::marker::PhantomData;
+
+use app_builder::AppBuilder;
+use bundle::Bundle;
+use super::{
+    GameMessage,
+    NewPeerSystem,
+    RecvSystem,
+    SendSystem,
+};
+
+/// Connects the app to network peers, sending and receiving game messages of type `G`.
+///
+/// Received messages are put in the `RecvMessageQueue<G>` each tick, before any
+/// game systems that depend on `"net_recv"` run, and anything in the `SendMessageQueue<G>`
+/// is sent after every other system has run for the tick.
+///
+/// Starting a server or connecting to one is still up to the game; see `ServerResource`.
+pub struct NetBundle<G> {
+    _phantom_game_message: PhantomData<G>,
+}
+
+impl<G: GameMessage> NetBundle<G> {
+    pub fn new() -> NetBundle<G> {
+        NetBundle { _phantom_game_message: PhantomData }
+    }
+}
+
+impl<G: GameMessage> Default for NetBundle<G> {
+    fn default() -> NetBundle<G> {
+        NetBundle::new()
+    }
+}
+
+impl<G: GameMessage> Bundle for NetBundle<G> {
+    fn add_to(self, mut app_builder: AppBuilder) -> AppBuilder {
+        let log = app_builder.root_log().clone();
+
+        let new_peer_sys = NewPeerSystem::<G>::new(&log, app_builder.world_mut());
+        let recv_sys = RecvSystem::<G>::new(&log, app_builder.world_mut());
+        let send_sys = SendSystem::<G>::new(&log, app_builder.world_mut());
+
+        app_builder
+            .add_auto_system(new_peer_sys)
+            .add_auto_system(recv_sys)
+            .add_final_auto_system(send_sys)
+    }
+}
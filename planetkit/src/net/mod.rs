@@ -8,6 +8,7 @@
 #[cfg(not(target_os="emscripten"))] mod server_resource;
 #[cfg(not(target_os="emscripten"))] mod udp;
 #[cfg(not(target_os="emscripten"))] mod tcp;
+#[cfg(not(target_os="emscripten"))] mod bundle;
 
 #[cfg(test)]
 mod tests;
@@ -29,6 +30,7 @@ use ::AutoResource;
 #[cfg(not(target_os="emscripten"))] pub use self::new_peer_system::NewPeerSystem;
 #[cfg(not(target_os="emscripten"))] pub use self::server::Server;
 #[cfg(not(target_os="emscripten"))] pub use self::server_resource::ServerResource;
+#[cfg(not(target_os="emscripten"))] pub use self::bundle::NetBundle;
 
 // TODO: all this naming is pretty shoddy, and evolved in an awkward
 // way that makes it super unclear what's for what.
@@ -183,22 +185,24 @@ pub struct NetworkPeer<G> {
 /// `World`-global resource for network peers.
 pub struct NetworkPeers<G> {
     pub peers: Vec<NetworkPeer<G>>,
-    // List of new peers for a single game-specific
-    // system to use.
-    // TODO: This makes yet another good use case for some kind
-    // of pub/sub event system.
-    pub new_peers: VecDeque<PeerId>,
 }
 
 impl<G: GameMessage> AutoResource for NetworkPeers<G> {
     fn new(_world: &mut specs::World) -> NetworkPeers<G> {
         NetworkPeers {
             peers: Vec::<NetworkPeer<G>>::new(),
-            new_peers: VecDeque::<PeerId>::new(),
         }
     }
 }
 
+/// Event sent through an `EventChannel` whenever a new network peer
+/// has been registered, so that game-specific systems can do
+/// whatever initialization they might need to do.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PeerJoined {
+    pub peer_id: PeerId,
+}
+
 /// `World`-global resource for global entity naming.
 pub struct EntityIds {
     // Range of IDs this node can allocate for itself.
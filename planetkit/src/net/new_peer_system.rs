@@ -5,12 +5,15 @@ use specs;
 use specs::FetchMut;
 use slog::Logger;
 
+use ::event_channel::EventChannel;
+use ::auto_system::AutoSystem;
 use super::{
     GameMessage,
     NewPeer,
     NetworkPeers,
     NetworkPeer,
     PeerId,
+    PeerJoined,
 };
 
 pub struct NewPeerSystem<G: GameMessage>{
@@ -26,6 +29,7 @@ impl<G> NewPeerSystem<G>
 
         // Ensure resources we use are present.
         NetworkPeers::<G>::ensure(world);
+        EventChannel::<PeerJoined>::ensure(world);
 
         // Ensure ServerResource is present, and fetch the
         // channel ends we need from it.
@@ -50,11 +54,13 @@ impl<'a, G> specs::System<'a> for NewPeerSystem<G>
 {
     type SystemData = (
         FetchMut<'a, NetworkPeers<G>>,
+        FetchMut<'a, EventChannel<PeerJoined>>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
         let (
             mut network_peers,
+            mut peer_joined_events,
         ) = data;
 
         // Register any new peers that have connected
@@ -85,7 +91,7 @@ impl<'a, G> specs::System<'a> for NewPeerSystem<G>
                     // we might start sending them things over UDP that
                     // they're not ready to receive, and they'll spew a bunch
                     // of unnecessary warnings. :)
-                    network_peers.new_peers.push_back(next_peer_id);
+                    peer_joined_events.single_write(PeerJoined { peer_id: next_peer_id });
                 },
                 Err(err) => {
                     match err {
@@ -103,3 +109,16 @@ impl<'a, G> specs::System<'a> for NewPeerSystem<G>
         }
     }
 }
+
+impl<G> AutoSystem for NewPeerSystem<G>
+    where G: GameMessage
+{
+    fn name(&self) -> &'static str {
+        "net_new_peer"
+    }
+
+    fn init(&mut self, _world: &mut specs::World) {
+        // Resources we use are ensured in `new`, because
+        // we need to take our channel ends out of `ServerResource`.
+    }
+}
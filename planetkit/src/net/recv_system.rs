@@ -4,6 +4,9 @@ use specs;
 use specs::{Fetch, FetchMut};
 use slog::Logger;
 
+use ::fixed_timestep::TickCounter;
+use ::replay::Replay;
+use ::auto_system::AutoSystem;
 use super::{
     GameMessage,
     RecvMessage,
@@ -31,6 +34,8 @@ impl<G> RecvSystem<G>
         // Ensure resources we use are present.
         RecvMessageQueue::<G>::ensure(world);
         NetworkPeers::<G>::ensure(world);
+        TickCounter::ensure(world);
+        Replay::ensure(world);
 
         // Ensure ServerResource is present, and fetch the
         // wire message receiver from it.
@@ -55,14 +60,30 @@ impl<'a, G> specs::System<'a> for RecvSystem<G>
     type SystemData = (
         FetchMut<'a, RecvMessageQueue<G>>,
         Fetch<'a, NetworkPeers<G>>,
+        Fetch<'a, TickCounter>,
+        FetchMut<'a, Replay>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
         let (
             mut recv_message_queue,
             network_peers,
+            tick_counter,
+            mut replay,
         ) = data;
 
+        if replay.is_replaying() {
+            // Pretend the recorded messages are the only ones that arrived.
+            while let Ok(_) = self.recv_rx.try_recv() {}
+            while let Some(recv_message) = replay.next_message(tick_counter.ticks) {
+                match recv_message {
+                    Ok(recv_message) => recv_message_queue.queue.push_back(recv_message),
+                    Err(err) => warn!(self.log, "Couldn't decode recorded message"; "error" => format!("{}", err)),
+                }
+            }
+            return;
+        }
+
         // Slurp everything the server sent us.
         loop {
             let recv_wire_message = match self.recv_rx.try_recv() {
@@ -114,8 +135,23 @@ impl<'a, G> specs::System<'a> for RecvSystem<G>
                 source: peer_id,
                 game_message: game_message,
             };
+            if let Err(err) = replay.record_message(tick_counter.ticks, &recv_message) {
+                warn!(self.log, "Failed to record message"; "error" => format!("{}", err));
+            }
             recv_message_queue.queue.push_back(recv_message);
-
         }
     }
 }
+
+impl<G> AutoSystem for RecvSystem<G>
+    where G: GameMessage
+{
+    fn name(&self) -> &'static str {
+        "net_recv"
+    }
+
+    fn init(&mut self, _world: &mut specs::World) {
+        // Resources we use are ensured in `new`, because
+        // we need to take our channel ends out of `ServerResource`.
+    }
+}
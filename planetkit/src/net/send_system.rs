@@ -3,6 +3,7 @@ use specs::{Fetch, FetchMut};
 use slog::Logger;
 use futures;
 
+use ::auto_system::AutoSystem;
 use super::{
     GameMessage,
     WireMessage,
@@ -190,3 +191,16 @@ impl<'a, G> specs::System<'a> for SendSystem<G>
         }
     }
 }
+
+impl<G> AutoSystem for SendSystem<G>
+    where G: GameMessage
+{
+    fn name(&self) -> &'static str {
+        "net_send"
+    }
+
+    fn init(&mut self, _world: &mut specs::World) {
+        // Resources we use are ensured in `new`, because
+        // we need to take our channel ends out of `ServerResource`.
+    }
+}
@@ -0,0 +1,32 @@
+use specs;
+
+/// Lets an entity with a `Velocity` bump into the terrain of the
+/// `Globe` it's moving relative to, rather than passing straight through it.
+///
+/// See `TerrainCollisionSystem`.
+pub struct Collider {
+    /// How far the body extends from its position in every direction.
+    pub radius: f64,
+    /// How much of the body's speed into a surface is kept, bouncing back
+    /// off it, when it hits. Zero means it doesn't bounce at all; one means
+    /// it bounces back out as fast as it went in.
+    pub restitution: f64,
+    /// How much of the body's speed along a surface is lost for each second
+    /// it spends sliding along it, between zero (perfectly slippery)
+    /// and one (stops dead).
+    pub friction: f64,
+}
+
+impl Collider {
+    pub fn new(radius: f64, restitution: f64, friction: f64) -> Collider {
+        Collider {
+            radius: radius,
+            restitution: restitution,
+            friction: friction,
+        }
+    }
+}
+
+impl specs::Component for Collider {
+    type Storage = specs::VecStorage<Collider>;
+}
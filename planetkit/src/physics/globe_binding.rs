@@ -0,0 +1,19 @@
+use specs;
+
+/// Ties an entity with `Mass` to the globe whose gravity it should feel,
+/// regardless of which spheres of influence it happens to be in.
+pub struct GlobeBinding {
+    pub globe_entity: specs::Entity,
+}
+
+impl GlobeBinding {
+    pub fn new(globe_entity: specs::Entity) -> GlobeBinding {
+        GlobeBinding {
+            globe_entity: globe_entity,
+        }
+    }
+}
+
+impl specs::Component for GlobeBinding {
+    type Storage = specs::HashMapStorage<GlobeBinding>;
+}
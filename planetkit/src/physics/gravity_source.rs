@@ -0,0 +1,54 @@
+use std;
+
+use specs;
+
+/// Newton's gravitational constant, in m³/(kg·s²).
+pub const GRAVITATIONAL_CONSTANT: f64 = 6.674e-11;
+
+/// Something massive enough to pull other objects toward it; usually a `Globe`.
+///
+/// Globes without one of these pull things toward them as if
+/// they had Earth gravity at sea level, and an unbounded sphere of influence.
+#[derive(Clone, Copy, Debug)]
+pub struct GravitySource {
+    /// In kilograms.
+    pub mass: f64,
+    /// How far from its center this attracts things.
+    ///
+    /// Bodies inside the spheres of influence of several sources (e.g. near a moon
+    /// orbiting a planet) are only pulled toward the one with the smallest sphere.
+    pub sphere_of_influence: f64,
+}
+
+impl GravitySource {
+    pub fn new(mass: f64, sphere_of_influence: f64) -> GravitySource {
+        GravitySource {
+            mass: mass,
+            sphere_of_influence: sphere_of_influence,
+        }
+    }
+
+    /// Make a source with just enough mass to give the requested
+    /// acceleration due to gravity at the given radius (e.g. sea level).
+    pub fn with_surface_gravity(surface_gravity: f64, radius: f64, sphere_of_influence: f64) -> GravitySource {
+        GravitySource::new(
+            surface_gravity * radius * radius / GRAVITATIONAL_CONSTANT,
+            sphere_of_influence,
+        )
+    }
+
+    /// What Kaboom and friends have always had: Earth gravity at the given
+    /// radius, reaching out forever.
+    pub fn earth_like(radius: f64) -> GravitySource {
+        GravitySource::with_surface_gravity(9.8, radius, std::f64::INFINITY)
+    }
+
+    /// Acceleration toward the source at the given distance from its center.
+    pub fn acceleration_at(&self, distance: f64) -> f64 {
+        GRAVITATIONAL_CONSTANT * self.mass / (distance * distance)
+    }
+}
+
+impl specs::Component for GravitySource {
+    type Storage = specs::HashMapStorage<GravitySource>;
+}
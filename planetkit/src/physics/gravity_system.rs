@@ -3,13 +3,19 @@ use specs::{ReadStorage, WriteStorage, Fetch, Entities};
 use slog::Logger;
 
 use types::*;
-use super::Velocity;
-use super::Mass;
+use super::{Velocity, Mass, GravitySource, GlobeBinding};
 use Spatial;
 use globe::Globe;
+use auto_system::{AutoSystem, ensure_component_registered};
 
-/// Accelerates everything with mass toward the first globe we find.
-// (TODO: this is horrible hacks, but works for Kaboom.)
+/// Accelerates everything with mass toward the globe whose gravity it's under.
+///
+/// That's the globe it's bound to by a `GlobeBinding` if it has one, or otherwise
+/// the globe with the smallest sphere of influence that it's inside. Gravity falls off
+/// with the square of the distance from the globe's center; see `GravitySource`.
+///
+/// Acceleration is applied in the frame of each body's parent, which is where
+/// its `Velocity` lives. Bodies without a parent are left alone.
 pub struct GravitySystem {
     _log: Logger,
 }
@@ -30,6 +36,8 @@ impl<'a> specs::System<'a> for GravitySystem {
         WriteStorage<'a, Velocity>,
         ReadStorage<'a, Mass>,
         ReadStorage<'a, Globe>,
+        ReadStorage<'a, GravitySource>,
+        ReadStorage<'a, GlobeBinding>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -43,24 +51,210 @@ impl<'a> specs::System<'a> for GravitySystem {
             mut velocities,
             masses,
             globes,
+            gravity_sources,
+            globe_bindings,
         ) = data;
 
-        // For now just find the first globe, and assume that's
-        // the one we're supposed to be accelerating towards.
-        let globe_entity = match (&*entities, &spatials, &globes).join().next() {
-            Some((globe_entity, _spatial, _globe)) => globe_entity,
-            // If there's no globe yet, then just do nothing.
-            None => return,
-        };
-
-        for (mass_entity, _mass, velocity) in (&*entities, &masses, &mut velocities).join() {
-            // Accelerate toward the globe. Might as well use Earth gravity for now.
-            // Do it "backwards" because we need to strip off the mass's orientation.
-            //
-            let mass_from_globe = spatials.a_relative_to_b(mass_entity, globe_entity).translation.vector;
-            let gravity_direction = -mass_from_globe.normalize();
-            let acceleration = gravity_direction * 9.8 * dt.0;
-            *velocity.local_velocity_mut() += acceleration;
+        // Gather up everything that might be pulling on things.
+        let globe_sources: Vec<(specs::Entity, GravitySource, f64)> = (&*entities, &globes, &spatials)
+            .join()
+            .map(|(globe_entity, globe, _spatial)| {
+                let spec = globe.spec();
+                let source = gravity_sources
+                    .get(globe_entity)
+                    .cloned()
+                    .unwrap_or_else(|| GravitySource::earth_like(spec.ocean_radius));
+                // Don't let gravity blow up for anything
+                // that somehow ends up near the center.
+                (globe_entity, source, spec.floor_radius)
+            })
+            .collect();
+
+        for (mass_entity, _mass, spatial, velocity) in (&*entities, &masses, &spatials, &mut velocities).join() {
+            let parent_entity = match spatial.parent_entity() {
+                Some(parent_entity) => parent_entity,
+                None => continue,
+            };
+            let maybe_bound_globe_entity = globe_bindings
+                .get(mass_entity)
+                .map(|globe_binding| globe_binding.globe_entity);
+            let mass_pos = spatial.local_transform().translation.vector;
+
+            // Find which globe we're under the influence of, and where it is.
+            let mut best: Option<(Vec3, GravitySource, f64)> = None;
+            for &(globe_entity, source, min_distance) in &globe_sources {
+                if globe_entity == mass_entity || !spatials.have_common_ancestor(globe_entity, mass_entity) {
+                    continue;
+                }
+                let is_bound_here = match maybe_bound_globe_entity {
+                    Some(bound_globe_entity) => {
+                        if bound_globe_entity != globe_entity {
+                            continue;
+                        }
+                        true
+                    }
+                    None => false,
+                };
+                let globe_pos = spatials.a_relative_to_b(globe_entity, parent_entity).translation.vector;
+                let to_globe = globe_pos - mass_pos;
+                if !is_bound_here && to_globe.norm() > source.sphere_of_influence {
+                    continue;
+                }
+                let is_better = best
+                    .map(|(_, best_source, _)| source.sphere_of_influence < best_source.sphere_of_influence)
+                    .unwrap_or(true);
+                if is_better {
+                    best = Some((to_globe, source, min_distance));
+                }
+            }
+
+            let (to_globe, source, min_distance) = match best {
+                Some(best) => best,
+                // Drifting through empty space.
+                None => continue,
+            };
+            let distance = to_globe.norm();
+            if distance == 0.0 {
+                continue;
+            }
+            let acceleration = to_globe / distance * source.acceleration_at(distance.max(min_distance));
+            *velocity.local_velocity_mut() += acceleration * dt.0;
         }
     }
 }
+
+impl AutoSystem for GravitySystem {
+    fn name(&self) -> &'static str {
+        "gravity"
+    }
+
+    fn init(&mut self, world: &mut specs::World) {
+        ensure_component_registered::<Spatial>(world);
+        ensure_component_registered::<Velocity>(world);
+        ensure_component_registered::<Mass>(world);
+        ensure_component_registered::<Globe>(world);
+        ensure_component_registered::<GravitySource>(world);
+        ensure_component_registered::<GlobeBinding>(world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use slog;
+    use specs;
+    use na;
+
+    use types::*;
+    use globe::Globe;
+    use Spatial;
+    use super::*;
+    use super::super::{Velocity, Mass, GravitySource, GlobeBinding};
+
+    // A planet with a moon orbiting it, loosely following
+    // the `SolarSystem` used to test `Spatial`.
+    struct PlanetAndMoon {
+        world: specs::World,
+        dispatcher: specs::Dispatcher<'static, 'static>,
+        planet: specs::Entity,
+        moon: specs::Entity,
+    }
+
+    impl PlanetAndMoon {
+        fn new() -> PlanetAndMoon {
+            let root_log = slog::Logger::root(slog::Discard, o!());
+
+            let mut world = specs::World::new();
+            world.register::<Spatial>();
+            world.register::<Globe>();
+            world.register::<Velocity>();
+            world.register::<Mass>();
+            world.register::<GravitySource>();
+            world.register::<GlobeBinding>();
+            world.add_resource(TimeDeltaResource(1.0));
+
+            // The planet gets Earth gravity by default.
+            let planet = world
+                .create_entity()
+                .with(Spatial::new_root())
+                .with(Globe::new_example())
+                .build();
+            let moon_globe = Globe::new_example();
+            let moon_source = GravitySource::with_surface_gravity(1.6, moon_globe.spec().ocean_radius, 200.0);
+            let moon = world
+                .create_entity()
+                .with(Spatial::new(planet, Iso3::new(Vec3::new(0.0, 0.0, 1000.0), na::zero())))
+                .with(moon_globe)
+                .with(moon_source)
+                .build();
+
+            let dispatcher = specs::DispatcherBuilder::new()
+                .add(GravitySystem::new(&root_log), "gravity", &[])
+                .build();
+
+            PlanetAndMoon {
+                world: world,
+                dispatcher: dispatcher,
+                planet: planet,
+                moon: moon,
+            }
+        }
+
+        // Make a rock at rest relative to the planet.
+        fn add_rock(&mut self, pos: Vec3) -> specs::Entity {
+            self.world
+                .create_entity()
+                .with(Spatial::new(self.planet, Iso3::new(pos, na::zero())))
+                .with(Velocity::new(Vec3::new(0.0, 0.0, 0.0)))
+                .with(Mass::new())
+                .build()
+        }
+
+        fn step(&mut self) {
+            self.dispatcher.dispatch(&self.world.res);
+            self.world.maintain();
+        }
+
+        fn velocity_of(&self, entity: specs::Entity) -> Vec3 {
+            self.world.read::<Velocity>().get(entity).unwrap().local_velocity()
+        }
+    }
+
+    #[test]
+    fn gravity_falls_off_with_distance() {
+        let mut pam = PlanetAndMoon::new();
+        let near_rock = pam.add_rock(Vec3::new(0.0, 0.0, 100.0));
+        let far_rock = pam.add_rock(Vec3::new(0.0, 0.0, -400.0));
+        pam.step();
+
+        let near_velocity = pam.velocity_of(near_rock);
+        let far_velocity = pam.velocity_of(far_rock);
+        // Both pulled toward the planet...
+        assert!(near_velocity.z < 0.0);
+        assert!(far_velocity.z > 0.0);
+        // ...with strength following the inverse-square law.
+        assert_relative_eq!(near_velocity.norm() / far_velocity.norm(), 16.0, max_relative = 1e-9);
+    }
+
+    #[test]
+    fn moon_captures_nearby_rocks() {
+        let mut pam = PlanetAndMoon::new();
+        let moon_rock = pam.add_rock(Vec3::new(100.0, 0.0, 1000.0));
+        let bound_rock = pam.add_rock(Vec3::new(100.0, 0.0, 1000.0));
+        let planet = pam.planet;
+        pam.world.write::<GlobeBinding>().insert(bound_rock, GlobeBinding::new(planet));
+        pam.step();
+
+        // Inside the moon's sphere of influence, so pulled sideways toward it.
+        let moon_rock_velocity = pam.velocity_of(moon_rock);
+        assert!(moon_rock_velocity.x < 0.0);
+        assert_relative_eq!(moon_rock_velocity.z, 0.0);
+        let moon_ocean_radius = pam.world.read::<Globe>().get(pam.moon).unwrap().spec().ocean_radius;
+        let expected_speed = 1.6 * moon_ocean_radius * moon_ocean_radius / (100.0 * 100.0);
+        assert_relative_eq!(moon_rock_velocity.norm(), expected_speed, max_relative = 1e-9);
+
+        // Bound to the planet, so it only feels the planet's gravity.
+        let bound_rock_velocity = pam.velocity_of(bound_rock);
+        assert!(bound_rock_velocity.z < 0.0);
+        assert!(bound_rock_velocity.z.abs() > bound_rock_velocity.x.abs());
+    }
+}
@@ -7,8 +7,16 @@ mod velocity;
 mod velocity_system;
 mod mass;
 mod gravity_system;
+mod gravity_source;
+mod globe_binding;
+mod collider;
+mod terrain_collision_system;
 
 pub use self::velocity::Velocity;
 pub use self::velocity_system::VelocitySystem;
 pub use self::mass::Mass;
 pub use self::gravity_system::GravitySystem;
+pub use self::gravity_source::{GravitySource, GRAVITATIONAL_CONSTANT};
+pub use self::globe_binding::GlobeBinding;
+pub use self::collider::Collider;
+pub use self::terrain_collision_system::{TerrainCollisionSystem, TerrainContacts, TerrainContact};
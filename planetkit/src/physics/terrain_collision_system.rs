@@ -0,0 +1,402 @@
+use specs;
+use specs::{ReadStorage, WriteStorage, Fetch, FetchMut, Entities, Entity};
+use slog::Logger;
+
+use types::*;
+use grid::{GridCoord, GridPoint3};
+use globe::{Globe, RaycastResult};
+use super::{Velocity, Collider};
+use Spatial;
+use auto_system::{AutoSystem, ensure_component_registered};
+
+// Bodies hitting the terrain slower than this just stop instead of bouncing,
+// so that things resting on the ground don't jitter around forever.
+const MIN_BOUNCE_SPEED: f64 = 0.5;
+
+// Give up pushing a body out of the ground if it's buried deeper than this.
+const MAX_CELLS_TO_ESCAPE: GridCoord = 8;
+
+/// A body with a `Collider` hitting the terrain of a `Globe`.
+#[derive(Clone, Copy, Debug)]
+pub struct TerrainContact {
+    /// The body that hit the terrain.
+    pub entity: Entity,
+    /// The globe whose terrain it hit.
+    pub globe_entity: Entity,
+    /// The solid cell it hit.
+    pub cell: GridPoint3,
+    /// Unit vector pointing out of the surface that was hit, relative to the globe.
+    pub normal: Vec3,
+    /// How fast the body was moving into the surface when it hit.
+    pub impact_speed: f64,
+}
+
+/// `World`-global resource listing every contact between bodies
+/// and terrain found by the last run of the `TerrainCollisionSystem`.
+///
+/// The list is replaced every tick, so any other tick system will see each
+/// contact exactly once, regardless of whether it runs before or after the
+/// `TerrainCollisionSystem`. Don't read it from a frame system (see
+/// `AppBuilder::add_frame_systems`); when several ticks run in one frame,
+/// it would only see the contacts from the last of them.
+pub struct TerrainContacts {
+    pub contacts: Vec<TerrainContact>,
+}
+
+impl ::AutoResource for TerrainContacts {
+    fn new(_world: &mut specs::World) -> TerrainContacts {
+        TerrainContacts { contacts: Vec::new() }
+    }
+}
+
+/// Stops bodies with a `Collider` from passing through solid cells of the globe
+/// they're moving relative to.
+///
+/// Each body's motion for the coming tick is swept through the globe, and if it would
+/// hit something then the body is moved up to the surface and bounces off it according
+/// to its restitution and friction, and then only moves for whatever is left of the tick
+/// after it reached the surface. Bodies that are already stuck inside the terrain
+/// (e.g. because someone built on top of them) are pushed back up out of it.
+///
+/// Should run after anything that changes velocities (like `GravitySystem`),
+/// and before the `VelocitySystem` actually moves things.
+pub struct TerrainCollisionSystem {
+    log: Logger,
+}
+
+impl TerrainCollisionSystem {
+    pub fn new(parent_log: &Logger, world: &mut specs::World) -> TerrainCollisionSystem {
+        use ::AutoResource;
+
+        TerrainContacts::ensure(world);
+
+        TerrainCollisionSystem {
+            log: parent_log.new(o!()),
+        }
+    }
+}
+
+impl<'a> specs::System<'a> for TerrainCollisionSystem {
+    type SystemData = (
+        Fetch<'a, TimeDeltaResource>,
+        Entities<'a>,
+        ReadStorage<'a, Globe>,
+        ReadStorage<'a, Collider>,
+        WriteStorage<'a, Spatial>,
+        WriteStorage<'a, Velocity>,
+        FetchMut<'a, TerrainContacts>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        use specs::Join;
+
+        let (
+            dt,
+            entities,
+            globes,
+            colliders,
+            mut spatials,
+            mut velocities,
+            mut terrain_contacts,
+        ) = data;
+
+        terrain_contacts.contacts.clear();
+
+        for (entity, collider, spatial, velocity) in (&*entities, &colliders, &mut spatials, &mut velocities).join() {
+            // We only know how to collide with the globe we're moving relative to.
+            let globe_entity = match spatial.parent_entity() {
+                Some(parent_entity) => parent_entity,
+                None => continue,
+            };
+            let globe = match globes.get(globe_entity) {
+                Some(globe) => globe,
+                None => continue,
+            };
+
+            let mut transform = spatial.local_transform();
+            let mut pos = Pt3::from_coordinates(transform.translation.vector);
+            let local_velocity = velocity.local_velocity();
+
+            // Also find how much of the tick the body spent getting to the surface.
+            let maybe_hit = match escape_terrain(globe, pos, collider.radius) {
+                Some((escaped_pos, cell)) => {
+                    trace!(self.log, "Pushed body out of the terrain"; "cell" => format!("{:?}", cell));
+                    pos = escaped_pos;
+                    Some((cell, escaped_pos.coords.normalize(), 0.0))
+                }
+                None => {
+                    // See if we'd hit anything on our way to where we're going next.
+                    let motion = local_velocity * dt.0;
+                    let distance = motion.norm();
+                    if distance > 0.0 {
+                        match globe.raycast(pos, motion, distance + collider.radius) {
+                            RaycastResult::Hit(hit) => {
+                                let contact_distance = (hit.distance - collider.radius).max(0.0);
+                                let fraction_of_tick = (contact_distance / distance).min(1.0);
+                                pos += motion * fraction_of_tick;
+                                Some((hit.pos, hit.normal, dt.0 * fraction_of_tick))
+                            }
+                            // Let things fly into chunks that aren't loaded;
+                            // they'll get pushed back out once they are.
+                            RaycastResult::Unloaded { .. } | RaycastResult::Miss => None,
+                        }
+                    } else {
+                        None
+                    }
+                }
+            };
+
+            let (cell, normal, seconds_before_contact) = match maybe_hit {
+                Some(hit) => hit,
+                None => continue,
+            };
+            let (new_velocity, impact_speed) = bounce(local_velocity, normal, collider, dt.0);
+            velocity.set_local_velocity(new_velocity);
+            // The `VelocitySystem` is about to move the body for the whole tick,
+            // but it has already used up some of that getting to the surface.
+            // Back it up by as far as its new velocity would take it in that time,
+            // so that it ends up where it would if it only moved for the rest of the tick.
+            pos -= new_velocity * seconds_before_contact;
+            transform.translation.vector = pos.coords;
+            spatial.set_local_transform(transform);
+
+            terrain_contacts.contacts.push(TerrainContact {
+                entity: entity,
+                globe_entity: globe_entity,
+                cell: cell,
+                normal: normal,
+                impact_speed: impact_speed,
+            });
+        }
+    }
+}
+
+// If the given point is inside a solid cell, find the nearest point straight
+// above it that leaves the body clear of the terrain, and the last solid cell
+// it had to pass through to get there.
+fn escape_terrain(globe: &Globe, pos: Pt3, radius: f64) -> Option<(Pt3, GridPoint3)> {
+    let spec = globe.spec();
+    let mut cell_pos = match spec.approx_cell_containing_point(pos) {
+        Some(cell_pos) => cell_pos,
+        // Below the floor; there's nowhere sensible to put it.
+        None => return None,
+    };
+    let is_solid = |cell_pos: GridPoint3| {
+        globe
+            .maybe_non_authoritative_cell(cell_pos)
+            .map(|cell| cell.material.is_solid())
+            // Assume unloaded chunks are empty.
+            .unwrap_or(false)
+    };
+    if !is_solid(cell_pos) {
+        return None;
+    }
+    let mut solid_cell_pos = cell_pos;
+    for _ in 0..MAX_CELLS_TO_ESCAPE {
+        if !is_solid(cell_pos) {
+            break;
+        }
+        solid_cell_pos = cell_pos;
+        cell_pos.z += 1;
+    }
+    // Sit the body on the bottom of the first empty cell.
+    let clear_radius = spec.floor_radius + cell_pos.z as f64 * spec.block_height + radius;
+    let escaped_pos = Pt3::from_coordinates(pos.coords.normalize() * clear_radius);
+    Some((escaped_pos, solid_cell_pos))
+}
+
+// Work out how a body should rebound off a surface with the given normal,
+// given that it will be in contact with it for `dt` seconds.
+//
+// Returns the body's new velocity, and how fast it was moving into the surface.
+fn bounce(velocity: Vec3, normal: Vec3, collider: &Collider, dt: TimeDelta) -> (Vec3, f64) {
+    let normal_speed = velocity.dot(&normal);
+    if normal_speed >= 0.0 {
+        // Already moving away from the surface.
+        return (velocity, 0.0);
+    }
+    let impact_speed = -normal_speed;
+    let tangential_velocity = velocity - normal * normal_speed;
+    let restitution = if impact_speed < MIN_BOUNCE_SPEED {
+        0.0
+    } else {
+        collider.restitution
+    };
+    // Friction is how much speed is lost per second, so that bodies sliding
+    // along the ground slow down at the same rate regardless of tick length.
+    let tangential_speed_kept = (1.0 - collider.friction).max(0.0).powf(dt);
+    let new_velocity = normal * (impact_speed * restitution) + tangential_velocity * tangential_speed_kept;
+    (new_velocity, impact_speed)
+}
+
+impl AutoSystem for TerrainCollisionSystem {
+    fn name(&self) -> &'static str {
+        "terrain_collision"
+    }
+
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec!["gravity"]
+    }
+
+    fn init(&mut self, world: &mut specs::World) {
+        ensure_component_registered::<Globe>(world);
+        ensure_component_registered::<Collider>(world);
+        ensure_component_registered::<Spatial>(world);
+        ensure_component_registered::<Velocity>(world);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use na;
+    use rand::{XorShiftRng, SeedableRng};
+    use slog;
+    use specs::RunNow;
+
+    use super::*;
+    use physics::VelocitySystem;
+
+    #[test]
+    fn bounce_reflects_and_slows() {
+        let collider = Collider::new(0.1, 0.5, 0.25);
+        let up = Vec3::new(0.0, 0.0, 1.0);
+        let (new_velocity, impact_speed) = bounce(Vec3::new(4.0, 0.0, -2.0), up, &collider, 1.0);
+        assert_eq!(impact_speed, 2.0);
+        assert!((new_velocity - Vec3::new(3.0, 0.0, 1.0)).norm() < 1e-9);
+    }
+
+    #[test]
+    fn slow_impacts_dont_bounce() {
+        let collider = Collider::new(0.1, 1.0, 0.0);
+        let up = Vec3::new(0.0, 0.0, 1.0);
+        let (new_velocity, _) = bounce(Vec3::new(0.0, 0.0, -0.1), up, &collider, 1.0);
+        assert_eq!(new_velocity, Vec3::new(0.0, 0.0, 0.0));
+        // Moving away from the surface is left alone.
+        let (new_velocity, impact_speed) = bounce(up, up, &collider, 1.0);
+        assert_eq!(new_velocity, up);
+        assert_eq!(impact_speed, 0.0);
+    }
+
+    #[test]
+    fn friction_depends_on_how_long_bodies_slide() {
+        let collider = Collider::new(0.1, 0.0, 0.75);
+        let up = Vec3::new(0.0, 0.0, 1.0);
+        let sliding = Vec3::new(4.0, 0.0, -0.1);
+        let (after_one_second, _) = bounce(sliding, up, &collider, 1.0);
+        assert!((after_one_second - Vec3::new(1.0, 0.0, 0.0)).norm() < 1e-9);
+
+        // Two short ticks slow it down as much as one long one.
+        let (after_half_a_second, _) = bounce(sliding, up, &collider, 0.5);
+        let (after_two_halves, _) = bounce(after_half_a_second - up * 0.1, up, &collider, 0.5);
+        assert!((after_two_halves - after_one_second).norm() < 1e-9);
+
+        // No friction means no slowing down, and full friction stops it dead.
+        let (slippery, _) = bounce(sliding, up, &Collider::new(0.1, 0.0, 0.0), 0.1);
+        assert!((slippery - Vec3::new(4.0, 0.0, 0.0)).norm() < 1e-9);
+        let (sticky, _) = bounce(sliding, up, &Collider::new(0.1, 0.0, 1.0), 0.1);
+        assert!(sticky.norm() < 1e-9);
+    }
+
+    // A body straight above some dry land, and the distance
+    // from the center of the globe to the surface of that land.
+    struct BodyAboveLand {
+        world: specs::World,
+        collision_sys: TerrainCollisionSystem,
+        velocity_sys: VelocitySystem,
+        body_entity: Entity,
+        ground_radius: f64,
+        collider_radius: f64,
+    }
+
+    impl BodyAboveLand {
+        fn new(collider: Collider, height_above_ground: f64, downward_speed: f64) -> BodyAboveLand {
+            let log = slog::Logger::root(slog::Discard, o!("pk_version" => env!("CARGO_PKG_VERSION")));
+            let mut world = specs::World::new();
+            world.add_resource(TimeDeltaResource(0.0));
+            let mut collision_sys = TerrainCollisionSystem::new(&log, &mut world);
+            collision_sys.init(&mut world);
+            let mut velocity_sys = VelocitySystem::new(&log);
+            velocity_sys.init(&mut world);
+
+            let mut globe = Globe::new_example();
+            let spec = globe.spec();
+            let mut rng = XorShiftRng::from_seed([13, 14, 15, 16]);
+            let air_pos = globe
+                .air_above_random_surface_dry_land(&mut rng, 5, 5, 5)
+                .expect("Should have been able to find some land");
+            let center = spec.cell_center_center(air_pos);
+            for cell_pos in globe.cells_in_sphere(center, 8.0).unloaded {
+                let chunk_origin = globe.origin_of_chunk_owning(cell_pos);
+                globe.ensure_chunk_present(chunk_origin);
+            }
+            let up = center.coords.normalize();
+            let ground_radius = spec.floor_radius + air_pos.z as f64 * spec.block_height;
+            let collider_radius = collider.radius;
+            let body_pos = up * (ground_radius + collider_radius + height_above_ground);
+
+            let globe_entity = world.create_entity().with(globe).build();
+            let body_entity = world
+                .create_entity()
+                .with(Spatial::new(globe_entity, Iso3::new(body_pos, na::zero())))
+                .with(Velocity::new(-up * downward_speed))
+                .with(collider)
+                .build();
+            BodyAboveLand {
+                world: world,
+                collision_sys: collision_sys,
+                velocity_sys: velocity_sys,
+                body_entity: body_entity,
+                ground_radius: ground_radius,
+                collider_radius: collider_radius,
+            }
+        }
+
+        fn tick(&mut self, dt: TimeDelta) {
+            self.world.write_resource::<TimeDeltaResource>().0 = dt;
+            self.collision_sys.run_now(&self.world.res);
+            self.velocity_sys.run_now(&self.world.res);
+            self.world.maintain();
+        }
+
+        // How far the body is from the center of the globe.
+        fn radius(&self) -> f64 {
+            let spatials = self.world.read::<Spatial>();
+            spatials.get(self.body_entity).unwrap().local_transform().translation.vector.norm()
+        }
+    }
+
+    #[test]
+    fn bodies_bounce_back_up_for_the_rest_of_the_tick() {
+        let collider = Collider::new(0.1, 1.0, 0.0);
+        let mut body = BodyAboveLand::new(collider, 0.5, 1.0);
+        // Hit the ground half way through the tick, and then
+        // spend the other half bouncing back up again.
+        body.tick(1.0);
+        let expected_radius = body.ground_radius + body.collider_radius + 0.5;
+        assert!((body.radius() - expected_radius).abs() < 0.01);
+        assert_eq!(body.world.read_resource::<TerrainContacts>().contacts.len(), 1);
+    }
+
+    #[test]
+    fn bodies_that_dont_bounce_stop_at_the_surface() {
+        let collider = Collider::new(0.1, 0.0, 0.0);
+        let mut body = BodyAboveLand::new(collider, 0.25, 1.0);
+        body.tick(1.0);
+        let expected_radius = body.ground_radius + body.collider_radius;
+        assert!((body.radius() - expected_radius).abs() < 0.01);
+        // And it stays there.
+        body.tick(1.0);
+        assert!((body.radius() - expected_radius).abs() < 0.01);
+    }
+
+    #[test]
+    fn buried_bodies_are_pushed_out() {
+        let collider = Collider::new(0.1, 0.0, 0.0);
+        // Start it a cell and a half under ground, not moving at all.
+        let mut body = BodyAboveLand::new(collider, -1.5, 0.0);
+        body.tick(0.1);
+        let expected_radius = body.ground_radius + body.collider_radius;
+        assert!((body.radius() - expected_radius).abs() < 1e-6);
+        assert_eq!(body.world.read_resource::<TerrainContacts>().contacts.len(), 1);
+    }
+}
@@ -6,6 +6,7 @@ use slog::Logger;
 use types::*;
 use super::Velocity;
 use Spatial;
+use auto_system::{AutoSystem, ensure_component_registered};
 
 pub struct VelocitySystem {
     _log: Logger,
@@ -42,3 +43,18 @@ impl<'a> specs::System<'a> for VelocitySystem {
         }
     }
 }
+
+impl AutoSystem for VelocitySystem {
+    fn name(&self) -> &'static str {
+        "velocity"
+    }
+
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec!["terrain_collision"]
+    }
+
+    fn init(&mut self, world: &mut specs::World) {
+        ensure_component_registered::<Spatial>(world);
+        ensure_component_registered::<Velocity>(world);
+    }
+}
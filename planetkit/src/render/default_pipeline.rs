@@ -1,6 +1,6 @@
 use gfx;
 
-use types::Pt3;
+use types::{Pt3, Vec3};
 
 // Pretty basic pipeline currently used for terrain.
 //
@@ -12,6 +12,11 @@ gfx_vertex_struct!(_Vertex {
     a_pos: [f32; 4] = "a_pos",
     tex_coord: [f32; 2] = "a_tex_coord",
     a_color: [f32; 3] = "a_color",
+    // Zero for things that shouldn't be lit, like debug markers.
+    a_normal: [f32; 3] = "a_normal",
+    // How much ambient light is blocked from reaching this vertex,
+    // from 0 (none) to 1 (all of it).
+    a_occlusion: f32 = "a_occlusion",
 });
 
 pub type Vertex = _Vertex;
@@ -22,18 +27,48 @@ impl Vertex {
             a_pos: [pos[0], pos[1], pos[2], 1.0],
             a_color: color,
             tex_coord: [0.0, 0.0],
+            a_normal: [0.0, 0.0, 0.0],
+            a_occlusion: 0.0,
         }
     }
 
     pub fn new_from_pt3(pos: Pt3, color: [f32; 3]) -> Vertex {
         Vertex::new([pos[0] as f32, pos[1] as f32, pos[2] as f32], color)
     }
+
+    /// Light the vertex as if it were on a surface facing the given direction.
+    ///
+    /// Expects a unit vector.
+    pub fn with_normal(mut self, normal: Vec3) -> Vertex {
+        self.a_normal = [normal[0] as f32, normal[1] as f32, normal[2] as f32];
+        self
+    }
+
+    /// Darken the vertex to fake ambient light being blocked
+    /// by things around it.
+    pub fn with_occlusion(mut self, occlusion: f32) -> Vertex {
+        self.a_occlusion = occlusion;
+        self
+    }
+
+    pub fn pos(&self) -> Pt3 {
+        Pt3::new(self.a_pos[0] as f64, self.a_pos[1] as f64, self.a_pos[2] as f64)
+    }
+
+    pub fn normal(&self) -> Vec3 {
+        Vec3::new(self.a_normal[0] as f64, self.a_normal[1] as f64, self.a_normal[2] as f64)
+    }
+
+    pub fn occlusion(&self) -> f32 {
+        self.a_occlusion
+    }
 }
 
 gfx_pipeline!(
     pipe {
         vbuf: gfx::VertexBuffer<Vertex> = (),
         u_model_view_proj: gfx::Global<[[f32; 4]; 4]> = "u_model_view_proj",
+        u_model_view: gfx::Global<[[f32; 4]; 4]> = "u_model_view",
         t_color: gfx::TextureSampler<[f32; 4]> = "t_color",
         out_color: gfx::RenderTarget<gfx::format::Srgba8> = "o_color",
         out_depth: gfx::DepthTarget<gfx::format::DepthStencil> =
@@ -0,0 +1,159 @@
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use super::ProtoMesh;
+
+/// File formats that a `ProtoMesh` can be written out as
+/// for inspecting in other tools.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MeshFormat {
+    /// Wavefront OBJ, with vertex colours tacked onto the end of each
+    /// vertex position. Not part of the original format, but most tools
+    /// that read OBJ files (e.g. Blender and MeshLab) understand it.
+    Obj,
+    /// ASCII Stanford PLY.
+    Ply,
+}
+
+impl MeshFormat {
+    /// Guess the format from a file's extension.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<MeshFormat> {
+        let extension = path.as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+        match extension.as_ref().map(|extension| extension.as_str()) {
+            Some("obj") => Some(MeshFormat::Obj),
+            Some("ply") => Some(MeshFormat::Ply),
+            _ => None,
+        }
+    }
+}
+
+/// Write out a mesh's triangles, including vertex colours and normals,
+/// in the given format.
+pub fn write_mesh<W: Write>(mesh: &ProtoMesh, format: MeshFormat, writer: &mut W) -> io::Result<()> {
+    match format {
+        MeshFormat::Obj => write_obj(mesh, writer),
+        MeshFormat::Ply => write_ply(mesh, writer),
+    }
+}
+
+fn write_obj<W: Write>(mesh: &ProtoMesh, writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "# Exported from PlanetKit")?;
+    for vertex in &mesh.vertexes {
+        let pos = vertex.a_pos;
+        let color = vertex.a_color;
+        writeln!(
+            writer,
+            "v {} {} {} {} {} {}",
+            pos[0], pos[1], pos[2],
+            color[0], color[1], color[2]
+        )?;
+    }
+    for vertex in &mesh.vertexes {
+        let normal = vertex.a_normal;
+        writeln!(writer, "vn {} {} {}", normal[0], normal[1], normal[2])?;
+    }
+    // OBJ indexes count from 1.
+    for triangle in mesh.indexes.chunks(3) {
+        writeln!(
+            writer,
+            "f {a}//{a} {b}//{b} {c}//{c}",
+            a = triangle[0] + 1,
+            b = triangle[1] + 1,
+            c = triangle[2] + 1
+        )?;
+    }
+    Ok(())
+}
+
+fn write_ply<W: Write>(mesh: &ProtoMesh, writer: &mut W) -> io::Result<()> {
+    writeln!(writer, "ply")?;
+    writeln!(writer, "format ascii 1.0")?;
+    writeln!(writer, "comment Exported from PlanetKit")?;
+    writeln!(writer, "element vertex {}", mesh.vertexes.len())?;
+    for property in &["x", "y", "z", "nx", "ny", "nz"] {
+        writeln!(writer, "property float {}", property)?;
+    }
+    for property in &["red", "green", "blue"] {
+        writeln!(writer, "property uchar {}", property)?;
+    }
+    writeln!(writer, "element face {}", mesh.indexes.len() / 3)?;
+    writeln!(writer, "property list uchar uint vertex_indices")?;
+    writeln!(writer, "end_header")?;
+    for vertex in &mesh.vertexes {
+        let pos = vertex.a_pos;
+        let normal = vertex.a_normal;
+        let color = vertex.a_color;
+        writeln!(
+            writer,
+            "{} {} {} {} {} {} {} {} {}",
+            pos[0], pos[1], pos[2],
+            normal[0], normal[1], normal[2],
+            color_byte(color[0]), color_byte(color[1]), color_byte(color[2])
+        )?;
+    }
+    for triangle in mesh.indexes.chunks(3) {
+        writeln!(writer, "3 {} {} {}", triangle[0], triangle[1], triangle[2])?;
+    }
+    Ok(())
+}
+
+fn color_byte(channel: f32) -> u8 {
+    (channel.max(0.0).min(1.0) * 255.0).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use render::Vertex;
+    use types::Vec3;
+
+    fn make_triangle() -> ProtoMesh {
+        let up = Vec3::new(0.0, 0.0, 1.0);
+        ProtoMesh::new(
+            vec![
+                Vertex::new([0.0, 0.0, 0.0], [1.0, 0.0, 0.0]).with_normal(up),
+                Vertex::new([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]).with_normal(up),
+                Vertex::new([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]).with_normal(up),
+            ],
+            vec![0, 1, 2],
+        )
+    }
+
+    #[test]
+    fn obj_has_colors_and_one_based_faces() {
+        let mut out: Vec<u8> = Vec::new();
+        write_mesh(&make_triangle(), MeshFormat::Obj, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(lines.contains(&"v 1 0 0 0 1 0"));
+        assert!(lines.contains(&"vn 0 0 1"));
+        assert!(lines.contains(&"f 1//1 2//2 3//3"));
+    }
+
+    #[test]
+    fn ply_header_matches_body() {
+        let mut out: Vec<u8> = Vec::new();
+        write_mesh(&make_triangle(), MeshFormat::Ply, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(lines.contains(&"element vertex 3"));
+        assert!(lines.contains(&"element face 1"));
+        let end_of_header = lines.iter().position(|line| *line == "end_header").unwrap();
+        let body = &lines[(end_of_header + 1)..];
+        assert_eq!(body.len(), 4);
+        assert_eq!(body[0], "0 0 0 0 0 1 255 0 0");
+        assert_eq!(body[3], "3 0 1 2");
+    }
+
+    #[test]
+    fn format_from_extension() {
+        assert_eq!(MeshFormat::from_path("globe.OBJ"), Some(MeshFormat::Obj));
+        assert_eq!(MeshFormat::from_path("some/dir/chunk.ply"), Some(MeshFormat::Ply));
+        assert_eq!(MeshFormat::from_path("globe.stl"), None);
+        assert_eq!(MeshFormat::from_path("globe"), None);
+    }
+}
@@ -75,6 +75,7 @@ impl<R: gfx::Resources> Mesh<R> {
         let data = pipe::Data {
             vbuf: vbuf.clone(),
             u_model_view_proj: [[0.0; 4]; 4],
+            u_model_view: [[0.0; 4]; 4],
             t_color: (texture_view, factory.create_sampler(sinfo)),
             out_color: output_color,
             out_depth: output_stencil,
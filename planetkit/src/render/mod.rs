@@ -6,6 +6,7 @@ mod proto_mesh;
 mod encoder_channel;
 mod visual;
 mod axes_mesh;
+mod export;
 
 pub use self::system::System;
 pub use self::default_pipeline::Vertex;
@@ -15,3 +16,4 @@ pub use self::proto_mesh::ProtoMesh;
 pub use self::encoder_channel::EncoderChannel;
 pub use self::visual::Visual;
 pub use self::axes_mesh::make_axes_mesh;
+pub use self::export::{MeshFormat, write_mesh};
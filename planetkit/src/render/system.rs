@@ -162,6 +162,9 @@ impl<R: gfx::Resources, C: gfx::CommandBuffer<R>> System<R, C> {
 
             let mesh = mesh_repo.get_mut(mesh_pointer);
             mesh.data_mut().u_model_view_proj = model_view_projection;
+            // Normals only need rotating into view space, but this is
+            // what the shader expects; it ignores the translation.
+            mesh.data_mut().u_model_view = model_for_camera_controllers;
             encoder.draw(mesh.slice(), &self.pso, mesh.data());
         }
 
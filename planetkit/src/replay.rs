@@ -0,0 +1,389 @@
+use std::collections::VecDeque;
+use std::io;
+use std::io::{BufRead, Write};
+
+use piston::input::Input;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+use specs;
+
+use net::{PeerId, RecvMessage};
+use input_map::InputMap;
+
+/// Everything that needs to be known before replaying a recording
+/// can start. Written as the first line of every recording.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RecordingHeader {
+    /// Replays are only deterministic if they run at the same
+    /// tick rate they were recorded at.
+    pub ticks_per_second: f64,
+    /// Inputs are recorded as raw button presses, so they have to be
+    /// replayed through the same bindings to trigger the same actions.
+    #[serde(default)]
+    pub input_map: InputMap,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum RecordedEventKind {
+    /// Seed chosen for generating a world; see `Replay::choose_seed`.
+    Seed(u32),
+    /// Input event that was passed to every `InputAdapter`.
+    Input(Input),
+    /// Game message received from a network peer, in whatever
+    /// form the game's message type serializes to.
+    Message {
+        source: PeerId,
+        game_message: serde_json::Value,
+    },
+}
+
+/// Something from outside the simulation that affected it,
+/// stamped with the simulation tick during which it took effect.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RecordedEvent {
+    pub tick: u64,
+    pub kind: RecordedEventKind,
+}
+
+/// A recording read back in from a file; see `Recorder` for the format.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recording {
+    pub header: RecordingHeader,
+    pub events: Vec<RecordedEvent>,
+}
+
+impl Recording {
+    pub fn read<R: BufRead>(reader: R) -> io::Result<Recording> {
+        let mut lines = reader.lines();
+        let header = match lines.next() {
+            Some(line) => serde_json::from_str(&line?)?,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Recording is missing its header",
+                ))
+            }
+        };
+        let mut events = Vec::new();
+        for line in lines {
+            let line = line?;
+            // Tolerate trailing blank lines from hand-edited files.
+            if line.trim().is_empty() {
+                continue;
+            }
+            events.push(serde_json::from_str(&line)?);
+        }
+        Ok(Recording {
+            header: header,
+            events: events,
+        })
+    }
+}
+
+/// Writes events out as they happen, one JSON object per line,
+/// starting with a `RecordingHeader`.
+///
+/// Events are written as they arrive rather than all at the end, so that
+/// a recording of a session that crashed is still useful.
+pub struct Recorder {
+    writer: Box<Write + Send + Sync>,
+}
+
+impl Recorder {
+    pub fn new<W>(header: &RecordingHeader, writer: W) -> io::Result<Recorder>
+    where
+        W: Write + Send + Sync + 'static,
+    {
+        let mut recorder = Recorder { writer: Box::new(writer) };
+        recorder.write_line(header)?;
+        Ok(recorder)
+    }
+
+    fn write_line<T: Serialize>(&mut self, value: &T) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, value)?;
+        self.writer.write_all(b"\n")
+    }
+}
+
+// Events left to replay, split up by where they get fed back in.
+struct Replayer {
+    seeds: VecDeque<u32>,
+    inputs: VecDeque<(u64, Input)>,
+    messages: VecDeque<(u64, PeerId, serde_json::Value)>,
+}
+
+impl Replayer {
+    fn new(recording: Recording) -> Replayer {
+        let mut replayer = Replayer {
+            seeds: VecDeque::new(),
+            inputs: VecDeque::new(),
+            messages: VecDeque::new(),
+        };
+        for event in recording.events {
+            match event.kind {
+                RecordedEventKind::Seed(seed) => replayer.seeds.push_back(seed),
+                RecordedEventKind::Input(input) => replayer.inputs.push_back((event.tick, input)),
+                RecordedEventKind::Message { source, game_message } => {
+                    replayer.messages.push_back((event.tick, source, game_message))
+                }
+            }
+        }
+        replayer
+    }
+
+    fn is_finished(&self) -> bool {
+        self.seeds.is_empty() && self.inputs.is_empty() && self.messages.is_empty()
+    }
+}
+
+enum Mode {
+    Live,
+    Recording(Recorder),
+    Replaying(Replayer),
+}
+
+/// `World`-global resource for recording everything that comes into
+/// the simulation from outside (input events, network messages, and world seeds)
+/// so that a session can be replayed exactly, e.g. to reproduce a bug.
+///
+/// This relies on the simulation only ever advancing in fixed-length ticks,
+/// and on systems only using randomness derived from the world seed.
+///
+/// Use `AppBuilder::record_to` or `AppBuilder::replay_from` to turn this on.
+pub struct Replay {
+    mode: Mode,
+}
+
+impl Replay {
+    pub fn start_recording(&mut self, recorder: Recorder) {
+        self.mode = Mode::Recording(recorder);
+    }
+
+    pub fn start_replaying(&mut self, recording: Recording) {
+        self.mode = Mode::Replaying(Replayer::new(recording));
+    }
+
+    pub fn is_recording(&self) -> bool {
+        match self.mode {
+            Mode::Recording(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        match self.mode {
+            Mode::Replaying(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Choose the seed to generate a world from.
+    ///
+    /// When replaying, this returns the seed that was chosen at the same point
+    /// in the recording, and otherwise the given seed (recording it if recording).
+    pub fn choose_seed(&mut self, tick: u64, seed: u32) -> io::Result<u32> {
+        match self.mode {
+            Mode::Live => Ok(seed),
+            Mode::Recording(ref mut recorder) => {
+                recorder.write_line(&RecordedEvent {
+                    tick: tick,
+                    kind: RecordedEventKind::Seed(seed),
+                })?;
+                Ok(seed)
+            }
+            Mode::Replaying(ref mut replayer) => Ok(replayer.seeds.pop_front().unwrap_or(seed)),
+        }
+    }
+
+    pub fn record_input(&mut self, tick: u64, input: &Input) -> io::Result<()> {
+        if let Mode::Recording(ref mut recorder) = self.mode {
+            recorder.write_line(&RecordedEvent {
+                tick: tick,
+                kind: RecordedEventKind::Input(input.clone()),
+            })?;
+        }
+        Ok(())
+    }
+
+    pub fn record_message<G: Serialize>(&mut self, tick: u64, message: &RecvMessage<G>) -> io::Result<()> {
+        if let Mode::Recording(ref mut recorder) = self.mode {
+            recorder.write_line(&RecordedEvent {
+                tick: tick,
+                kind: RecordedEventKind::Message {
+                    source: message.source,
+                    game_message: serde_json::to_value(&message.game_message)?,
+                },
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Take the next recorded input event that should be handled
+    /// before simulating the given tick, if any.
+    pub fn next_input(&mut self, tick: u64) -> Option<Input> {
+        if let Mode::Replaying(ref mut replayer) = self.mode {
+            let is_due = replayer.inputs.front().map_or(false, |&(input_tick, _)| input_tick <= tick);
+            if is_due {
+                return replayer.inputs.pop_front().map(|(_, input)| input);
+            }
+        }
+        None
+    }
+
+    /// Take the next recorded message that should be received
+    /// while simulating the given tick, if any.
+    pub fn next_message<G: DeserializeOwned>(&mut self, tick: u64) -> Option<io::Result<RecvMessage<G>>> {
+        if let Mode::Replaying(ref mut replayer) = self.mode {
+            let is_due = replayer.messages.front().map_or(false, |&(message_tick, _, _)| message_tick <= tick);
+            if is_due {
+                return replayer.messages.pop_front().map(|(_, source, game_message)| {
+                    serde_json::from_value(game_message)
+                        .map(|game_message| RecvMessage {
+                            source: source,
+                            game_message: game_message,
+                        })
+                        .map_err(io::Error::from)
+                });
+            }
+        }
+        None
+    }
+
+    /// If everything in the recording has been replayed,
+    /// go back to taking live input, and return true.
+    pub fn finish_if_done(&mut self) -> bool {
+        let is_done = match self.mode {
+            Mode::Replaying(ref replayer) => replayer.is_finished(),
+            _ => false,
+        };
+        if is_done {
+            self.mode = Mode::Live;
+        }
+        is_done
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        if let Mode::Recording(ref mut recorder) = self.mode {
+            recorder.writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl ::AutoResource for Replay {
+    fn new(_world: &mut specs::World) -> Replay {
+        Replay { mode: Mode::Live }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    use piston::input::{Button, ButtonArgs, ButtonState, Key};
+
+    use super::*;
+
+    // Lets the test read back what a `Recorder` wrote
+    // after handing ownership of the writer to it.
+    #[derive(Clone)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn press(key: Key) -> Input {
+        Input::Button(ButtonArgs {
+            state: ButtonState::Press,
+            button: Button::Keyboard(key),
+            scancode: None,
+        })
+    }
+
+    #[test]
+    fn recording_round_trips() {
+        let buffer = SharedBuffer(Arc::new(Mutex::new(Vec::new())));
+        let mut input_map = InputMap::new();
+        input_map.bind("step_forward", Button::Keyboard(Key::W));
+        let header = RecordingHeader {
+            ticks_per_second: 30.0,
+            input_map: input_map,
+        };
+        let mut replay = Replay { mode: Mode::Live };
+        replay.start_recording(Recorder::new(&header, buffer.clone()).unwrap());
+        assert_eq!(replay.choose_seed(0, 42).unwrap(), 42);
+        replay.record_input(3, &press(Key::W)).unwrap();
+        replay
+            .record_message(5, &RecvMessage {
+                source: PeerId(1),
+                game_message: "hello".to_string(),
+            })
+            .unwrap();
+        replay.flush().unwrap();
+
+        let bytes = buffer.0.lock().unwrap().clone();
+        let recording = Recording::read(&bytes[..]).unwrap();
+        assert_eq!(recording.header, header);
+        assert_eq!(recording.events.len(), 3);
+        assert_eq!(recording.events[1], RecordedEvent {
+            tick: 3,
+            kind: RecordedEventKind::Input(press(Key::W)),
+        });
+    }
+
+    #[test]
+    fn recordings_without_bindings_can_still_be_read() {
+        let recording = Recording::read(&b"{\"ticks_per_second\":60.0}\n"[..]).unwrap();
+        assert_eq!(recording.header.input_map, InputMap::new());
+    }
+
+    #[test]
+    fn events_are_replayed_on_their_ticks() {
+        let recording = Recording {
+            header: RecordingHeader {
+                ticks_per_second: 60.0,
+                input_map: InputMap::new(),
+            },
+            events: vec![
+                RecordedEvent { tick: 0, kind: RecordedEventKind::Seed(7) },
+                RecordedEvent { tick: 2, kind: RecordedEventKind::Input(press(Key::A)) },
+                RecordedEvent { tick: 2, kind: RecordedEventKind::Input(press(Key::D)) },
+                RecordedEvent {
+                    tick: 4,
+                    kind: RecordedEventKind::Message {
+                        source: PeerId(3),
+                        game_message: serde_json::to_value(&"hi".to_string()).unwrap(),
+                    },
+                },
+            ],
+        };
+        let mut replay = Replay { mode: Mode::Live };
+        replay.start_replaying(recording);
+
+        // Replayed seed wins over whatever the game asks for.
+        assert_eq!(replay.choose_seed(0, 1).unwrap(), 7);
+
+        assert_eq!(replay.next_input(1), None);
+        assert_eq!(replay.next_input(2), Some(press(Key::A)));
+        assert_eq!(replay.next_input(2), Some(press(Key::D)));
+        assert_eq!(replay.next_input(2), None);
+
+        assert!(replay.next_message::<String>(3).is_none());
+        let message = replay.next_message::<String>(4).unwrap().unwrap();
+        assert_eq!(message.source, PeerId(3));
+        assert_eq!(message.game_message, "hi");
+
+        assert!(replay.finish_if_done());
+        assert!(!replay.is_replaying());
+    }
+}
@@ -23,7 +23,15 @@ pub fn populate_world(world: &mut specs::World) {
 }
 
 pub fn create_simple_globe_now(world: &mut specs::World) -> specs::Entity {
-    let globe = globe::Globe::new_earth_scale_example();
+    use auto_resource::AutoResource;
+
+    // Let a replay override the seed, so we generate the same world it was recorded in.
+    let mut spec = globe::Spec::new_earth_scale_example();
+    let tick = ::TickCounter::ensure(world).ticks;
+    spec.seed = ::Replay::ensure(world)
+        .choose_seed(tick, spec.seed)
+        .expect("Couldn't record world seed");
+    let globe = globe::Globe::new(spec);
     world
         .create_entity()
         .with(globe)
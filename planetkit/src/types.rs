@@ -15,6 +15,13 @@ pub type Rot3 = na::Rotation3<f64>;
 pub type Iso3 = na::Isometry3<f64>;
 
 pub type TimeDelta = f64;
+
+/// How much time the systems currently being dispatched should account for.
+///
+/// While systems added with `AppBuilder::add_systems` are running, this is
+/// always the length of one fixed-timestep tick. While systems added with
+/// `AppBuilder::add_frame_systems` are running, it is the real time since
+/// the last frame.
 pub struct TimeDeltaResource(pub TimeDelta);
 
 pub type Mat4 = na::Matrix4<f64>;
@@ -31,7 +31,12 @@ fn add_systems(
 }
 
 fn create_entities(world: &mut specs::World) {
+    use pk::AutoResource;
     use pk::cell_dweller::ActiveCellDweller;
+    use pk::net::NodeResource;
+
+    // There's nobody else playing, so we're in charge of the world.
+    NodeResource::ensure(world).is_master = true;
 
     // TODO: this should all actually be done by a game system,
     // rather than in the app builder. Because, e.g. if you change levels,
@@ -39,13 +39,20 @@ pub fn create_now(world: &mut specs::World, globe_entity: specs::Entity) -> spec
     let mut shepherd_visual = render::Visual::new_empty();
     shepherd_visual.proto_mesh = Some(render::make_axes_mesh());
 
+    let mut shepherd_cd = cell_dweller::CellDweller::new(
+        shepherd_pos,
+        grid::Dir::default(),
+        globe_spec,
+        Some(globe_entity),
+    );
+    // Sheep are forever wandering into the water.
+    shepherd_cd.set_movement_profile(cell_dweller::MovementProfile {
+        can_swim: true,
+        ..Default::default()
+    });
+
     let shepherd_entity = world.create_entity()
-        .with(cell_dweller::CellDweller::new(
-            shepherd_pos,
-            grid::Dir::default(),
-            globe_spec,
-            Some(globe_entity),
-        ))
+        .with(shepherd_cd)
         .with(shepherd_visual)
         // The CellDweller's transformation will be set based
         // on its coordinates in cell space.